@@ -24,7 +24,7 @@
 
 use std::path::PathBuf;
 
-use clap::ValueHint;
+use clap::{ValueEnum, ValueHint};
 
 #[derive(Parser)]
 pub struct Args {
@@ -67,4 +67,33 @@ pub enum Cmd {
         #[clap(value_hint = ValueHint::FilePath)]
         dst: Option<PathBuf>,
     },
+
+    /// Export the graph of contract operations
+    ///
+    /// Works for contract consignments and stockpiles
+    Graph {
+        /// Format of the exported graph
+        #[clap(short, long, value_enum, default_value_t)]
+        format: GraphFormat,
+
+        /// Source data to process
+        #[clap(value_hint = ValueHint::FilePath)]
+        src: PathBuf,
+
+        /// Destination file to write the graph to
+        ///
+        /// If skipped, prints the graph to STDOUT.
+        #[clap(value_hint = ValueHint::FilePath)]
+        dst: Option<PathBuf>,
+    },
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default, ValueEnum)]
+pub enum GraphFormat {
+    /// Graphviz DOT format
+    #[default]
+    Dot,
+
+    /// GraphML format
+    Graphml,
 }
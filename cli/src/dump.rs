@@ -25,14 +25,16 @@
 use std::convert::Infallible;
 use std::fs;
 use std::fs::File;
+use std::marker::PhantomData;
 use std::path::Path;
 
 use amplify::confinement::{SmallBlob, SmallOrdMap, TinyVec};
 use binfile::BinFile;
 use hypersonic::Operation;
 use rgb::{
-    parse_consignment, Articles, Contract, Issue, PublishedWitness, RgbSeal, RgbSealDef,
-    SealWitness, Semantics, SigBlob, SingleUseSeal, CONSIGN_MAGIC_NUMBER, CONSIGN_VERSION,
+    parse_consignment, Articles, Contract, ContractId, Issue, PublishedWitness, RgbSealDef,
+    SealIndex, SealWitness, Semantics, SigBlob, SingleUseSeal, CONSIGN_MAGIC_NUMBER,
+    CONSIGN_VERSION,
 };
 use rgb_persist_fs::{PileFs, StockFs};
 use serde::{Deserialize, Serialize};
@@ -45,7 +47,7 @@ pub fn dump_stockpile<Seal>(
     force: bool,
 ) -> anyhow::Result<()>
 where
-    Seal: RgbSeal + Serialize + for<'de> Deserialize<'de>,
+    Seal: SealIndex + Serialize + for<'de> Deserialize<'de>,
     Seal::Definition: Serialize + for<'de> Deserialize<'de>,
     Seal::Client: Serialize + StrictEncode + StrictDecode,
     Seal::Published: Eq + Serialize + StrictEncode + StrictDecode,
@@ -93,6 +95,108 @@ where
     Ok(())
 }
 
+/// Operation read from a consignment stream, together with the seals it defines and its witness.
+pub struct ConsignedOp<SealDef: RgbSealDef> {
+    pub operation: Operation,
+    pub defined_seals: SmallOrdMap<u16, SealDef>,
+    pub witness: Option<SealWitness<SealDef::Src>>,
+}
+
+/// Consignment file read without validation.
+///
+/// The contract articles and the seals defined by the genesis are read on opening; the rest of
+/// the operations are read lazily by iterating over [`Self::operations`].
+pub struct ConsignmentReader<SealDef: RgbSealDef> {
+    pub contract_id: ContractId,
+    pub articles: Articles,
+    pub genesis_seals: SmallOrdMap<u16, SealDef>,
+    pub operations: ConsignedOps<SealDef>,
+}
+
+impl<SealDef> ConsignmentReader<SealDef>
+where
+    SealDef: RgbSealDef,
+    <SealDef::Src as SingleUseSeal>::CliWitness: StrictDecode,
+    <SealDef::Src as SingleUseSeal>::PubWitness: StrictDecode,
+    <<SealDef::Src as SingleUseSeal>::PubWitness as PublishedWitness<SealDef::Src>>::PubId:
+        StrictDecode,
+{
+    pub fn open(src: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let file = BinFile::<CONSIGN_MAGIC_NUMBER, CONSIGN_VERSION>::open(src)?;
+        let mut stream = StrictReader::with(StreamReader::new::<{ usize::MAX }>(file));
+
+        let contract_id = parse_consignment(&mut stream).map_err(|e| anyhow!(e.to_string()))?;
+        // Skipping extension blocks
+        let _ = TinyVec::<SmallBlob>::strict_decode(&mut stream)?;
+
+        let semantics = Semantics::strict_decode(&mut stream)?;
+        let sig = Option::<SigBlob>::strict_decode(&mut stream)?;
+        let issue = Issue::strict_decode(&mut stream)?;
+        let articles =
+            Articles::with(semantics, issue, sig, |_, _, _| Result::<_, Infallible>::Ok(()))?;
+
+        let genesis_seals = SmallOrdMap::<u16, SealDef>::strict_decode(&mut stream)?;
+        if bool::strict_decode(&mut stream)? {
+            bail!("Consignment stream has witnesses for genesis, but zero witnesses are expected");
+        }
+
+        let remaining = u32::strict_decode(&mut stream)?;
+        let operations = ConsignedOps { stream, remaining, _phantom: PhantomData };
+        Ok(Self { contract_id, articles, genesis_seals, operations })
+    }
+}
+
+/// Iterator over the operations following the genesis in a consignment stream.
+pub struct ConsignedOps<SealDef: RgbSealDef> {
+    stream: StrictReader<StreamReader<BinFile<CONSIGN_MAGIC_NUMBER, CONSIGN_VERSION>>>,
+    remaining: u32,
+    _phantom: PhantomData<SealDef>,
+}
+
+impl<SealDef> ConsignedOps<SealDef>
+where
+    SealDef: RgbSealDef,
+    <SealDef::Src as SingleUseSeal>::CliWitness: StrictDecode,
+    <SealDef::Src as SingleUseSeal>::PubWitness: StrictDecode,
+    <<SealDef::Src as SingleUseSeal>::PubWitness as PublishedWitness<SealDef::Src>>::PubId:
+        StrictDecode,
+{
+    fn read_operation(&mut self) -> anyhow::Result<ConsignedOp<SealDef>> {
+        let operation = Operation::strict_decode(&mut self.stream)?;
+        let defined_seals = SmallOrdMap::<u16, SealDef>::strict_decode(&mut self.stream)?;
+        let witness = if bool::strict_decode(&mut self.stream)? {
+            Some(SealWitness::<SealDef::Src>::strict_decode(&mut self.stream)?)
+        } else {
+            None
+        };
+        Ok(ConsignedOp { operation, defined_seals, witness })
+    }
+}
+
+impl<SealDef> Iterator for ConsignedOps<SealDef>
+where
+    SealDef: RgbSealDef,
+    <SealDef::Src as SingleUseSeal>::CliWitness: StrictDecode,
+    <SealDef::Src as SingleUseSeal>::PubWitness: StrictDecode,
+    <<SealDef::Src as SingleUseSeal>::PubWitness as PublishedWitness<SealDef::Src>>::PubId:
+        StrictDecode,
+{
+    type Item = anyhow::Result<ConsignedOp<SealDef>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let item = self.read_operation();
+        if item.is_err() {
+            // The stream position is unknown after a failure, so we can't continue reading
+            self.remaining = 0;
+        }
+        Some(item)
+    }
+}
+
 pub fn dump_consignment<SealDef>(
     src: impl AsRef<Path>,
     dst: impl AsRef<Path>,
@@ -106,7 +210,7 @@ where
     <SealDef::Src as SingleUseSeal>::PubWitness:
         Eq + Serialize + for<'de> Deserialize<'de> + StrictEncode + StrictDecode,
     <<SealDef::Src as SingleUseSeal>::PubWitness as PublishedWitness<SealDef::Src>>::PubId:
-        Ord + From<[u8; 32]> + Into<[u8; 32]> + Serialize,
+        Ord + From<[u8; 32]> + Into<[u8; 32]> + Serialize + StrictDecode,
 {
     let src = src.as_ref();
     let dst = dst.as_ref();
@@ -115,61 +219,33 @@ where
     }
     fs::create_dir_all(dst)?;
 
-    let file = BinFile::<CONSIGN_MAGIC_NUMBER, CONSIGN_VERSION>::open(src)?;
-    let mut stream = StrictReader::with(StreamReader::new::<{ usize::MAX }>(file));
-
-    let contract_id = parse_consignment(&mut stream).map_err(|e| anyhow!(e.to_string()))?;
-    println!("Dumping consignment for {} into '{}'", contract_id, dst.display());
+    print!("Processing contract articles ... ");
+    let consignment = ConsignmentReader::<SealDef>::open(src).inspect_err(|_| println!("error"))?;
+    println!("success");
+    println!("Dumping consignment for {} into '{}'", consignment.contract_id, dst.display());
 
     let mut seal_count = 0;
     let mut witness_count = 0;
 
-    println!("Skipping extension blocks ... ");
-    let _ = TinyVec::<SmallBlob>::strict_decode(&mut stream)?;
-
-    print!("Processing contract articles ... ");
-
-    let semantics = Semantics::strict_decode(&mut stream)?;
-    let sig = Option::<SigBlob>::strict_decode(&mut stream)?;
-    let issue = Issue::strict_decode(&mut stream)?;
-    let articles =
-        Articles::with(semantics, issue, sig, |_, _, _| Result::<_, Infallible>::Ok(()))?;
-
-    let genesis_opid = dump_articles(&articles, dst)?;
+    let genesis_opid = dump_articles(&consignment.articles, dst)?;
     let out = File::create_new(dst.join(format!("0000-seals-{genesis_opid}.yml")))?;
-    let defined_seals = SmallOrdMap::<u16, SealDef>::strict_decode(&mut stream)
-        .expect("Failed to read the consignment stream");
-    serde_yaml::to_writer(&out, &defined_seals)?;
-    seal_count += defined_seals.len();
-
-    let count = bool::strict_decode(&mut stream)?;
-    if count {
-        println!("error");
-        bail!(
-            "Consignment stream has {count} witnesses for genesis, but zero witnesses are expected",
-        );
-    }
-    println!("success");
+    serde_yaml::to_writer(&out, &consignment.genesis_seals)?;
+    seal_count += consignment.genesis_seals.len();
 
-    let count = u32::strict_decode(&mut stream)?;
     println!();
-    for i in 0..count {
+    for (i, op) in consignment.operations.enumerate() {
+        let ConsignedOp { operation, defined_seals, witness } = op?;
         let op_count = i + 1;
-        let operation = Operation::strict_decode(&mut stream)?;
         let opid = operation.opid();
 
         let out = File::create_new(dst.join(format!("{op_count:04}-op-{opid}.yaml")))?;
         serde_yaml::to_writer(&out, &operation)?;
 
         let out = File::create_new(dst.join(format!("{op_count:04}-seals-{opid}.yml")))?;
-        let defined_seals = SmallOrdMap::<u16, SealDef>::strict_decode(&mut stream)
-            .expect("Failed to read the consignment stream");
         serde_yaml::to_writer(&out, &defined_seals)?;
         seal_count += defined_seals.len();
 
-        let witness = bool::strict_decode(&mut stream)?;
-        if witness {
-            let witness = SealWitness::<SealDef::Src>::strict_decode(&mut stream)?;
+        if let Some(witness) = witness {
             let out = File::create_new(
                 dst.join(format!("{op_count:04}-witness-{}.yaml", witness.published.pub_id())),
             )?;
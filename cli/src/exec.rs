@@ -23,14 +23,16 @@
 // the License.
 
 use std::convert::Infallible;
+use std::fs;
 use std::io::stdout;
 
 use bp::seals::{TxoSeal, WTxoSeal};
 use rgb::popls::bp::PrefabBundle;
 use rgb::Issuer;
 
-use crate::cmd::{Args, Cmd};
+use crate::cmd::{Args, Cmd, GraphFormat};
 use crate::dump::{dump_consignment, dump_stockpile};
+use crate::graph::{consignment_graph, stockpile_graph};
 
 impl Args {
     pub fn exec(&self) -> anyhow::Result<()> {
@@ -115,6 +117,32 @@ impl Args {
                     ))
                 }
             },
+            Cmd::Graph { format, src, dst } => {
+                let graph = match src.extension() {
+                    Some(ext) if ext == "rgb" => consignment_graph::<WTxoSeal>(src)?,
+                    Some(ext) if ext == "contract" => stockpile_graph::<TxoSeal>(src)?,
+                    Some(_) => {
+                        return Err(anyhow!(
+                            "Can't detect the type for '{}': the extension is not recognized",
+                            src.display()
+                        ))
+                    }
+                    None => {
+                        return Err(anyhow!(
+                            "The path '{}' can't be recognized as known data",
+                            src.display()
+                        ))
+                    }
+                };
+                let graph = match format {
+                    GraphFormat::Dot => graph.to_dot(),
+                    GraphFormat::Graphml => graph.to_graphml(),
+                };
+                match dst {
+                    Some(dst) => fs::write(dst, graph)?,
+                    None => print!("{graph}"),
+                }
+            }
         }
         Ok(())
     }
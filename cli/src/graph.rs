@@ -0,0 +1,76 @@
+// RGB command-line toolbox utility
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2019-2025 by Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+// Written in 2024-2025 by Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 LNP/BP Laboratories,
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2025 RGB Consortium, Switzerland.
+// Copyright (C) 2019-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+use std::path::Path;
+
+use rgb::{
+    Contract, OpGraph, PublishedWitness, RgbSealDef, SealIndex, SingleUseSeal, WitnessStatus,
+};
+use rgb_persist_fs::{PileFs, StockFs};
+use strict_encoding::{StrictDecode, StrictEncode};
+
+use crate::dump::ConsignmentReader;
+
+pub fn stockpile_graph<Seal>(src: impl AsRef<Path>) -> anyhow::Result<OpGraph>
+where
+    Seal: SealIndex,
+    Seal::Client: StrictEncode + StrictDecode,
+    Seal::Published: Eq + StrictEncode + StrictDecode,
+    Seal::WitnessId: From<[u8; 32]> + Into<[u8; 32]>,
+{
+    let path = src.as_ref().to_path_buf();
+    let contract = Contract::<StockFs, PileFs<Seal>>::load(path.clone(), path)?;
+    Ok(contract.graph())
+}
+
+/// Constructs the operation graph from a consignment without validating it.
+///
+/// Since the consignment doesn't contain witness status information, the status and validity
+/// are known only for the genesis.
+pub fn consignment_graph<SealDef>(src: impl AsRef<Path>) -> anyhow::Result<OpGraph>
+where
+    SealDef: RgbSealDef,
+    <SealDef::Src as SingleUseSeal>::CliWitness: StrictDecode,
+    <SealDef::Src as SingleUseSeal>::PubWitness: StrictDecode,
+    <<SealDef::Src as SingleUseSeal>::PubWitness as PublishedWitness<SealDef::Src>>::PubId:
+        StrictDecode,
+{
+    let consignment = ConsignmentReader::<SealDef>::open(src)?;
+    let articles = &consignment.articles;
+
+    let mut graph = OpGraph::default();
+    let genesis = articles.genesis().to_operation(consignment.contract_id);
+    graph.add_operation(
+        articles,
+        articles.genesis_opid(),
+        &genesis,
+        Some(WitnessStatus::Genesis),
+        Some(true),
+    );
+    for op in consignment.operations {
+        let operation = op?.operation;
+        graph.add_operation(articles, operation.opid(), &operation, None, None);
+    }
+    Ok(graph)
+}
@@ -32,6 +32,7 @@ extern crate anyhow;
 pub mod cmd;
 mod exec;
 mod dump;
+mod graph;
 
 use clap::Parser;
 
@@ -22,14 +22,21 @@
 // or implied. See the License for the specific language governing permissions and limitations under
 // the License.
 
-use std::io;
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom, Write};
 use std::marker::PhantomData;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use amplify::confinement::SmallOrdMap;
+use amplify::Bytes32;
 use aora::file::{FileAoraIndex, FileAoraMap, FileAuraMap};
 use aora::{AoraIndex, AoraMap, AuraMap, TransactionalMap};
-use rgb::{CellAddr, OpRels, Opid, Pile, RgbSeal, Witness, WitnessStatus};
+use binfile::BinFile;
+use rgb::{
+    BlockInfo, CellAddr, OpRels, Opid, Pile, RgbSeal, RgbSealDef, SealIndex, Witness, WitnessSeen,
+    WitnessStatus,
+};
 use strict_encoding::{StrictDecode, StrictEncode};
 
 const HOARD_MAGIC: u64 = u64::from_be_bytes(*b"RGBHOARD");
@@ -38,6 +45,133 @@ const KEEP_MAGIC: u64 = u64::from_be_bytes(*b"RGBKEEPS");
 const INDEX_MAGIC: u64 = u64::from_be_bytes(*b"RGBINDEX");
 const STAND_MAGIC: u64 = u64::from_be_bytes(*b"RGBSTAND");
 const MINE_MAGIC: u64 = u64::from_be_bytes(*b"RGBMINES");
+const SEALS_MAGIC: u64 = u64::from_be_bytes(*b"RGBSEALS");
+const BLOCKS_MAGIC: u64 = u64::from_be_bytes(*b"RGBBLOCK");
+const SEEN_MAGIC: u64 = u64::from_be_bytes(*b"RGBSEENS");
+
+const SEALS_FILE: &str = "seals.log";
+const SEAL_RECORD_LEN: usize = 32 + 34;
+
+/// Index of the cells by the primary component of their seals, kept in memory and persisted as an
+/// append-only log of records.
+///
+/// The index is derived from the seal definitions and the witnesses, so if the log is absent or
+/// ends with an incomplete record, the index is rebuilt in memory, and the log is re-written as a
+/// whole on the next update.
+#[derive(Debug)]
+struct SealsFs {
+    path: PathBuf,
+    cache: HashMap<[u8; 32], BTreeSet<CellAddr>>,
+    stored: bool,
+}
+
+impl SealsFs {
+    fn create_new(path: &Path) -> io::Result<Self> {
+        let path = path.join(SEALS_FILE);
+        BinFile::<SEALS_MAGIC, 1>::create_new(&path)?;
+        Ok(Self { path, cache: HashMap::new(), stored: true })
+    }
+
+    /// Reads the index from the log; if the log is absent or incomplete, the returned index is
+    /// empty and not stored, and has to be rebuilt.
+    fn open(path: &Path) -> io::Result<Self> {
+        let path = path.join(SEALS_FILE);
+        let mut file = match BinFile::<SEALS_MAGIC, 1>::open(&path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                return Ok(Self { path, cache: HashMap::new(), stored: false });
+            }
+            Err(err) => return Err(err),
+        };
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+        let records = data.chunks_exact(SEAL_RECORD_LEN);
+        if !records.remainder().is_empty() {
+            return Ok(Self { path, cache: HashMap::new(), stored: false });
+        }
+        let mut index = Self { path, cache: HashMap::new(), stored: true };
+        for record in records {
+            let (key, addr) = record.split_at(32);
+            let key = <[u8; 32]>::try_from(key).expect("fixed record length");
+            let addr = <[u8; 34]>::try_from(addr).expect("fixed record length");
+            index.insert(key, CellAddr::from(addr));
+        }
+        Ok(index)
+    }
+
+    fn get(&self, key: [u8; 32]) -> impl Iterator<Item = CellAddr> + '_ {
+        self.cache.get(&key).into_iter().flatten().copied()
+    }
+
+    /// Adds the record to the in-memory index only, returning whether it was absent.
+    fn insert(&mut self, key: [u8; 32], addr: CellAddr) -> bool {
+        self.cache.entry(key).or_default().insert(addr)
+    }
+
+    /// Adds the records to the index and persists the new ones.
+    fn extend(
+        &mut self,
+        records: impl IntoIterator<Item = ([u8; 32], CellAddr)>,
+    ) -> io::Result<()> {
+        let added = records
+            .into_iter()
+            .filter(|(key, addr)| self.insert(*key, *addr))
+            .collect::<Vec<_>>();
+        if !self.stored {
+            return self.save();
+        }
+        if added.is_empty() {
+            return Ok(());
+        }
+        let mut data = Vec::with_capacity(added.len() * SEAL_RECORD_LEN);
+        for (key, addr) in added {
+            data.extend_from_slice(&key);
+            data.extend_from_slice(&<[u8; 34]>::from(addr));
+        }
+        let mut file = BinFile::<SEALS_MAGIC, 1>::open_rw(&self.path)?;
+        file.seek(SeekFrom::End(0))?;
+        file.write_all(&data)
+    }
+
+    /// Writes the whole index into a temporary file, which then replaces the log, such that an
+    /// interrupted write never leaves a partial index behind.
+    fn save(&mut self) -> io::Result<()> {
+        let tmp = self.path.with_extension("tmp");
+        let mut data = Vec::with_capacity(SEAL_RECORD_LEN * self.cache.len());
+        for (key, addrs) in &self.cache {
+            for addr in addrs {
+                data.extend_from_slice(key);
+                data.extend_from_slice(&<[u8; 34]>::from(*addr));
+            }
+        }
+        let mut file = BinFile::<SEALS_MAGIC, 1>::create(&tmp)?;
+        file.write_all(&data)?;
+        file.sync_all()?;
+        fs::rename(&tmp, &self.path)?;
+        self.stored = true;
+        Ok(())
+    }
+}
+
+/// Optional block details as stored in the pile; the minimal timestamp marks the absent details.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+struct StoredBlock(Option<BlockInfo>);
+
+impl From<[u8; 44]> for StoredBlock {
+    fn from(value: [u8; 44]) -> Self {
+        let block = BlockInfo::from(value);
+        Self((block.timestamp != i64::MIN).then_some(block))
+    }
+}
+
+impl From<StoredBlock> for [u8; 44] {
+    fn from(value: StoredBlock) -> Self {
+        value
+            .0
+            .unwrap_or(BlockInfo::new(Bytes32::zero(), i64::MIN))
+            .into()
+    }
+}
 
 #[derive(Debug)]
 pub struct PileFs<Seal: RgbSeal>
@@ -49,10 +183,62 @@ where Seal::WitnessId: From<[u8; 32]> + Into<[u8; 32]>
     index: FileAoraIndex<Opid, Seal::WitnessId, INDEX_MAGIC, 1>,
     stand: FileAoraIndex<Seal::WitnessId, Opid, STAND_MAGIC, 1>,
     mine: FileAuraMap<Seal::WitnessId, WitnessStatus, MINE_MAGIC, 1, 32, 8>,
+    seals: SealsFs,
+    // The block details and the witness tracking were added after the initial release, so the
+    // logs may be absent from older piles; they are created on the first write.
+    blocks: Option<FileAuraMap<Seal::WitnessId, StoredBlock, BLOCKS_MAGIC, 1, 32, 44>>,
+    seen: Option<FileAuraMap<Seal::WitnessId, WitnessSeen, SEEN_MAGIC, 1, 32, 17>>,
+    path: PathBuf,
     _phantom: PhantomData<Seal>,
 }
 
-impl<Seal: RgbSeal> Pile for PileFs<Seal>
+impl<Seal: SealIndex> PileFs<Seal>
+where
+    Seal::Client: StrictEncode + StrictDecode,
+    Seal::Published: Eq + StrictEncode + StrictDecode,
+    Seal::WitnessId: From<[u8; 32]> + Into<[u8; 32]>,
+{
+    /// Primary component index records for the seal, resolving it with each of the witnesses
+    /// known for the operation if the seal depends on the witness.
+    fn seal_records(&self, addr: CellAddr, seal: &Seal::Definition) -> Vec<([u8; 32], CellAddr)> {
+        if let Some(src) = seal.to_src() {
+            return vec![(Seal::index_key(src.primary()), addr)];
+        }
+        self.index
+            .get(addr.opid)
+            .map(|wid| (Seal::index_key(seal.resolve(wid).primary()), addr))
+            .collect()
+    }
+
+    fn blocks_mut(
+        &mut self,
+    ) -> &mut FileAuraMap<Seal::WitnessId, StoredBlock, BLOCKS_MAGIC, 1, 32, 44> {
+        let path = &self.path;
+        self.blocks.get_or_insert_with(|| {
+            FileAuraMap::open_or_create(path, "blocks.dat").expect("Cannot create the log file")
+        })
+    }
+
+    fn seen_mut(
+        &mut self,
+    ) -> &mut FileAuraMap<Seal::WitnessId, WitnessSeen, SEEN_MAGIC, 1, 32, 17> {
+        let path = &self.path;
+        self.seen.get_or_insert_with(|| {
+            FileAuraMap::open_or_create(path, "seen.dat").expect("Cannot create the log file")
+        })
+    }
+}
+
+/// Opens the log, returning `None` if the log doesn't exist.
+fn open_log<T>(open: io::Result<T>) -> io::Result<Option<T>> {
+    match open {
+        Ok(log) => Ok(Some(log)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+impl<Seal: SealIndex> Pile for PileFs<Seal>
 where
     Seal::Client: StrictEncode + StrictDecode,
     Seal::Published: Eq + StrictEncode + StrictDecode,
@@ -71,6 +257,9 @@ where
         let index = FileAoraIndex::create_new(&path, "index.dat")?;
         let stand = FileAoraIndex::create_new(&path, "stand.dat")?;
         let mine = FileAuraMap::create_new(&path, "mine.dat")?;
+        let seals = SealsFs::create_new(&path)?;
+        let blocks = FileAuraMap::create_new(&path, "blocks.dat")?;
+        let seen = FileAuraMap::create_new(&path, "seen.dat")?;
 
         Ok(Self {
             hoard,
@@ -79,6 +268,10 @@ where
             index,
             stand,
             mine,
+            seals,
+            blocks: Some(blocks),
+            seen: Some(seen),
+            path,
             _phantom: PhantomData,
         })
     }
@@ -92,16 +285,34 @@ where
         let index = FileAoraIndex::open(&path, "index.dat")?;
         let stand = FileAoraIndex::open(&path, "stand.dat")?;
         let mine = FileAuraMap::open(&path, "mine.dat")?;
+        let seals = SealsFs::open(&path)?;
+        let blocks = open_log(FileAuraMap::open(&path, "blocks.dat"))?;
+        let seen = open_log(FileAuraMap::open(&path, "seen.dat"))?;
 
-        Ok(Self {
+        let mut pile = Self {
             hoard,
             cache,
             keep,
             index,
             stand,
             mine,
+            seals,
+            blocks,
+            seen,
+            path,
             _phantom: PhantomData,
-        })
+        };
+        // The index is absent from the piles created before it was introduced, or may be left
+        // incomplete by an interrupted write. It is then rebuilt in memory, and written to the
+        // disk only with the next update, such that loading a pile never modifies it.
+        if !pile.seals.stored {
+            for (addr, seal) in pile.keep.iter().collect::<Vec<_>>() {
+                for (key, addr) in pile.seal_records(addr, &seal) {
+                    pile.seals.insert(key, addr);
+                }
+            }
+        }
+        Ok(pile)
     }
 
     fn has_witness(&self, wid: Seal::WitnessId) -> bool { self.hoard.contains_key(wid) }
@@ -163,11 +374,37 @@ where
         opid: Opid,
         seals: SmallOrdMap<u16, <Self::Seal as RgbSeal>::Definition>,
     ) {
+        // The index is written before the seals, so it never misses a known seal
+        let records = seals
+            .iter()
+            .flat_map(|(no, seal)| self.seal_records(CellAddr::new(opid, *no), seal))
+            .collect::<Vec<_>>();
+        self.seals
+            .extend(records)
+            .expect("Cannot save the seal index");
         for (no, seal) in seals {
             self.keep.insert(CellAddr::new(opid, no), &seal)
         }
     }
 
+    fn index_witness_seals(
+        &mut self,
+        opid: Opid,
+        up_to: u16,
+        wid: <Self::Seal as RgbSeal>::WitnessId,
+    ) {
+        let records = self
+            .seals(opid, up_to)
+            .into_iter()
+            .map(|(no, seal)| {
+                (Seal::index_key(seal.resolve(wid).primary()), CellAddr::new(opid, no))
+            })
+            .collect::<Vec<_>>();
+        self.seals
+            .extend(records)
+            .expect("Cannot save the seal index");
+    }
+
     fn update_witness_status(
         &mut self,
         wid: <Self::Seal as RgbSeal>::WitnessId,
@@ -176,7 +413,37 @@ where
         self.mine.update_only(wid, status);
     }
 
-    fn commit_transaction(&mut self) { self.mine.commit_transaction(); }
+    fn witness_block(&self, wid: <Self::Seal as RgbSeal>::WitnessId) -> Option<BlockInfo> {
+        self.blocks.as_ref()?.get(wid).and_then(|block| block.0)
+    }
+
+    fn update_witness_block(
+        &mut self,
+        wid: <Self::Seal as RgbSeal>::WitnessId,
+        block: Option<BlockInfo>,
+    ) {
+        assert!(self.mine.contains_key(wid), "unknown witness");
+        self.blocks_mut().insert_or_update(wid, StoredBlock(block));
+    }
+
+    fn witness_seen(&self, wid: <Self::Seal as RgbSeal>::WitnessId) -> Option<WitnessSeen> {
+        self.seen.as_ref()?.get(wid)
+    }
+
+    fn update_witness_seen(&mut self, wid: <Self::Seal as RgbSeal>::WitnessId, seen: WitnessSeen) {
+        assert!(self.mine.contains_key(wid), "unknown witness");
+        self.seen_mut().insert_or_update(wid, seen);
+    }
+
+    fn commit_transaction(&mut self) {
+        self.mine.commit_transaction();
+        if let Some(blocks) = &mut self.blocks {
+            blocks.commit_transaction();
+        }
+        if let Some(seen) = &mut self.seen {
+            seen.commit_transaction();
+        }
+    }
 
     fn witnesses(&self) -> impl Iterator<Item = Witness<Self::Seal>> {
         self.hoard.iter().map(|(wid, client)| {
@@ -187,6 +454,25 @@ where
         })
     }
 
+    fn cells_by_primary(&self, primary: Seal::Primary) -> Option<impl Iterator<Item = CellAddr>> {
+        let cells = self
+            .seals
+            .get(Seal::index_key(primary))
+            .filter(move |addr| {
+                let Some(seal) = self.keep.get(*addr) else {
+                    return false;
+                };
+                match seal.to_src() {
+                    Some(src) => src.primary() == primary,
+                    None => self
+                        .index
+                        .get(addr.opid)
+                        .any(|wid| seal.resolve(wid).primary() == primary),
+                }
+            });
+        Some(cells)
+    }
+
     fn op_relations(&self, opid: Opid, up_to: u16) -> OpRels<Self::Seal> {
         let seals = self.seals(opid, up_to);
         let witness_ids = self.index.get(opid).collect();
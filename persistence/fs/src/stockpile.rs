@@ -22,7 +22,7 @@
 // or implied. See the License for the specific language governing permissions and limitations under
 // the License.
 
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::convert::Infallible;
 use std::ffi::OsStr;
 use std::marker::PhantomData;
@@ -30,16 +30,27 @@ use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::{fs, io};
 
-use amplify::MultiError;
+use amplify::{Bytes32, MultiError};
 use rgb::{
     Articles, CodexId, Consensus, Consignment, ConsumeError, Contract, ContractId, CreateParams,
-    Issuer, IssuerError, Pile, RgbSeal, Stock, Stockpile,
+    Issuer, IssuerError, Pile, RgbSeal, SealIndex, Stock, Stockpile, SyncCheckpoint,
 };
 use sonic_persist_fs::{FsError, StockFs};
 use strict_encoding::{StrictDecode, StrictEncode};
 
 use crate::PileFs;
 
+/// Name of the file inside the contract directory keeping the sync checkpoint.
+const SYNC_CHECKPOINT_FILE: &str = "sync.dat";
+/// Name of the temporary file used to atomically replace the sync checkpoint.
+const SYNC_CHECKPOINT_TMP_FILE: &str = "sync.dat.tmp";
+/// The magic number used in storing the sync checkpoint as a binary file.
+const SYNC_CHECKPOINT_MAGIC_NUMBER: u64 = u64::from_be_bytes(*b"RGBSYNCP");
+const SYNC_CHECKPOINT_VERSION: u16 = 0;
+/// Length of the sync checkpoint file before the list of the pending witnesses: magic number,
+/// version, tip height, tip hash, number of confirmations and the number of pending witnesses.
+const SYNC_CHECKPOINT_HEADER_LEN: usize = 8 + 2 + 8 + 32 + 4 + 4;
+
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct StockpileDir<Seal: RgbSeal> {
     consensus: Consensus,
@@ -116,7 +127,7 @@ impl<Seal: RgbSeal> StockpileDir<Seal> {
     }
 }
 
-impl<Seal: RgbSeal> Stockpile for StockpileDir<Seal>
+impl<Seal: SealIndex> Stockpile for StockpileDir<Seal>
 where
     Seal::Client: StrictEncode + StrictDecode,
     Seal::Published: Eq + StrictEncode + StrictDecode,
@@ -216,4 +227,73 @@ where
             .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Contract not found"))?;
         fs::remove_dir_all(&path)
     }
+
+    fn sync_checkpoint(&self, contract_id: ContractId) -> Option<SyncCheckpoint<Seal::WitnessId>> {
+        let path = self.get_contract_dir(contract_id)?;
+        let data = fs::read(path.join(SYNC_CHECKPOINT_FILE)).ok()?;
+        let header = data.get(..SYNC_CHECKPOINT_HEADER_LEN)?;
+        let u64_at =
+            |pos: usize| u64::from_be_bytes(header[pos..pos + 8].try_into().expect("fixed length"));
+        let u32_at =
+            |pos: usize| u32::from_be_bytes(header[pos..pos + 4].try_into().expect("fixed length"));
+        if u64_at(0) != SYNC_CHECKPOINT_MAGIC_NUMBER
+            || u16::from_be_bytes([header[8], header[9]]) != SYNC_CHECKPOINT_VERSION
+        {
+            return None;
+        }
+        let tip_height = u64_at(10);
+        let tip_hash = Bytes32::from_slice_checked(&header[18..50]);
+        let min_confirmations = u32_at(50);
+        let count = u32_at(54) as usize;
+        // Truncated or otherwise damaged files are ignored, forcing the full sync
+        let pending = &data[SYNC_CHECKPOINT_HEADER_LEN..];
+        if pending.len() != count.checked_mul(32)? {
+            return None;
+        }
+        let pending = pending
+            .chunks_exact(32)
+            .map(|chunk| Seal::WitnessId::from(chunk.try_into().expect("fixed length")))
+            .collect::<BTreeSet<_>>();
+        Some(SyncCheckpoint { tip_height, tip_hash, min_confirmations, pending })
+    }
+
+    fn set_sync_checkpoint(
+        &mut self,
+        contract_id: ContractId,
+        checkpoint: Option<&SyncCheckpoint<Seal::WitnessId>>,
+    ) -> Result<(), io::Error> {
+        let Some(dir) = self.get_contract_dir(contract_id) else {
+            return match checkpoint {
+                None => Ok(()),
+                Some(_) => Err(io::Error::new(io::ErrorKind::NotFound, "Contract not found")),
+            };
+        };
+        let path = dir.join(SYNC_CHECKPOINT_FILE);
+        let Some(checkpoint) = checkpoint else {
+            return match fs::remove_file(path) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+                _ => Ok(()),
+            };
+        };
+        let count = u32::try_from(checkpoint.pending.len()).map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidInput, "too many pending witnesses")
+        })?;
+        let mut data =
+            Vec::with_capacity(SYNC_CHECKPOINT_HEADER_LEN + checkpoint.pending.len() * 32);
+        data.extend_from_slice(&SYNC_CHECKPOINT_MAGIC_NUMBER.to_be_bytes());
+        data.extend_from_slice(&SYNC_CHECKPOINT_VERSION.to_be_bytes());
+        data.extend_from_slice(&checkpoint.tip_height.to_be_bytes());
+        data.extend_from_slice(checkpoint.tip_hash.as_slice());
+        data.extend_from_slice(&checkpoint.min_confirmations.to_be_bytes());
+        data.extend_from_slice(&count.to_be_bytes());
+        for wid in &checkpoint.pending {
+            let wid: [u8; 32] = (*wid).into();
+            data.extend_from_slice(&wid);
+        }
+        // The checkpoint is replaced atomically, so an interrupted write never leaves a partial
+        // file
+        let tmp = dir.join(SYNC_CHECKPOINT_TMP_FILE);
+        fs::write(&tmp, data)?;
+        fs::rename(tmp, path)
+    }
 }
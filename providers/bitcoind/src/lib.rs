@@ -0,0 +1,41 @@
+// Standard Library for RGB smart contracts
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2019-2025 by Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+// Written in 2024-2025 by Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 LNP/BP Laboratories,
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2025 RGB Consortium, Switzerland.
+// Copyright (C) 2019-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+//! Bitcoin Core JSON-RPC client, providing a [`rgb::popls::bp::WalletProvider`] implementation
+//! backed by a descriptor wallet of a Bitcoin Core node.
+//!
+//! The client uses plain HTTP connections: TLS termination, if required, has to be done by a
+//! proxy. The `async` variants of the provider methods are performing the same blocking I/O as
+//! their sync counterparts.
+
+#![cfg_attr(feature = "async", allow(async_fn_in_trait))]
+
+#[macro_use]
+extern crate amplify;
+
+mod rpc;
+mod wallet;
+
+pub use rpc::{RpcClient, RpcError, RPC_INVALID_ADDRESS_OR_KEY, RPC_INVALID_PARAMETER};
+pub use wallet::BitcoindWallet;
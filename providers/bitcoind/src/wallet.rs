@@ -0,0 +1,362 @@
+// Standard Library for RGB smart contracts
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2019-2025 by Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+// Written in 2024-2025 by Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 LNP/BP Laboratories,
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2025 RGB Consortium, Switzerland.
+// Copyright (C) 2019-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::num::NonZeroU64;
+use std::str::FromStr;
+
+use amplify::{ByteArray, Bytes32};
+use bp::seals::WTxoSeal;
+use bp::{BlockHash, Outpoint, Tx, Txid, Vout};
+use rgb::invoice::bp::Address;
+use rgb::popls::bp::WalletProvider;
+use rgb::{AuthToken, BlockInfo, RgbSealDef, WitnessInfo, WitnessResolver, WitnessStatus};
+use serde_json::{json, Value};
+
+use crate::rpc::{RpcClient, RpcError, RPC_INVALID_ADDRESS_OR_KEY, RPC_INVALID_PARAMETER};
+
+/// Wallet backed by a descriptor wallet of a Bitcoin Core node, accessed via JSON-RPC.
+///
+/// The node wallet owns the keys: it provides the UTXOs (`listunspent`) and derives new addresses
+/// (`getnewaddress`), while the transactions are signed outside of this provider. The wallet
+/// statuses of the witness transactions are resolved with `getrawtransaction`, which requires
+/// either the node transaction index (`-txindex`) or the transaction to be in the mempool; for
+/// other transactions the resolver falls back to the wallet `gettransaction`, leaving the
+/// transactions unknown to the node wallet unresolved.
+///
+/// With the `async` feature the provider methods still perform blocking I/O, so they have to be
+/// called from a thread which can be blocked, like the one provided by `spawn_blocking` of the
+/// async runtime.
+#[derive(Clone, Debug)]
+pub struct BitcoindWallet {
+    rpc: RpcClient,
+    noise_seed: Bytes32,
+    utxos: BTreeSet<Outpoint>,
+    seals: BTreeMap<AuthToken, WTxoSeal>,
+    nonce: u64,
+}
+
+impl BitcoindWallet {
+    /// Constructs a wallet using the RPC client, which must be configured with the node wallet
+    /// path (see [`RpcClient::with_wallet`]) unless the node has a single wallet loaded.
+    ///
+    /// The `noise_seed` is used to blind the seals defined by the wallet and must be kept secret.
+    pub fn new(rpc: RpcClient, noise_seed: Bytes32) -> Self {
+        Self { rpc, noise_seed, utxos: none!(), seals: none!(), nonce: 0 }
+    }
+
+    /// The RPC client used by the wallet.
+    pub fn rpc(&self) -> &RpcClient { &self.rpc }
+
+    fn sync_utxos(&mut self) -> Result<(), RpcError> {
+        let unspent = self.rpc.call("listunspent", json!([0]))?;
+        let unspent = unspent
+            .as_array()
+            .ok_or_else(|| RpcError::InvalidResponse(s!("listunspent result is not an array")))?;
+        self.utxos = unspent
+            .iter()
+            .map(|utxo| {
+                let txid = utxo
+                    .get("txid")
+                    .and_then(Value::as_str)
+                    .and_then(|s| s.parse().ok());
+                let vout = utxo.get("vout").and_then(Value::as_u64);
+                match (txid, vout) {
+                    (Some(txid), Some(vout)) => Ok(Outpoint::new(txid, vout as u32)),
+                    _ => Err(RpcError::InvalidResponse(s!("invalid listunspent entry"))),
+                }
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(())
+    }
+
+    fn send_tx(&mut self, tx: &Tx) -> Result<(), RpcError> {
+        self.rpc
+            .call("sendrawtransaction", json!([format!("{tx:x}")]))?;
+        self.sync_utxos()
+    }
+
+    fn block_count(&self) -> Result<u64, RpcError> {
+        self.rpc
+            .call("getblockcount", json!([]))?
+            .as_u64()
+            .ok_or_else(|| RpcError::InvalidResponse(s!("invalid block count")))
+    }
+}
+
+impl WalletProvider for BitcoindWallet {
+    type Error = RpcError;
+
+    fn has_utxo(&self, outpoint: Outpoint) -> bool { self.utxos.contains(&outpoint) }
+
+    fn utxos(&self) -> impl Iterator<Item = Outpoint> { self.utxos.iter().copied() }
+
+    #[cfg(not(feature = "async"))]
+    fn update_utxos(&mut self) -> Result<(), Self::Error> { self.sync_utxos() }
+
+    #[cfg(feature = "async")]
+    async fn update_utxos_async(&mut self) -> Result<(), Self::Error> { self.sync_utxos() }
+
+    fn register_seal(&mut self, seal: WTxoSeal) { self.seals.insert(seal.auth_token(), seal); }
+
+    fn resolve_seals(
+        &self,
+        seals: impl Iterator<Item = AuthToken>,
+    ) -> impl Iterator<Item = WTxoSeal> {
+        seals.filter_map(|auth| self.seals.get(&auth).copied())
+    }
+
+    fn noise_seed(&self) -> Bytes32 { self.noise_seed }
+
+    /// Derives a new address with the node `getnewaddress` call.
+    ///
+    /// # Panics
+    ///
+    /// If the node is not reachable or returns an invalid address, since the address derivation
+    /// can't fail in the wallet provider API.
+    fn next_address(&mut self) -> Address {
+        let address = self
+            .rpc
+            .call("getnewaddress", json!([]))
+            .expect("unable to derive a new address with bitcoind");
+        address
+            .as_str()
+            .and_then(|s| Address::from_str(s).ok())
+            .expect("bitcoind returned an invalid address")
+    }
+
+    fn next_nonce(&mut self) -> u64 {
+        self.nonce += 1;
+        self.nonce
+    }
+
+    #[cfg(not(feature = "async"))]
+    fn txid_resolver(&self) -> impl WitnessResolver<Txid, Error = Self::Error> {
+        TxResolver(&self.rpc)
+    }
+
+    #[cfg(feature = "async")]
+    fn txid_resolver_async(&self) -> impl WitnessResolver<Txid, Error = Self::Error> {
+        TxResolver(&self.rpc)
+    }
+
+    #[cfg(not(feature = "async"))]
+    fn block_resolver(&self) -> impl Fn(u64) -> Result<Option<Bytes32>, Self::Error> {
+        |height| block_hash(&self.rpc, height)
+    }
+
+    #[cfg(feature = "async")]
+    fn block_resolver_async(&self) -> impl AsyncFn(u64) -> Result<Option<Bytes32>, Self::Error> {
+        async |height| block_hash(&self.rpc, height)
+    }
+
+    /// Finds the spending transactions with `gettxspendingprevout`, which reports the mempool
+    /// spends only; outputs spent by mined transactions are not resolved.
+    #[cfg(not(feature = "async"))]
+    fn spender_resolver(&self) -> impl Fn(Outpoint) -> Result<Option<Txid>, Self::Error> {
+        |outpoint| mempool_spender(&self.rpc, outpoint)
+    }
+
+    /// Finds the spending transactions with `gettxspendingprevout`, which reports the mempool
+    /// spends only; outputs spent by mined transactions are not resolved.
+    #[cfg(feature = "async")]
+    fn spender_resolver_async(
+        &self,
+    ) -> impl AsyncFn(Outpoint) -> Result<Option<Txid>, Self::Error> {
+        async |outpoint| mempool_spender(&self.rpc, outpoint)
+    }
+
+    #[cfg(not(feature = "async"))]
+    fn last_block_height(&self) -> Result<u64, Self::Error> { self.block_count() }
+
+    #[cfg(feature = "async")]
+    async fn last_block_height_async(&self) -> Result<u64, Self::Error> { self.block_count() }
+
+    /// Broadcasts the transaction with `sendrawtransaction`; the change output is tracked by the
+    /// node wallet, so the `change` argument is ignored.
+    #[cfg(not(feature = "async"))]
+    fn broadcast(&mut self, tx: &Tx, _change: Option<(Vout, u32, u32)>) -> Result<(), Self::Error> {
+        self.send_tx(tx)
+    }
+
+    /// Broadcasts the transaction with `sendrawtransaction`; the change output is tracked by the
+    /// node wallet, so the `change` argument is ignored.
+    #[cfg(feature = "async")]
+    async fn broadcast_async(
+        &mut self,
+        tx: &Tx,
+        _change: Option<(Vout, u32, u32)>,
+    ) -> Result<(), Self::Error> {
+        self.send_tx(tx)
+    }
+}
+
+fn parse_block_hash(value: &Value) -> Result<Bytes32, RpcError> {
+    value
+        .as_str()
+        .and_then(|s| BlockHash::from_str(s).ok())
+        .map(|hash| Bytes32::from_byte_array(hash.to_byte_array()))
+        .ok_or_else(|| RpcError::InvalidResponse(s!("invalid block hash")))
+}
+
+fn block_hash(rpc: &RpcClient, height: u64) -> Result<Option<Bytes32>, RpcError> {
+    match rpc.call("getblockhash", json!([height])) {
+        Ok(hash) => parse_block_hash(&hash).map(Some),
+        Err(err) if err.rpc_code() == Some(RPC_INVALID_PARAMETER) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+fn mempool_spender(rpc: &RpcClient, outpoint: Outpoint) -> Result<Option<Txid>, RpcError> {
+    let prevout = json!({ "txid": outpoint.txid.to_string(), "vout": outpoint.vout.into_u32() });
+    let spends = rpc.call("gettxspendingprevout", json!([[prevout]]))?;
+    let Some(spend) = spends.as_array().and_then(|spends| spends.first()) else {
+        return Err(RpcError::InvalidResponse(s!("invalid gettxspendingprevout result")));
+    };
+    match spend.get("spendingtxid").and_then(Value::as_str) {
+        None => Ok(None),
+        Some(txid) => Txid::from_str(txid)
+            .map(Some)
+            .map_err(|_| RpcError::InvalidResponse(s!("invalid spending txid"))),
+    }
+}
+
+/// Resolver of the witness transaction statuses, querying all the transactions with a constant
+/// number of batched RPC requests.
+struct TxResolver<'rpc>(&'rpc RpcClient);
+
+impl TxResolver<'_> {
+    fn resolve(&self, txids: &[Txid]) -> Result<HashMap<Txid, WitnessInfo>, RpcError> {
+        let rpc = self.0;
+        let mut resolved = HashMap::with_capacity(txids.len());
+        // Block hashes of the mined transactions
+        let mut mined = Vec::<(Txid, String)>::new();
+        let mut unknown = Vec::<Txid>::new();
+
+        let txs = rpc.call_batch(
+            txids
+                .iter()
+                .map(|txid| ("getrawtransaction", json!([txid.to_string(), true]))),
+        )?;
+        for (txid, tx) in txids.iter().zip(txs) {
+            match tx {
+                Ok(tx) => match tx.get("blockhash").and_then(Value::as_str) {
+                    Some(hash) => mined.push((*txid, hash.to_owned())),
+                    None => {
+                        resolved.insert(*txid, WitnessStatus::Tentative.into());
+                    }
+                },
+                Err(err) if err.rpc_code() == Some(RPC_INVALID_ADDRESS_OR_KEY) => {
+                    unknown.push(*txid)
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        // Without the transaction index, the mined transactions can be resolved only if they belong
+        // to the node wallet; the status of other transactions can't be determined, so they are
+        // left unresolved.
+        if !unknown.is_empty() {
+            let txs = rpc.call_batch(
+                unknown
+                    .iter()
+                    .map(|txid| ("gettransaction", json!([txid.to_string()]))),
+            )?;
+            for (txid, tx) in unknown.into_iter().zip(txs) {
+                let tx = match tx {
+                    Ok(tx) => tx,
+                    Err(err) if err.rpc_code() == Some(RPC_INVALID_ADDRESS_OR_KEY) => continue,
+                    Err(err) => return Err(err),
+                };
+                let confirmations = tx
+                    .get("confirmations")
+                    .and_then(Value::as_i64)
+                    .ok_or_else(|| RpcError::InvalidResponse(s!("invalid confirmations")))?;
+                match tx.get("blockhash").and_then(Value::as_str) {
+                    Some(hash) if confirmations > 0 => mined.push((txid, hash.to_owned())),
+                    // Conflicts with a mined transaction
+                    _ if confirmations < 0 => {
+                        resolved.insert(txid, WitnessStatus::Archived.into());
+                    }
+                    // Neither mined nor in the mempool, but still may be re-broadcasted
+                    _ => {}
+                }
+            }
+        }
+        if mined.is_empty() {
+            return Ok(resolved);
+        }
+
+        let hashes = mined
+            .iter()
+            .map(|(_, hash)| hash.as_str())
+            .collect::<BTreeSet<_>>();
+        let blocks = rpc.call_batch(hashes.iter().map(|hash| ("getblock", json!([hash, 1]))))?;
+        let blocks = hashes
+            .into_iter()
+            .zip(blocks)
+            .map(|(hash, block)| block.map(|block| (hash, block)))
+            .collect::<Result<HashMap<_, _>, _>>()?;
+        for (txid, hash) in &mined {
+            let block = &blocks[hash.as_str()];
+            let height = block
+                .get("height")
+                .and_then(Value::as_u64)
+                .and_then(NonZeroU64::new)
+                .ok_or_else(|| RpcError::InvalidResponse(s!("invalid block height")))?;
+            let time = block
+                .get("time")
+                .and_then(Value::as_i64)
+                .ok_or_else(|| RpcError::InvalidResponse(s!("invalid block time")))?;
+            let mut info = BlockInfo::new(parse_block_hash(&block["hash"])?, time);
+            let txid_str = txid.to_string();
+            if let Some(pos) = block
+                .get("tx")
+                .and_then(Value::as_array)
+                .and_then(|txs| txs.iter().position(|tx| tx.as_str() == Some(&txid_str)))
+            {
+                info = info.with_tx_pos(pos as u32);
+            }
+            resolved.insert(*txid, WitnessInfo::mined(height, info));
+        }
+        Ok(resolved)
+    }
+}
+
+impl WitnessResolver<Txid> for TxResolver<'_> {
+    type Error = RpcError;
+
+    #[cfg(not(feature = "async"))]
+    fn resolve_witnesses(&self, wids: &[Txid]) -> Result<HashMap<Txid, WitnessInfo>, RpcError> {
+        self.resolve(wids)
+    }
+
+    #[cfg(feature = "async")]
+    async fn resolve_witnesses_async(
+        &self,
+        wids: &[Txid],
+    ) -> Result<HashMap<Txid, WitnessInfo>, RpcError> {
+        self.resolve(wids)
+    }
+}
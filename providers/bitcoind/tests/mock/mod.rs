@@ -0,0 +1,365 @@
+//! Mock Bitcoin Core node serving the subset of the JSON-RPC API used by the wallet provider.
+
+use std::collections::BTreeSet;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use amplify::confinement::Confined;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use bp::{BlockHash, Outpoint, Sats, ScriptPubkey, SeqNo, Tx, TxIn, TxOut, Txid, WPubkeyHash};
+use rgb::invoice::bp::{Address, AddressNetwork, AddressPayload};
+use serde_json::{json, Value};
+
+pub const USER: &str = "rgb";
+pub const PASSWORD: &str = "secret";
+pub const GENESIS_TIME: i64 = 1_700_000_000;
+
+pub struct MockBlock {
+    pub hash: BlockHash,
+    pub time: i64,
+    pub txs: Vec<Tx>,
+}
+
+#[derive(Default)]
+pub struct MockNode {
+    pub blocks: Vec<MockBlock>,
+    pub mempool: Vec<Tx>,
+    pub txindex: bool,
+    pub scripts: BTreeSet<ScriptPubkey>,
+    /// Methods called in each of the HTTP requests
+    pub requests: Vec<Vec<String>>,
+    reorgs: u64,
+}
+
+impl MockNode {
+    pub fn new(txindex: bool) -> Self {
+        let mut node = MockNode { txindex, ..default!() };
+        node.mine();
+        node
+    }
+
+    pub fn height(&self) -> u64 { self.blocks.len() as u64 - 1 }
+
+    pub fn tip(&self) -> &MockBlock { self.blocks.last().unwrap() }
+
+    pub fn mine(&mut self) -> u64 { self.mine_with(vec![]) }
+
+    /// Mines a block with a coinbase-like transaction paying to the script pubkey.
+    pub fn fund(&mut self, script_pubkey: ScriptPubkey, sats: u64) -> Outpoint {
+        let tx = Tx {
+            version: default!(),
+            inputs: Confined::from_checked(vec![TxIn {
+                prev_output: Outpoint::new(Txid::from([self.blocks.len() as u8; 32]), 0u32),
+                sig_script: none!(),
+                sequence: SeqNo::from_consensus_u32(0xFFFF_FFFF),
+                witness: none!(),
+            }]),
+            outputs: Confined::from_checked(vec![TxOut::new(script_pubkey, Sats::from(sats))]),
+            lock_time: default!(),
+        };
+        let txid = tx.txid();
+        self.mine_with(vec![tx]);
+        Outpoint::new(txid, 0u32)
+    }
+
+    /// Removes `depth` blocks from the tip, returning their transactions to the mempool.
+    pub fn reorg(&mut self, depth: usize) {
+        for _ in 0..depth {
+            let block = self.blocks.pop().unwrap();
+            self.mempool.splice(0..0, block.txs);
+        }
+        self.reorgs += 1;
+    }
+
+    fn mine_with(&mut self, coinbase: Vec<Tx>) -> u64 {
+        let height = self.blocks.len() as u64;
+        let mut hash = [0u8; 32];
+        hash[..8].copy_from_slice(&height.to_le_bytes());
+        hash[8..16].copy_from_slice(&self.reorgs.to_le_bytes());
+        hash[31] = 0xFF;
+        let mut txs = coinbase;
+        txs.append(&mut self.mempool);
+        self.blocks.push(MockBlock {
+            hash: BlockHash::from(hash),
+            time: GENESIS_TIME + height as i64 * 600,
+            txs,
+        });
+        height
+    }
+
+    fn find_tx(&self, txid: Txid) -> Option<(&Tx, Option<usize>)> {
+        if let Some(tx) = self.mempool.iter().find(|tx| tx.txid() == txid) {
+            return Some((tx, None));
+        }
+        self.blocks.iter().enumerate().find_map(|(height, block)| {
+            block
+                .txs
+                .iter()
+                .find(|tx| tx.txid() == txid)
+                .map(|tx| (tx, Some(height)))
+        })
+    }
+
+    fn all_txs(&self) -> impl Iterator<Item = (&Tx, Option<usize>)> {
+        self.blocks
+            .iter()
+            .enumerate()
+            .flat_map(|(height, block)| block.txs.iter().map(move |tx| (tx, Some(height))))
+            .chain(self.mempool.iter().map(|tx| (tx, None)))
+    }
+
+    fn is_spent(&self, outpoint: Outpoint) -> bool {
+        self.all_txs()
+            .any(|(tx, _)| tx.inputs.iter().any(|input| input.prev_output == outpoint))
+    }
+
+    fn is_wallet_tx(&self, tx: &Tx) -> bool {
+        let is_mine = |outpoint: Outpoint| {
+            self.find_tx(outpoint.txid).is_some_and(|(tx, _)| {
+                tx.outputs
+                    .get(outpoint.vout.into_usize())
+                    .is_some_and(|out| self.scripts.contains(&out.script_pubkey))
+            })
+        };
+        tx.outputs
+            .iter()
+            .any(|out| self.scripts.contains(&out.script_pubkey))
+            || tx.inputs.iter().any(|input| is_mine(input.prev_output))
+    }
+
+    fn confirmations(&self, height: Option<usize>) -> u64 {
+        height
+            .map(|h| self.height() - h as u64 + 1)
+            .unwrap_or_default()
+    }
+
+    fn handle(&mut self, method: &str, params: &[Value]) -> Result<Value, (i64, &'static str)> {
+        const NOT_FOUND: (i64, &str) = (-5, "No such mempool or blockchain transaction");
+        let txid = || {
+            params[0]
+                .as_str()
+                .and_then(|s| Txid::from_str(s).ok())
+                .ok_or((-8, "invalid txid"))
+        };
+        match method {
+            "getblockcount" => Ok(json!(self.height())),
+            "getblockhash" => {
+                let height = params[0].as_u64().unwrap() as usize;
+                let block = self
+                    .blocks
+                    .get(height)
+                    .ok_or((-8, "Block height out of range"))?;
+                Ok(json!(block.hash.to_string()))
+            }
+            "getblock" => {
+                let hash = params[0].as_str().unwrap();
+                let (height, block) = self
+                    .blocks
+                    .iter()
+                    .enumerate()
+                    .find(|(_, block)| block.hash.to_string() == hash)
+                    .ok_or((-5, "Block not found"))?;
+                let txs = block
+                    .txs
+                    .iter()
+                    .map(|tx| tx.txid().to_string())
+                    .collect::<Vec<_>>();
+                Ok(json!({ "hash": hash, "height": height, "time": block.time, "tx": txs }))
+            }
+            "getrawtransaction" => {
+                let (tx, height) = self.find_tx(txid()?).ok_or(NOT_FOUND)?;
+                let mut res = json!({ "txid": tx.txid().to_string(), "hex": format!("{tx:x}") });
+                match height {
+                    Some(_) if !self.txindex => return Err(NOT_FOUND),
+                    Some(height) => {
+                        let block = &self.blocks[height];
+                        res["blockhash"] = json!(block.hash.to_string());
+                        res["blocktime"] = json!(block.time);
+                        res["confirmations"] = json!(self.confirmations(Some(height)));
+                    }
+                    None => {}
+                }
+                Ok(res)
+            }
+            "gettransaction" => {
+                const NOT_WALLET: (i64, &str) = (-5, "Invalid or non-wallet transaction id");
+                let (tx, height) = self.find_tx(txid()?).ok_or(NOT_WALLET)?;
+                if !self.is_wallet_tx(tx) {
+                    return Err(NOT_WALLET);
+                }
+                let mut res = json!({
+                    "txid": tx.txid().to_string(),
+                    "confirmations": self.confirmations(height),
+                    "hex": format!("{tx:x}"),
+                });
+                if let Some(height) = height {
+                    let block = &self.blocks[height];
+                    res["blockhash"] = json!(block.hash.to_string());
+                    res["blockheight"] = json!(height);
+                    res["blocktime"] = json!(block.time);
+                }
+                Ok(res)
+            }
+            "gettxspendingprevout" => {
+                let spends =
+                    params[0]
+                        .as_array()
+                        .unwrap()
+                        .iter()
+                        .map(|prevout| {
+                            let outpoint = Outpoint::new(
+                                prevout["txid"].as_str().unwrap().parse().unwrap(),
+                                prevout["vout"].as_u64().unwrap() as u32,
+                            );
+                            let mut spend = prevout.clone();
+                            if let Some(tx) = self.mempool.iter().find(|tx| {
+                                tx.inputs.iter().any(|input| input.prev_output == outpoint)
+                            }) {
+                                spend["spendingtxid"] = json!(tx.txid().to_string());
+                            }
+                            spend
+                        })
+                        .collect::<Vec<_>>();
+                Ok(json!(spends))
+            }
+            "listunspent" => {
+                let unspent =
+                    self.all_txs()
+                        .flat_map(|(tx, height)| {
+                            let txid = tx.txid();
+                            tx.outputs.iter().enumerate().map(move |(vout, out)| {
+                                (Outpoint::new(txid, vout as u32), out, height)
+                            })
+                        })
+                        .filter(|(outpoint, out, _)| {
+                            self.scripts.contains(&out.script_pubkey) && !self.is_spent(*outpoint)
+                        })
+                        .map(|(outpoint, out, height)| {
+                            json!({
+                                "txid": outpoint.txid.to_string(),
+                                "vout": outpoint.vout.into_u32(),
+                                "amount": out.value.sats(),
+                                "confirmations": self.confirmations(height),
+                            })
+                        })
+                        .collect::<Vec<_>>();
+                Ok(json!(unspent))
+            }
+            "getnewaddress" => {
+                let mut hash = [0u8; 20];
+                hash[..4].copy_from_slice(&(self.scripts.len() as u32).to_le_bytes());
+                let payload = AddressPayload::Wpkh(WPubkeyHash::from(hash));
+                self.scripts.insert(payload.script_pubkey());
+                Ok(json!(Address::new(payload, AddressNetwork::Regtest).to_string()))
+            }
+            "sendrawtransaction" => {
+                let tx = params[0]
+                    .as_str()
+                    .and_then(|s| Tx::from_str(s).ok())
+                    .ok_or((-22, "TX decode failed"))?;
+                if tx
+                    .inputs
+                    .iter()
+                    .any(|input| self.is_spent(input.prev_output))
+                {
+                    return Err((-26, "txn-mempool-conflict"));
+                }
+                let txid = tx.txid();
+                self.mempool.push(tx);
+                Ok(json!(txid.to_string()))
+            }
+            _ => Err((-32601, "Method not found")),
+        }
+    }
+
+    fn respond(&mut self, request: &Value) -> Value {
+        let method = request["method"].as_str().unwrap_or_default();
+        let params = request["params"].as_array().cloned().unwrap_or_default();
+        match self.handle(method, &params) {
+            Ok(result) => json!({ "result": result, "error": null, "id": request["id"] }),
+            Err((code, message)) => json!({
+                "result": null,
+                "error": { "code": code, "message": message },
+                "id": request["id"],
+            }),
+        }
+    }
+}
+
+/// Starts serving the node on a local port, returning the node URL.
+pub fn serve(node: Arc<Mutex<MockNode>>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            serve_conn(&node, stream.unwrap());
+        }
+    });
+    format!("http://{addr}")
+}
+
+fn serve_conn(node: &Mutex<MockNode>, mut stream: TcpStream) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut len = 0usize;
+    let mut authorized = false;
+    let expected = format!("Basic {}", BASE64.encode(format!("{USER}:{PASSWORD}")));
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(": ") {
+            match name.to_ascii_lowercase().as_str() {
+                "content-length" => len = value.parse().unwrap(),
+                "authorization" => authorized = value == expected,
+                _ => {}
+            }
+        }
+    }
+    let mut body = vec![0u8; len];
+    reader.read_exact(&mut body).unwrap();
+
+    let (status, body) = if !authorized {
+        (401, vec![])
+    } else {
+        let request: Value = serde_json::from_slice(&body).unwrap();
+        let mut node = node.lock().unwrap();
+        match request {
+            Value::Array(batch) => {
+                let methods = batch
+                    .iter()
+                    .map(|req| req["method"].as_str().unwrap_or_default().to_owned())
+                    .collect();
+                node.requests.push(methods);
+                let responses = batch
+                    .iter()
+                    .map(|req| node.respond(req))
+                    .collect::<Vec<_>>();
+                (200, serde_json::to_vec(&responses).unwrap())
+            }
+            request => {
+                node.requests
+                    .push(vec![request["method"].as_str().unwrap_or_default().to_owned()]);
+                let response = node.respond(&request);
+                let status = match response["error"]["code"].as_i64() {
+                    None => 200,
+                    Some(-32601) => 404,
+                    Some(_) => 500,
+                };
+                (status, serde_json::to_vec(&response).unwrap())
+            }
+        }
+    };
+    let head = format!(
+        "HTTP/1.1 {status} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n",
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).unwrap();
+    stream.write_all(&body).unwrap();
+}
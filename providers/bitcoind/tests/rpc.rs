@@ -0,0 +1,171 @@
+#![cfg(all(not(target_arch = "wasm32"), not(feature = "async")))]
+
+#[macro_use]
+extern crate amplify;
+
+mod mock;
+
+use std::num::NonZeroU64;
+use std::sync::{Arc, Mutex};
+
+use amplify::confinement::Confined;
+use amplify::{ByteArray, Bytes32};
+use bp::{Outpoint, Sats, ScriptPubkey, SeqNo, Tx, TxIn, TxOut, Txid};
+use mock::{MockNode, PASSWORD, USER};
+use rgb::popls::bp::WalletProvider;
+use rgb::{BlockInfo, WitnessInfo, WitnessResolver, WitnessStatus};
+use rgb_provider_bitcoind::{BitcoindWallet, RpcClient, RpcError};
+use serde_json::json;
+
+fn setup(txindex: bool) -> (Arc<Mutex<MockNode>>, BitcoindWallet) {
+    let node = Arc::new(Mutex::new(MockNode::new(txindex)));
+    let url = mock::serve(node.clone());
+    let rpc = RpcClient::new(&url)
+        .unwrap()
+        .with_auth(USER, PASSWORD)
+        .with_wallet("rgb");
+    (node, BitcoindWallet::new(rpc, Bytes32::from_byte_array([0xAA; 32])))
+}
+
+fn spend(wallet: &mut BitcoindWallet, outpoint: Outpoint) -> Tx {
+    let script_pubkey = wallet.next_address().script_pubkey();
+    Tx {
+        version: default!(),
+        inputs: Confined::from_checked(vec![TxIn {
+            prev_output: outpoint,
+            sig_script: none!(),
+            sequence: SeqNo::from_consensus_u32(0xFFFF_FFFD),
+            witness: none!(),
+        }]),
+        outputs: Confined::from_checked(vec![TxOut::new(script_pubkey, Sats::from(1000u64))]),
+        lock_time: default!(),
+    }
+}
+
+fn block_info(node: &MockNode, height: usize, tx_pos: u32) -> WitnessInfo {
+    let block = &node.blocks[height];
+    let hash = Bytes32::from_byte_array(block.hash.to_byte_array());
+    WitnessInfo::mined(
+        NonZeroU64::new(height as u64).unwrap(),
+        BlockInfo::new(hash, block.time).with_tx_pos(tx_pos),
+    )
+}
+
+#[test]
+fn client() {
+    let (node, wallet) = setup(true);
+    let rpc = wallet.rpc();
+    assert_eq!(rpc.call("getblockcount", json!([])).unwrap(), json!(0));
+
+    let err = rpc.call("getblockchaininfo", json!([])).unwrap_err();
+    assert!(matches!(err, RpcError::Rpc { code: -32601, .. }));
+    let err = rpc.call("getblockhash", json!([1])).unwrap_err();
+    assert_eq!(err.rpc_code(), Some(-8));
+
+    let results = rpc
+        .call_batch([("getblockcount", json!([])), ("getblockhash", json!([5]))])
+        .unwrap();
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].as_ref().unwrap(), &json!(0));
+    assert_eq!(results[1].as_ref().unwrap_err().rpc_code(), Some(-8));
+    assert!(rpc.call_batch([]).unwrap().is_empty());
+    assert_eq!(node.lock().unwrap().requests.len(), 4);
+
+    let url = format!("http://{USER}:wrong@{}", &mock::serve(node)["http://".len()..]);
+    let err = RpcClient::new(&url)
+        .unwrap()
+        .call("getblockcount", json!([]))
+        .unwrap_err();
+    assert!(matches!(err, RpcError::Http(401)));
+
+    assert!(matches!(RpcClient::new("https://127.0.0.1:8332"), Err(RpcError::InvalidUrl(_))));
+    assert!(matches!(RpcClient::new("http://localhost"), Err(RpcError::InvalidUrl(_))));
+}
+
+#[test]
+fn wallet() {
+    let (node, mut wallet) = setup(true);
+    let script_pubkey = wallet.next_address().script_pubkey();
+    let funding = node.lock().unwrap().fund(script_pubkey, 100_000);
+    assert!(!wallet.has_utxo(funding));
+    wallet.update_utxos().unwrap();
+    assert_eq!(wallet.utxos().collect::<Vec<_>>(), vec![funding]);
+    assert_eq!(wallet.last_block_height().unwrap(), 1);
+
+    let tx = spend(&mut wallet, funding);
+    wallet.broadcast(&tx, None).unwrap();
+    assert_eq!(node.lock().unwrap().mempool, vec![tx.clone()]);
+    assert_eq!(wallet.utxos().collect::<Vec<_>>(), vec![Outpoint::new(tx.txid(), 0u32)]);
+
+    let conflict = spend(&mut wallet, funding);
+    let err = wallet.broadcast(&conflict, None).unwrap_err();
+    assert_eq!(err.rpc_code(), Some(-26));
+
+    let spender_resolver = wallet.spender_resolver();
+    assert_eq!(spender_resolver(funding).unwrap(), Some(tx.txid()));
+    assert_eq!(spender_resolver(Outpoint::new(tx.txid(), 0u32)).unwrap(), None);
+
+    let block_resolver = wallet.block_resolver();
+    let tip = node.lock().unwrap().tip().hash;
+    assert_eq!(block_resolver(1).unwrap(), Some(Bytes32::from_byte_array(tip.to_byte_array())));
+    assert_eq!(block_resolver(2).unwrap(), None);
+}
+
+#[test]
+fn resolver() {
+    let (node, mut wallet) = setup(true);
+    let script_pubkey = wallet.next_address().script_pubkey();
+    let funding = node.lock().unwrap().fund(script_pubkey, 100_000);
+    let tx = spend(&mut wallet, funding);
+    wallet.broadcast(&tx, None).unwrap();
+    let unknown = Txid::from([0xEE; 32]);
+
+    let resolver = wallet.txid_resolver();
+    node.lock().unwrap().requests.clear();
+    let resolved = resolver
+        .resolve_witnesses(&[funding.txid, tx.txid(), unknown])
+        .unwrap();
+    assert_eq!(resolved.len(), 2);
+    assert_eq!(resolved[&funding.txid], block_info(&node.lock().unwrap(), 1, 0));
+    assert_eq!(resolved[&tx.txid()], WitnessStatus::Tentative.into());
+    // Unknown transactions are left unresolved
+    assert!(!resolved.contains_key(&unknown));
+    // Each kind of the RPC calls is batched
+    assert_eq!(node.lock().unwrap().requests, vec![
+        vec![s!("getrawtransaction"); 3],
+        vec![s!("gettransaction")],
+        vec![s!("getblock")],
+    ]);
+
+    // Re-org returns the transaction to mempool
+    node.lock().unwrap().mine();
+    let resolved = resolver.resolve_witnesses(&[tx.txid()]).unwrap();
+    assert_eq!(resolved[&tx.txid()], block_info(&node.lock().unwrap(), 2, 0));
+    node.lock().unwrap().reorg(1);
+    let resolved = resolver.resolve_witnesses(&[tx.txid()]).unwrap();
+    assert_eq!(resolved[&tx.txid()], WitnessStatus::Tentative.into());
+}
+
+#[test]
+fn resolver_without_txindex() {
+    let (node, mut wallet) = setup(false);
+    let script_pubkey = wallet.next_address().script_pubkey();
+    let funding = node.lock().unwrap().fund(script_pubkey, 100_000);
+    let tx = spend(&mut wallet, funding);
+    wallet.broadcast(&tx, None).unwrap();
+    node.lock().unwrap().mine();
+    node.lock().unwrap().mine();
+    let foreign = node.lock().unwrap().fund(ScriptPubkey::new(), 100_000);
+
+    let resolved = wallet
+        .txid_resolver()
+        .resolve_witnesses(&[funding.txid, tx.txid(), foreign.txid])
+        .unwrap();
+    let node = node.lock().unwrap();
+    // Mined wallet transactions are resolved via the node wallet, including the spent ones
+    assert_eq!(resolved[&tx.txid()], block_info(&node, 2, 0));
+    assert_eq!(resolved[&funding.txid], block_info(&node, 1, 0));
+    // ... while the status of other mined transactions can't be determined
+    assert!(!resolved.contains_key(&foreign.txid));
+    assert_eq!(resolved.len(), 2);
+}
@@ -0,0 +1,611 @@
+// Standard Library for RGB smart contracts
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2019-2025 by Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+// Written in 2024-2025 by Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 LNP/BP Laboratories,
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2025 RGB Consortium, Switzerland.
+// Copyright (C) 2019-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+//! Wallet provider working over the Electrum protocol.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::num::NonZeroU64;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use amplify::{ByteArray, Bytes32};
+use bp::seals::WTxoSeal;
+use bp::{BlockHeader, Outpoint, Tx, Txid, Vout};
+use rgb::invoice::bp::Address;
+use rgb::popls::bp::WalletProvider;
+use rgb::{AuthToken, BlockInfo, WitnessInfo, WitnessResolver, WitnessStatus};
+use serde_json::{json, Value};
+
+use crate::watch::Watcher;
+
+/// Errors communicating with an Electrum server.
+#[derive(Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum ElectrumError {
+    /// I/O error communicating with the Electrum server: {0}
+    #[from]
+    Io(io::Error),
+
+    /// invalid JSON in the Electrum server response: {0}
+    #[from]
+    Json(serde_json::Error),
+
+    /// Electrum server error {code}: {message}
+    Server { code: i64, message: String },
+
+    /// malformed Electrum server response: {0}
+    InvalidResponse(String),
+}
+
+impl ElectrumError {
+    /// Checks whether the server reported the requested transaction as unknown.
+    ///
+    /// The protocol has no dedicated error code for this case, so the message of the error is
+    /// matched against the ones used by ElectrumX, Fulcrum and electrs, which are passing through
+    /// the bitcoind error.
+    pub fn is_tx_not_found(&self) -> bool {
+        let ElectrumError::Server { message, .. } = self else {
+            return false;
+        };
+        let message = message.to_ascii_lowercase();
+        message.contains("no such mempool or blockchain transaction")
+            || message.contains("transaction not found")
+    }
+}
+
+/// Blocking client for the Electrum protocol, keeping a single TCP connection to the server.
+///
+/// The connection is re-established on the next call after any I/O error. Notifications sent by
+/// the server for the subscriptions made over the connection are skipped.
+///
+/// The client supports only plain TCP connections (`tcp://` servers): TLS (`ssl://` servers)
+/// requires a proxy terminating the TLS connection.
+#[derive(Debug)]
+pub struct ElectrumClient {
+    addr: String,
+    timeout: Option<Duration>,
+    conn: Mutex<Option<BufReader<TcpStream>>>,
+    next_id: AtomicU64,
+}
+
+impl Clone for ElectrumClient {
+    fn clone(&self) -> Self {
+        Self {
+            addr: self.addr.clone(),
+            timeout: self.timeout,
+            conn: Mutex::new(None),
+            next_id: AtomicU64::new(self.next_id.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+impl ElectrumClient {
+    /// Constructs a client for the server at the given `host:port` address; the connection is
+    /// established on the first call.
+    pub fn new(addr: &str) -> Self {
+        let addr = addr.strip_prefix("tcp://").unwrap_or(addr);
+        Self {
+            addr: addr.to_owned(),
+            timeout: None,
+            conn: Mutex::new(None),
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    /// Sets the timeout for connecting, reading and writing to the server.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Calls the protocol `method` with the given positional `params`.
+    pub fn call(&self, method: &str, params: Value) -> Result<Value, ElectrumError> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let request = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        parse_response(self.request(&request)?)
+    }
+
+    /// Performs multiple calls in a single JSON-RPC batch request.
+    ///
+    /// Returns the results of the calls in the order of the requests. Errors of the individual
+    /// calls are reported in the returned vector, while the batch-level errors fail the whole
+    /// request.
+    pub fn call_batch<'a>(
+        &self,
+        calls: impl IntoIterator<Item = (&'a str, Value)>,
+    ) -> Result<Vec<Result<Value, ElectrumError>>, ElectrumError> {
+        let first = self.next_id.load(Ordering::Relaxed);
+        let requests = calls
+            .into_iter()
+            .map(|(method, params)| {
+                let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+                json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+            })
+            .collect::<Vec<_>>();
+        if requests.is_empty() {
+            return Ok(vec![]);
+        }
+        let len = requests.len();
+        let Value::Array(responses) = self.request(&Value::Array(requests))? else {
+            return Err(ElectrumError::InvalidResponse(s!("batch response is not an array")));
+        };
+        let mut results = (0..len).map(|_| None).collect::<Vec<_>>();
+        for response in responses {
+            let pos = response
+                .get("id")
+                .and_then(Value::as_u64)
+                .and_then(|id| id.checked_sub(first))
+                .filter(|pos| (*pos as usize) < len)
+                .ok_or_else(|| ElectrumError::InvalidResponse(s!("unknown batch response id")))?;
+            results[pos as usize] = Some(parse_response(response));
+        }
+        results
+            .into_iter()
+            .map(|res| {
+                res.ok_or_else(|| ElectrumError::InvalidResponse(s!("missing batch response")))
+            })
+            .collect()
+    }
+
+    fn request(&self, request: &Value) -> Result<Value, ElectrumError> {
+        let mut conn = self.conn.lock().expect("poisoned lock");
+        let res = self.exchange(&mut conn, request);
+        if matches!(res, Err(ElectrumError::Io(_))) {
+            *conn = None;
+        }
+        res
+    }
+
+    fn exchange(
+        &self,
+        conn: &mut Option<BufReader<TcpStream>>,
+        request: &Value,
+    ) -> Result<Value, ElectrumError> {
+        let reader = match conn {
+            Some(reader) => reader,
+            None => {
+                let stream = TcpStream::connect(&self.addr)?;
+                stream.set_read_timeout(self.timeout)?;
+                stream.set_write_timeout(self.timeout)?;
+                conn.insert(BufReader::new(stream))
+            }
+        };
+        let mut line = serde_json::to_vec(request)?;
+        line.push(b'\n');
+        reader.get_mut().write_all(&line)?;
+        reader.get_mut().flush()?;
+
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line)? == 0 {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+            }
+            let response = serde_json::from_str::<Value>(&line)?;
+            // Skip the subscription notifications, which have no id
+            if response.is_array() || response.get("id").is_some_and(|id| !id.is_null()) {
+                return Ok(response);
+            }
+        }
+    }
+}
+
+fn parse_response(mut response: Value) -> Result<Value, ElectrumError> {
+    match response.get_mut("error").map(Value::take) {
+        None | Some(Value::Null) => {}
+        Some(error) => {
+            return Err(ElectrumError::Server {
+                code: error
+                    .get("code")
+                    .and_then(Value::as_i64)
+                    .unwrap_or_default(),
+                message: error
+                    .get("message")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default()
+                    .to_owned(),
+            })
+        }
+    }
+    response
+        .get_mut("result")
+        .map(Value::take)
+        .ok_or_else(|| ElectrumError::InvalidResponse(s!("response has no result")))
+}
+
+fn invalid(what: &str) -> ElectrumError {
+    ElectrumError::InvalidResponse(format!("invalid {what}"))
+}
+
+/// Wallet watching the scripts of its addresses with an Electrum server.
+///
+/// The addresses are derived by the `derive` function from their index, while the keys and the
+/// transaction signing remain outside of the wallet.
+///
+/// With the `async` feature the provider methods still perform blocking I/O, so they have to be
+/// called from a thread which can be blocked, like the one provided by `spawn_blocking` of the
+/// async runtime.
+#[derive(Clone, Debug)]
+pub struct ElectrumWallet<D: Fn(u32) -> Address> {
+    client: ElectrumClient,
+    watcher: Watcher<D>,
+}
+
+impl<D: Fn(u32) -> Address> ElectrumWallet<D> {
+    /// Constructs a wallet using the client, deriving the addresses with the `derive` function.
+    ///
+    /// The `noise_seed` is used to blind the seals defined by the wallet and must be kept secret.
+    pub fn new(client: ElectrumClient, noise_seed: Bytes32, derive: D) -> Self {
+        Self { client, watcher: Watcher::new(derive, noise_seed) }
+    }
+
+    /// Watches all the addresses with the index below `next_index`, which is required to restore
+    /// a previously used wallet.
+    pub fn with_index(mut self, next_index: u32) -> Self {
+        self.watcher.derive_until(next_index);
+        self
+    }
+
+    /// The client used by the wallet.
+    pub fn client(&self) -> &ElectrumClient { &self.client }
+
+    /// Subscribes to all the watched scripts and updates the unspent outputs of the scripts whose
+    /// status has changed since the last update.
+    fn sync_utxos(&mut self) -> Result<(), ElectrumError> {
+        let statuses = self.client.call_batch(
+            self.watcher
+                .scripts
+                .values()
+                .map(|watched| ("blockchain.scripthash.subscribe", json!([watched.hash]))),
+        )?;
+        let mut changed = Vec::new();
+        for (watched, status) in self.watcher.scripts.values_mut().zip(statuses) {
+            let status = status?.as_str().map(str::to_owned);
+            if status != watched.status {
+                if status.is_none() {
+                    watched.utxos.clear();
+                } else {
+                    changed.push(watched.hash.clone());
+                }
+                watched.status = status;
+            }
+        }
+
+        let unspent = self.client.call_batch(
+            changed
+                .iter()
+                .map(|hash| ("blockchain.scripthash.listunspent", json!([hash]))),
+        )?;
+        let mut updated = HashMap::with_capacity(changed.len());
+        for (hash, unspent) in changed.into_iter().zip(unspent) {
+            let utxos = unspent?
+                .as_array()
+                .ok_or_else(|| invalid("unspent output list"))?
+                .iter()
+                .map(|utxo| {
+                    let txid = utxo
+                        .get("tx_hash")
+                        .and_then(Value::as_str)
+                        .and_then(|s| Txid::from_str(s).ok());
+                    let vout = utxo.get("tx_pos").and_then(Value::as_u64);
+                    match (txid, vout) {
+                        (Some(txid), Some(vout)) => Ok(Outpoint::new(txid, vout as u32)),
+                        _ => Err(invalid("unspent output")),
+                    }
+                })
+                .collect::<Result<BTreeSet<_>, _>>()?;
+            updated.insert(hash, utxos);
+        }
+        for watched in self.watcher.scripts.values_mut() {
+            if let Some(utxos) = updated.remove(&watched.hash) {
+                watched.utxos = utxos;
+            }
+        }
+        Ok(())
+    }
+
+    fn send_tx(&mut self, tx: &Tx, change: Option<(Vout, u32, u32)>) -> Result<(), ElectrumError> {
+        self.watcher.watch_change(tx, change);
+        self.client
+            .call("blockchain.transaction.broadcast", json!([format!("{tx:x}")]))?;
+        self.sync_utxos()
+    }
+}
+
+fn tip_height(client: &ElectrumClient) -> Result<u64, ElectrumError> {
+    client
+        .call("blockchain.headers.subscribe", json!([]))?
+        .get("height")
+        .and_then(Value::as_u64)
+        .ok_or_else(|| invalid("block height"))
+}
+
+fn parse_header(header: &Value) -> Result<BlockHeader, ElectrumError> {
+    header
+        .as_str()
+        .and_then(|s| BlockHeader::from_str(s).ok())
+        .ok_or_else(|| invalid("block header"))
+}
+
+fn block_hash(client: &ElectrumClient, height: u64) -> Result<Option<Bytes32>, ElectrumError> {
+    if height > tip_height(client)? {
+        return Ok(None);
+    }
+    let header = parse_header(&client.call("blockchain.block.header", json!([height]))?)?;
+    Ok(Some(Bytes32::from_byte_array(header.block_hash().to_byte_array())))
+}
+
+impl<D: Fn(u32) -> Address> WalletProvider for ElectrumWallet<D> {
+    type Error = ElectrumError;
+
+    fn has_utxo(&self, outpoint: Outpoint) -> bool { self.watcher.has_utxo(outpoint) }
+
+    fn utxos(&self) -> impl Iterator<Item = Outpoint> { self.watcher.utxos() }
+
+    #[cfg(not(feature = "async"))]
+    fn update_utxos(&mut self) -> Result<(), Self::Error> { self.sync_utxos() }
+
+    #[cfg(feature = "async")]
+    async fn update_utxos_async(&mut self) -> Result<(), Self::Error> { self.sync_utxos() }
+
+    fn register_seal(&mut self, seal: WTxoSeal) { self.watcher.register_seal(seal) }
+
+    fn resolve_seals(
+        &self,
+        seals: impl Iterator<Item = AuthToken>,
+    ) -> impl Iterator<Item = WTxoSeal> {
+        seals.filter_map(|auth| self.watcher.seal(auth))
+    }
+
+    fn noise_seed(&self) -> Bytes32 { self.watcher.noise_seed() }
+
+    fn next_address(&mut self) -> Address { self.watcher.next_address() }
+
+    fn next_nonce(&mut self) -> u64 { self.watcher.next_nonce() }
+
+    #[cfg(not(feature = "async"))]
+    fn txid_resolver(&self) -> impl WitnessResolver<Txid, Error = Self::Error> {
+        TxResolver(&self.client)
+    }
+
+    #[cfg(feature = "async")]
+    fn txid_resolver_async(&self) -> impl WitnessResolver<Txid, Error = Self::Error> {
+        TxResolver(&self.client)
+    }
+
+    #[cfg(not(feature = "async"))]
+    fn block_resolver(&self) -> impl Fn(u64) -> Result<Option<Bytes32>, Self::Error> {
+        |height| block_hash(&self.client, height)
+    }
+
+    #[cfg(feature = "async")]
+    fn block_resolver_async(&self) -> impl AsyncFn(u64) -> Result<Option<Bytes32>, Self::Error> {
+        async |height| block_hash(&self.client, height)
+    }
+
+    /// Finds the spending transactions in the history of the spent output script.
+    #[cfg(not(feature = "async"))]
+    fn spender_resolver(&self) -> impl Fn(Outpoint) -> Result<Option<Txid>, Self::Error> {
+        |outpoint| spender(&self.client, outpoint)
+    }
+
+    /// Finds the spending transactions in the history of the spent output script.
+    #[cfg(feature = "async")]
+    fn spender_resolver_async(
+        &self,
+    ) -> impl AsyncFn(Outpoint) -> Result<Option<Txid>, Self::Error> {
+        async |outpoint| spender(&self.client, outpoint)
+    }
+
+    #[cfg(not(feature = "async"))]
+    fn last_block_height(&self) -> Result<u64, Self::Error> { tip_height(&self.client) }
+
+    #[cfg(feature = "async")]
+    async fn last_block_height_async(&self) -> Result<u64, Self::Error> { tip_height(&self.client) }
+
+    #[cfg(not(feature = "async"))]
+    fn broadcast(&mut self, tx: &Tx, change: Option<(Vout, u32, u32)>) -> Result<(), Self::Error> {
+        self.send_tx(tx, change)
+    }
+
+    #[cfg(feature = "async")]
+    async fn broadcast_async(
+        &mut self,
+        tx: &Tx,
+        change: Option<(Vout, u32, u32)>,
+    ) -> Result<(), Self::Error> {
+        self.send_tx(tx, change)
+    }
+}
+
+fn parse_tx(tx: &Value) -> Result<Tx, ElectrumError> {
+    tx.as_str()
+        .and_then(|s| Tx::from_str(s).ok())
+        .ok_or_else(|| invalid("transaction"))
+}
+
+fn spender(client: &ElectrumClient, outpoint: Outpoint) -> Result<Option<Txid>, ElectrumError> {
+    let prev_tx =
+        parse_tx(&client.call("blockchain.transaction.get", json!([outpoint.txid.to_string()]))?)?;
+    let output = prev_tx
+        .outputs
+        .get(outpoint.vout_usize())
+        .ok_or_else(|| invalid("output number"))?;
+    let history = client.call(
+        "blockchain.scripthash.get_history",
+        json!([crate::script_hash(&output.script_pubkey)]),
+    )?;
+    let prev_txid = outpoint.txid.to_string();
+    let mut txids = vec![];
+    for entry in history
+        .as_array()
+        .ok_or_else(|| invalid("script history"))?
+    {
+        let txid = entry
+            .get("tx_hash")
+            .and_then(Value::as_str)
+            .ok_or_else(|| invalid("script history entry"))?;
+        if txid != prev_txid {
+            txids.push(txid.to_owned());
+        }
+    }
+    let txs = client.call_batch(
+        txids
+            .iter()
+            .map(|txid| ("blockchain.transaction.get", json!([txid]))),
+    )?;
+    for tx in txs {
+        let tx = parse_tx(&tx?)?;
+        if tx.inputs().any(|input| input.prev_output == outpoint) {
+            return Ok(Some(tx.txid()));
+        }
+    }
+    Ok(None)
+}
+
+/// Resolver of the witness transaction statuses, querying all the transactions with a constant
+/// number of batched requests.
+///
+/// Electrum protocol doesn't provide the status of a transaction directly, so it is looked up in
+/// the history of the script of the first transaction output which is not an `OP_RETURN`.
+/// Transactions unknown to the server are left unresolved, since they may be just evicted from the
+/// server mempool.
+struct TxResolver<'client>(&'client ElectrumClient);
+
+impl TxResolver<'_> {
+    fn resolve(&self, txids: &[Txid]) -> Result<HashMap<Txid, WitnessInfo>, ElectrumError> {
+        let client = self.0;
+        let mut resolved = HashMap::with_capacity(txids.len());
+
+        let txs = client.call_batch(
+            txids
+                .iter()
+                .map(|txid| ("blockchain.transaction.get", json!([txid.to_string()]))),
+        )?;
+        let mut scripts = BTreeMap::<String, Vec<Txid>>::new();
+        for (txid, tx) in txids.iter().zip(txs) {
+            let tx = match tx {
+                Ok(tx) => tx,
+                Err(err) if err.is_tx_not_found() => continue,
+                Err(err) => return Err(err),
+            };
+            let tx = parse_tx(&tx)?;
+            let output = tx
+                .outputs
+                .iter()
+                .find(|output| !output.script_pubkey.is_op_return())
+                .or(tx.outputs.first())
+                .ok_or_else(|| invalid("transaction without outputs"))?;
+            scripts
+                .entry(crate::script_hash(&output.script_pubkey))
+                .or_default()
+                .push(*txid);
+        }
+
+        let histories = client.call_batch(
+            scripts
+                .keys()
+                .map(|hash| ("blockchain.scripthash.get_history", json!([hash]))),
+        )?;
+        let mut mined = Vec::<(Txid, u64)>::new();
+        for (txids, history) in scripts.into_values().zip(histories) {
+            let history = history?;
+            let history = history
+                .as_array()
+                .ok_or_else(|| invalid("script history"))?;
+            for txid in txids {
+                let height = history
+                    .iter()
+                    .find(|entry| {
+                        entry.get("tx_hash").and_then(Value::as_str) == Some(&txid.to_string())
+                    })
+                    .and_then(|entry| entry.get("height"))
+                    .and_then(Value::as_i64)
+                    .unwrap_or_default();
+                // Zero and negative heights are used for the mempool transactions
+                match u64::try_from(height) {
+                    Ok(height) if height > 0 => mined.push((txid, height)),
+                    _ => {
+                        resolved.insert(txid, WitnessStatus::Tentative.into());
+                    }
+                }
+            }
+        }
+        if mined.is_empty() {
+            return Ok(resolved);
+        }
+
+        let heights = mined
+            .iter()
+            .map(|(_, height)| *height)
+            .collect::<BTreeSet<_>>();
+        let responses = client.call_batch(
+            heights
+                .iter()
+                .map(|height| ("blockchain.block.header", json!([height])))
+                .chain(mined.iter().map(|(txid, height)| {
+                    ("blockchain.transaction.get_merkle", json!([txid.to_string(), height]))
+                })),
+        )?;
+        let mut responses = responses.into_iter();
+        let mut headers = HashMap::with_capacity(heights.len());
+        for (height, header) in heights.into_iter().zip(responses.by_ref()) {
+            headers.insert(height, parse_header(&header?)?);
+        }
+        for ((txid, height), proof) in mined.into_iter().zip(responses) {
+            let header = &headers[&height];
+            let hash = Bytes32::from_byte_array(header.block_hash().to_byte_array());
+            let mut info = BlockInfo::new(hash, header.time as i64);
+            if let Some(pos) = proof?.get("pos").and_then(Value::as_u64) {
+                info = info.with_tx_pos(pos as u32);
+            }
+            let height = NonZeroU64::new(height).expect("checked to be positive");
+            resolved.insert(txid, WitnessInfo::mined(height, info));
+        }
+        Ok(resolved)
+    }
+}
+
+impl WitnessResolver<Txid> for TxResolver<'_> {
+    type Error = ElectrumError;
+
+    #[cfg(not(feature = "async"))]
+    fn resolve_witnesses(
+        &self,
+        wids: &[Txid],
+    ) -> Result<HashMap<Txid, WitnessInfo>, ElectrumError> {
+        self.resolve(wids)
+    }
+
+    #[cfg(feature = "async")]
+    async fn resolve_witnesses_async(
+        &self,
+        wids: &[Txid],
+    ) -> Result<HashMap<Txid, WitnessInfo>, ElectrumError> {
+        self.resolve(wids)
+    }
+}
@@ -0,0 +1,400 @@
+// Standard Library for RGB smart contracts
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2019-2025 by Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+// Written in 2024-2025 by Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 LNP/BP Laboratories,
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2025 RGB Consortium, Switzerland.
+// Copyright (C) 2019-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+//! Wallet provider working over the Esplora REST API.
+
+use std::collections::{BTreeSet, HashMap};
+use std::io;
+use std::num::NonZeroU64;
+use std::str::FromStr;
+use std::time::Duration;
+
+use amplify::{ByteArray, Bytes32};
+use bp::seals::WTxoSeal;
+use bp::{BlockHash, Outpoint, Tx, Txid, Vout};
+use rgb::invoice::bp::Address;
+use rgb::popls::bp::WalletProvider;
+use rgb::{AuthToken, BlockInfo, WitnessInfo, WitnessResolver, WitnessStatus};
+use serde_json::Value;
+
+use crate::http;
+use crate::watch::Watcher;
+
+/// Errors communicating with an Esplora server.
+#[derive(Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum EsploraError {
+    /// I/O error communicating with the Esplora server: {0}
+    #[from]
+    Io(io::Error),
+
+    /// invalid Esplora server URL '{0}'; only plain `http://host:port[/path]` URLs are supported.
+    InvalidUrl(String),
+
+    /// Esplora server responded with HTTP status {status}: {message}
+    Http { status: u16, message: String },
+
+    /// invalid JSON in the Esplora server response: {0}
+    #[from]
+    Json(serde_json::Error),
+
+    /// malformed Esplora server response: {0}
+    InvalidResponse(String),
+}
+
+impl EsploraError {
+    /// Checks whether the server reported the requested resource as missing.
+    pub fn is_not_found(&self) -> bool { matches!(self, EsploraError::Http { status: 404, .. }) }
+}
+
+/// Blocking client for the Esplora REST API.
+///
+/// The client supports only plain HTTP (`http://` URLs): HTTPS servers require a proxy
+/// terminating the TLS connection.
+#[derive(Clone, Debug)]
+pub struct EsploraClient {
+    host: String,
+    base: String,
+    timeout: Option<Duration>,
+}
+
+impl EsploraClient {
+    /// Constructs a client for the server at the given URL, like `http://127.0.0.1:3002/api`.
+    pub fn new(url: &str) -> Result<Self, EsploraError> {
+        let invalid = || EsploraError::InvalidUrl(url.to_owned());
+        let rest = url.strip_prefix("http://").ok_or_else(invalid)?;
+        let (host, base) = match rest.find('/') {
+            Some(pos) => rest.split_at(pos),
+            None => (rest, ""),
+        };
+        if host.is_empty() || !host.contains(':') {
+            return Err(invalid());
+        }
+        Ok(Self {
+            host: host.to_owned(),
+            base: base.trim_end_matches('/').to_owned(),
+            timeout: None,
+        })
+    }
+
+    /// Sets the timeout for connecting, reading and writing to the server.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Requests the API `path` (like `/blocks/tip/height`), returning the response body as text.
+    pub fn get(&self, path: &str) -> Result<String, EsploraError> {
+        self.request("GET", path, None)
+    }
+
+    /// Requests the API `path`, parsing the response body as JSON.
+    pub fn get_json(&self, path: &str) -> Result<Value, EsploraError> {
+        Ok(serde_json::from_str(&self.get(path)?)?)
+    }
+
+    /// Posts the `body` to the API `path`, returning the response body as text.
+    pub fn post(&self, path: &str, body: &str) -> Result<String, EsploraError> {
+        self.request("POST", path, Some(body))
+    }
+
+    fn request(
+        &self,
+        method: &str,
+        path: &str,
+        body: Option<&str>,
+    ) -> Result<String, EsploraError> {
+        let path = format!("{}{path}", self.base);
+        let response =
+            http::request(&self.host, method, &path, body.map(str::as_bytes), self.timeout)?;
+        let body = String::from_utf8(response.body)
+            .map_err(|_| EsploraError::InvalidResponse(s!("non-UTF8 response body")))?;
+        if response.status != 200 {
+            return Err(EsploraError::Http { status: response.status, message: body });
+        }
+        Ok(body)
+    }
+}
+
+fn invalid(what: &str) -> EsploraError { EsploraError::InvalidResponse(format!("invalid {what}")) }
+
+/// Wallet watching the scripts of its addresses with an Esplora server.
+///
+/// The addresses are derived by the `derive` function from their index, while the keys and the
+/// transaction signing remain outside of the wallet.
+///
+/// With the `async` feature the provider methods still perform blocking I/O, so they have to be
+/// called from a thread which can be blocked, like the one provided by `spawn_blocking` of the
+/// async runtime.
+#[derive(Clone, Debug)]
+pub struct EsploraWallet<D: Fn(u32) -> Address> {
+    client: EsploraClient,
+    watcher: Watcher<D>,
+}
+
+impl<D: Fn(u32) -> Address> EsploraWallet<D> {
+    /// Constructs a wallet using the client, deriving the addresses with the `derive` function.
+    ///
+    /// The `noise_seed` is used to blind the seals defined by the wallet and must be kept secret.
+    pub fn new(client: EsploraClient, noise_seed: Bytes32, derive: D) -> Self {
+        Self { client, watcher: Watcher::new(derive, noise_seed) }
+    }
+
+    /// Watches all the addresses with the index below `next_index`, which is required to restore
+    /// a previously used wallet.
+    pub fn with_index(mut self, next_index: u32) -> Self {
+        self.watcher.derive_until(next_index);
+        self
+    }
+
+    /// The client used by the wallet.
+    pub fn client(&self) -> &EsploraClient { &self.client }
+
+    /// Polls the statistics of all the watched scripts and updates the unspent outputs of the
+    /// scripts whose statistics have changed since the last update.
+    ///
+    /// Esplora has no subscriptions, so the script statistics are used as the script status.
+    fn sync_utxos(&mut self) -> Result<(), EsploraError> {
+        for watched in self.watcher.scripts.values_mut() {
+            let stats = self
+                .client
+                .get_json(&format!("/scripthash/{}", watched.hash))?;
+            let status = match (stats.get("chain_stats"), stats.get("mempool_stats")) {
+                (Some(chain), Some(mempool)) => format!("{chain}/{mempool}"),
+                _ => return Err(invalid("script statistics")),
+            };
+            if watched.status.as_ref() == Some(&status) {
+                continue;
+            }
+            let utxos = self
+                .client
+                .get_json(&format!("/scripthash/{}/utxo", watched.hash))?;
+            watched.utxos = utxos
+                .as_array()
+                .ok_or_else(|| invalid("unspent output list"))?
+                .iter()
+                .map(|utxo| {
+                    let txid = utxo
+                        .get("txid")
+                        .and_then(Value::as_str)
+                        .and_then(|s| Txid::from_str(s).ok());
+                    let vout = utxo.get("vout").and_then(Value::as_u64);
+                    match (txid, vout) {
+                        (Some(txid), Some(vout)) => Ok(Outpoint::new(txid, vout as u32)),
+                        _ => Err(invalid("unspent output")),
+                    }
+                })
+                .collect::<Result<BTreeSet<_>, _>>()?;
+            watched.status = Some(status);
+        }
+        Ok(())
+    }
+
+    fn send_tx(&mut self, tx: &Tx, change: Option<(Vout, u32, u32)>) -> Result<(), EsploraError> {
+        self.watcher.watch_change(tx, change);
+        self.client.post("/tx", &format!("{tx:x}"))?;
+        self.sync_utxos()
+    }
+}
+
+fn tip_height(client: &EsploraClient) -> Result<u64, EsploraError> {
+    client
+        .get("/blocks/tip/height")?
+        .trim()
+        .parse()
+        .map_err(|_| invalid("block height"))
+}
+
+fn parse_block_hash(hash: &str) -> Result<Bytes32, EsploraError> {
+    let hash = BlockHash::from_str(hash.trim()).map_err(|_| invalid("block hash"))?;
+    Ok(Bytes32::from_byte_array(hash.to_byte_array()))
+}
+
+fn block_hash(client: &EsploraClient, height: u64) -> Result<Option<Bytes32>, EsploraError> {
+    match client.get(&format!("/block-height/{height}")) {
+        Ok(hash) => parse_block_hash(&hash).map(Some),
+        Err(err) if err.is_not_found() => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+impl<D: Fn(u32) -> Address> WalletProvider for EsploraWallet<D> {
+    type Error = EsploraError;
+
+    fn has_utxo(&self, outpoint: Outpoint) -> bool { self.watcher.has_utxo(outpoint) }
+
+    fn utxos(&self) -> impl Iterator<Item = Outpoint> { self.watcher.utxos() }
+
+    #[cfg(not(feature = "async"))]
+    fn update_utxos(&mut self) -> Result<(), Self::Error> { self.sync_utxos() }
+
+    #[cfg(feature = "async")]
+    async fn update_utxos_async(&mut self) -> Result<(), Self::Error> { self.sync_utxos() }
+
+    fn register_seal(&mut self, seal: WTxoSeal) { self.watcher.register_seal(seal) }
+
+    fn resolve_seals(
+        &self,
+        seals: impl Iterator<Item = AuthToken>,
+    ) -> impl Iterator<Item = WTxoSeal> {
+        seals.filter_map(|auth| self.watcher.seal(auth))
+    }
+
+    fn noise_seed(&self) -> Bytes32 { self.watcher.noise_seed() }
+
+    fn next_address(&mut self) -> Address { self.watcher.next_address() }
+
+    fn next_nonce(&mut self) -> u64 { self.watcher.next_nonce() }
+
+    #[cfg(not(feature = "async"))]
+    fn txid_resolver(&self) -> impl WitnessResolver<Txid, Error = Self::Error> {
+        TxResolver(&self.client)
+    }
+
+    #[cfg(feature = "async")]
+    fn txid_resolver_async(&self) -> impl WitnessResolver<Txid, Error = Self::Error> {
+        TxResolver(&self.client)
+    }
+
+    #[cfg(not(feature = "async"))]
+    fn block_resolver(&self) -> impl Fn(u64) -> Result<Option<Bytes32>, Self::Error> {
+        |height| block_hash(&self.client, height)
+    }
+
+    #[cfg(feature = "async")]
+    fn block_resolver_async(&self) -> impl AsyncFn(u64) -> Result<Option<Bytes32>, Self::Error> {
+        async |height| block_hash(&self.client, height)
+    }
+
+    #[cfg(not(feature = "async"))]
+    fn spender_resolver(&self) -> impl Fn(Outpoint) -> Result<Option<Txid>, Self::Error> {
+        |outpoint| spender(&self.client, outpoint)
+    }
+
+    #[cfg(feature = "async")]
+    fn spender_resolver_async(
+        &self,
+    ) -> impl AsyncFn(Outpoint) -> Result<Option<Txid>, Self::Error> {
+        async |outpoint| spender(&self.client, outpoint)
+    }
+
+    #[cfg(not(feature = "async"))]
+    fn last_block_height(&self) -> Result<u64, Self::Error> { tip_height(&self.client) }
+
+    #[cfg(feature = "async")]
+    async fn last_block_height_async(&self) -> Result<u64, Self::Error> { tip_height(&self.client) }
+
+    #[cfg(not(feature = "async"))]
+    fn broadcast(&mut self, tx: &Tx, change: Option<(Vout, u32, u32)>) -> Result<(), Self::Error> {
+        self.send_tx(tx, change)
+    }
+
+    #[cfg(feature = "async")]
+    async fn broadcast_async(
+        &mut self,
+        tx: &Tx,
+        change: Option<(Vout, u32, u32)>,
+    ) -> Result<(), Self::Error> {
+        self.send_tx(tx, change)
+    }
+}
+
+fn spender(client: &EsploraClient, outpoint: Outpoint) -> Result<Option<Txid>, EsploraError> {
+    let outspend = client.get_json(&format!("/tx/{}/outspend/{}", outpoint.txid, outpoint.vout))?;
+    if outspend.get("spent").and_then(Value::as_bool) != Some(true) {
+        return Ok(None);
+    }
+    outspend
+        .get("txid")
+        .and_then(Value::as_str)
+        .and_then(|s| Txid::from_str(s).ok())
+        .map(Some)
+        .ok_or_else(|| invalid("spending txid"))
+}
+
+/// Resolves the status of a witness transaction; for transactions unknown to the server returns
+/// `None`, since they may be just evicted from the server mempool.
+fn tx_status(client: &EsploraClient, txid: Txid) -> Result<Option<WitnessInfo>, EsploraError> {
+    let status = match client.get_json(&format!("/tx/{txid}/status")) {
+        Ok(status) => status,
+        Err(err) if err.is_not_found() => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    if status.get("confirmed").and_then(Value::as_bool) != Some(true) {
+        return Ok(Some(WitnessStatus::Tentative.into()));
+    }
+    let height = status
+        .get("block_height")
+        .and_then(Value::as_u64)
+        .and_then(NonZeroU64::new)
+        .ok_or_else(|| invalid("block height"))?;
+    let hash = status
+        .get("block_hash")
+        .and_then(Value::as_str)
+        .ok_or_else(|| invalid("block hash"))?;
+    let time = status
+        .get("block_time")
+        .and_then(Value::as_i64)
+        .ok_or_else(|| invalid("block time"))?;
+    let mut info = BlockInfo::new(parse_block_hash(hash)?, time);
+    let proof = client.get_json(&format!("/tx/{txid}/merkle-proof"))?;
+    if let Some(pos) = proof.get("pos").and_then(Value::as_u64) {
+        info = info.with_tx_pos(pos as u32);
+    }
+    Ok(Some(WitnessInfo::mined(height, info)))
+}
+
+/// Resolver of the witness transaction statuses, querying the transactions one by one.
+///
+/// Transactions unknown to the server are left unresolved.
+struct TxResolver<'client>(&'client EsploraClient);
+
+impl TxResolver<'_> {
+    fn resolve(&self, txids: &[Txid]) -> Result<HashMap<Txid, WitnessInfo>, EsploraError> {
+        let mut resolved = HashMap::with_capacity(txids.len());
+        for txid in txids {
+            if let Some(info) = tx_status(self.0, *txid)? {
+                resolved.insert(*txid, info);
+            }
+        }
+        Ok(resolved)
+    }
+}
+
+impl WitnessResolver<Txid> for TxResolver<'_> {
+    type Error = EsploraError;
+
+    #[cfg(not(feature = "async"))]
+    fn resolve_witnesses(&self, wids: &[Txid]) -> Result<HashMap<Txid, WitnessInfo>, EsploraError> {
+        self.resolve(wids)
+    }
+
+    #[cfg(feature = "async")]
+    async fn resolve_witnesses_async(
+        &self,
+        wids: &[Txid],
+    ) -> Result<HashMap<Txid, WitnessInfo>, EsploraError> {
+        self.resolve(wids)
+    }
+}
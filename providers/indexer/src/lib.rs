@@ -0,0 +1,61 @@
+// Standard Library for RGB smart contracts
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2019-2025 by Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+// Written in 2024-2025 by Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 LNP/BP Laboratories,
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2025 RGB Consortium, Switzerland.
+// Copyright (C) 2019-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+//! [`rgb::popls::bp::WalletProvider`] implementations working over the blockchain indexers
+//! using Electrum protocol or Esplora REST API, which do not require a bitcoin node.
+//!
+//! The wallets watch the scripts of the addresses derived by a user-provided function, while the
+//! keys and the transaction signing remain outside of the providers. Both clients use plain TCP
+//! connections: TLS termination, if required, has to be done by a proxy. The `async` variants of
+//! the provider methods are performing the same blocking I/O as their sync counterparts.
+
+#![cfg_attr(feature = "async", allow(async_fn_in_trait))]
+
+#[cfg_attr(any(feature = "electrum", feature = "esplora"), macro_use)]
+extern crate amplify;
+
+#[cfg(feature = "electrum")]
+pub mod electrum;
+#[cfg(feature = "esplora")]
+pub mod esplora;
+#[cfg(feature = "esplora")]
+mod http;
+#[cfg(any(feature = "electrum", feature = "esplora"))]
+mod watch;
+
+use amplify::hex::ToHex;
+use bp::ScriptPubkey;
+use commit_verify::{Digest, Sha256};
+#[cfg(feature = "electrum")]
+pub use electrum::{ElectrumClient, ElectrumError, ElectrumWallet};
+#[cfg(feature = "esplora")]
+pub use esplora::{EsploraClient, EsploraError, EsploraWallet};
+
+/// Computes the hash of the script pubkey used to index the scripts by Electrum and Esplora
+/// servers: a SHA256 hash of the script in the reversed byte order, encoded as a hex string.
+pub fn script_hash(script_pubkey: &ScriptPubkey) -> String {
+    let mut hash = Sha256::digest(script_pubkey.as_slice());
+    hash.reverse();
+    hash.to_hex()
+}
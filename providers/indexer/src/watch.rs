@@ -0,0 +1,136 @@
+// Standard Library for RGB smart contracts
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2019-2025 by Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+// Written in 2024-2025 by Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 LNP/BP Laboratories,
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2025 RGB Consortium, Switzerland.
+// Copyright (C) 2019-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Debug, Formatter};
+
+use amplify::Bytes32;
+use bp::seals::WTxoSeal;
+use bp::{Outpoint, ScriptPubkey, Tx, Vout};
+use indexmap::IndexMap;
+use rgb::invoice::bp::Address;
+use rgb::{AuthToken, RgbSealDef};
+
+use crate::script_hash;
+
+/// Script watched by the wallet.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub(crate) struct Watched {
+    /// Script hash used by the indexer.
+    pub hash: String,
+    /// Last known status of the script history, used to detect the changes in it.
+    pub status: Option<String>,
+    /// Unspent outputs of the script as of the last known status.
+    pub utxos: BTreeSet<Outpoint>,
+}
+
+/// Wallet state shared by the indexer-based wallet providers.
+#[derive(Clone)]
+pub(crate) struct Watcher<D: Fn(u32) -> Address> {
+    derive: D,
+    noise_seed: Bytes32,
+    next_index: u32,
+    pub scripts: IndexMap<ScriptPubkey, Watched>,
+    seals: BTreeMap<AuthToken, WTxoSeal>,
+    nonce: u64,
+}
+
+impl<D: Fn(u32) -> Address> Debug for Watcher<D> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Watcher")
+            .field("noise_seed", &self.noise_seed)
+            .field("next_index", &self.next_index)
+            .field("scripts", &self.scripts)
+            .field("seals", &self.seals)
+            .field("nonce", &self.nonce)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<D: Fn(u32) -> Address> Watcher<D> {
+    pub fn new(derive: D, noise_seed: Bytes32) -> Self {
+        Self {
+            derive,
+            noise_seed,
+            next_index: 0,
+            scripts: none!(),
+            seals: none!(),
+            nonce: 0,
+        }
+    }
+
+    pub fn derive_until(&mut self, index: u32) {
+        while self.next_index < index {
+            self.next_address();
+        }
+    }
+
+    pub fn watch(&mut self, script_pubkey: ScriptPubkey) {
+        self.scripts
+            .entry(script_pubkey)
+            .or_insert_with_key(|script_pubkey| Watched {
+                hash: script_hash(script_pubkey),
+                status: None,
+                utxos: none!(),
+            });
+    }
+
+    pub fn watch_change(&mut self, tx: &Tx, change: Option<(Vout, u32, u32)>) {
+        if let Some((vout, _, _)) = change {
+            if let Some(output) = tx.outputs.get(vout.into_usize()) {
+                self.watch(output.script_pubkey.clone());
+            }
+        }
+    }
+
+    pub fn has_utxo(&self, outpoint: Outpoint) -> bool {
+        self.scripts
+            .values()
+            .any(|watched| watched.utxos.contains(&outpoint))
+    }
+
+    pub fn utxos(&self) -> impl Iterator<Item = Outpoint> + '_ {
+        self.scripts
+            .values()
+            .flat_map(|watched| watched.utxos.iter().copied())
+    }
+
+    pub fn register_seal(&mut self, seal: WTxoSeal) { self.seals.insert(seal.auth_token(), seal); }
+
+    pub fn seal(&self, auth: AuthToken) -> Option<WTxoSeal> { self.seals.get(&auth).copied() }
+
+    pub fn noise_seed(&self) -> Bytes32 { self.noise_seed }
+
+    pub fn next_address(&mut self) -> Address {
+        let address = (self.derive)(self.next_index);
+        self.next_index += 1;
+        self.watch(address.script_pubkey());
+        address
+    }
+
+    pub fn next_nonce(&mut self) -> u64 {
+        self.nonce += 1;
+        self.nonce
+    }
+}
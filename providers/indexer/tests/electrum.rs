@@ -0,0 +1,120 @@
+#![cfg(all(not(target_arch = "wasm32"), not(feature = "async"), feature = "electrum"))]
+
+#[macro_use]
+extern crate amplify;
+
+mod replay;
+
+use std::num::NonZeroU64;
+
+use amplify::Bytes32;
+use bp::Outpoint;
+use replay::*;
+use rgb::popls::bp::WalletProvider;
+use rgb::{BlockInfo, WitnessInfo, WitnessResolver, WitnessStatus};
+use rgb_provider_indexer::{ElectrumClient, ElectrumError, ElectrumWallet};
+use serde_json::json;
+
+#[test]
+fn wallet() {
+    let (addr, replay) = replay::electrum("tests/data/electrum.json");
+    let client = ElectrumClient::new(&addr);
+    let mut wallet =
+        ElectrumWallet::new(client, Bytes32::from_byte_array([0xAA; 32]), derive).with_index(2);
+
+    wallet.update_utxos().unwrap();
+    assert_eq!(wallet.utxos().collect::<Vec<_>>(), vec![Outpoint::new(txid(TRANSFER), 0u32)]);
+    assert_eq!(
+        replay
+            .lock()
+            .unwrap()
+            .count("blockchain.scripthash.listunspent"),
+        2
+    );
+
+    // Scripts with unchanged statuses are not re-queried
+    wallet.update_utxos().unwrap();
+    assert_eq!(
+        replay
+            .lock()
+            .unwrap()
+            .count("blockchain.scripthash.subscribe"),
+        4
+    );
+    assert_eq!(
+        replay
+            .lock()
+            .unwrap()
+            .count("blockchain.scripthash.listunspent"),
+        2
+    );
+
+    assert_eq!(wallet.last_block_height().unwrap(), 102);
+    {
+        let block_resolver = wallet.block_resolver();
+        assert_eq!(block_resolver(101).unwrap(), Some(block_hash(BLOCK_101)));
+        assert_eq!(block_resolver(103).unwrap(), None);
+    }
+
+    let tx = resend_tx();
+    assert_eq!(tx.txid(), txid(RESEND));
+    wallet.broadcast(&tx, None).unwrap();
+    assert_eq!(wallet.utxos().collect::<Vec<_>>(), vec![Outpoint::new(txid(RESEND), 0u32)]);
+    assert!(!wallet.has_utxo(Outpoint::new(txid(TRANSFER), 0u32)));
+    assert_eq!(
+        replay
+            .lock()
+            .unwrap()
+            .count("blockchain.scripthash.listunspent"),
+        4
+    );
+}
+
+#[test]
+fn resolver() {
+    let (addr, replay) = replay::electrum("tests/data/electrum.json");
+    let wallet = ElectrumWallet::new(ElectrumClient::new(&addr), Bytes32::zero(), derive);
+
+    let resolved = wallet
+        .txid_resolver()
+        .resolve_witnesses(&[txid(FUNDING), txid(TRANSFER), txid(UNKNOWN)])
+        .unwrap();
+    assert_eq!(resolved.len(), 2);
+    assert_eq!(
+        resolved[&txid(FUNDING)],
+        WitnessInfo::mined(
+            NonZeroU64::new(101).unwrap(),
+            BlockInfo::new(block_hash(BLOCK_101), BLOCK_101_TIME).with_tx_pos(1)
+        )
+    );
+    assert_eq!(resolved[&txid(TRANSFER)], WitnessStatus::Tentative.into());
+    // Transactions unknown to the server are left unresolved
+    assert!(!resolved.contains_key(&txid(UNKNOWN)));
+    // All the witnesses are resolved with three batch requests
+    assert_eq!(replay.lock().unwrap().requests, vec![
+        vec![s!("blockchain.transaction.get"); 3],
+        vec![s!("blockchain.scripthash.get_history"); 2],
+        vec![s!("blockchain.block.header"), s!("blockchain.transaction.get_merkle")],
+    ]);
+
+    // Server errors other than the unknown transaction are reported
+    let err = wallet
+        .txid_resolver()
+        .resolve_witnesses(&[txid(FUNDING), txid(FAILING)])
+        .unwrap_err();
+    assert!(matches!(err, ElectrumError::Server { code: 2, .. }));
+    assert!(!err.is_tx_not_found());
+
+    // Spenders are found in the history of the spent output script
+    let spender_resolver = wallet.spender_resolver();
+    let funding = Outpoint::new(txid(FUNDING), 0u32);
+    assert_eq!(spender_resolver(funding).unwrap(), Some(txid(TRANSFER)));
+    let transfer = Outpoint::new(txid(TRANSFER), 0u32);
+    assert_eq!(spender_resolver(transfer).unwrap(), None);
+
+    let err = wallet
+        .client()
+        .call("blockchain.transaction.get", json!([UNKNOWN]))
+        .unwrap_err();
+    assert!(err.is_tx_not_found());
+}
@@ -0,0 +1,91 @@
+#![cfg(all(not(target_arch = "wasm32"), not(feature = "async"), feature = "esplora"))]
+
+#[macro_use]
+extern crate amplify;
+
+mod replay;
+
+use std::num::NonZeroU64;
+
+use amplify::Bytes32;
+use bp::Outpoint;
+use replay::*;
+use rgb::popls::bp::WalletProvider;
+use rgb::{BlockInfo, WitnessInfo, WitnessResolver, WitnessStatus};
+use rgb_provider_indexer::{EsploraClient, EsploraError, EsploraWallet};
+
+#[test]
+fn wallet() {
+    let (url, replay) = replay::esplora("tests/data/esplora.json");
+    let client = EsploraClient::new(&url).unwrap();
+    let mut wallet =
+        EsploraWallet::new(client, Bytes32::from_byte_array([0xAA; 32]), derive).with_index(2);
+
+    wallet.update_utxos().unwrap();
+    assert_eq!(wallet.utxos().collect::<Vec<_>>(), vec![Outpoint::new(txid(TRANSFER), 0u32)]);
+    assert_eq!(replay.lock().unwrap().count("GET /scripthash/"), 4);
+
+    // Scripts with unchanged statistics are not re-queried
+    wallet.update_utxos().unwrap();
+    assert_eq!(replay.lock().unwrap().count("GET /scripthash/"), 6);
+
+    assert_eq!(wallet.last_block_height().unwrap(), 102);
+    {
+        let block_resolver = wallet.block_resolver();
+        assert_eq!(block_resolver(101).unwrap(), Some(block_hash(BLOCK_101)));
+        assert_eq!(block_resolver(103).unwrap(), None);
+    }
+
+    let tx = resend_tx();
+    wallet.broadcast(&tx, None).unwrap();
+    assert_eq!(wallet.utxos().collect::<Vec<_>>(), vec![Outpoint::new(txid(RESEND), 0u32)]);
+    assert_eq!(replay.lock().unwrap().count("GET /scripthash/"), 10);
+}
+
+#[test]
+fn resolver() {
+    let (url, _) = replay::esplora("tests/data/esplora.json");
+    let mut wallet = EsploraWallet::new(EsploraClient::new(&url).unwrap(), Bytes32::zero(), derive);
+
+    let resolved = wallet
+        .txid_resolver()
+        .resolve_witnesses(&[txid(FUNDING), txid(TRANSFER), txid(UNKNOWN)])
+        .unwrap();
+    assert_eq!(resolved.len(), 2);
+    assert_eq!(
+        resolved[&txid(FUNDING)],
+        WitnessInfo::mined(
+            NonZeroU64::new(101).unwrap(),
+            BlockInfo::new(block_hash(BLOCK_101), BLOCK_101_TIME).with_tx_pos(1)
+        )
+    );
+    assert_eq!(resolved[&txid(TRANSFER)], WitnessStatus::Tentative.into());
+    // Transactions unknown to the server are left unresolved
+    assert!(!resolved.contains_key(&txid(UNKNOWN)));
+    // ... while other server errors are reported
+    let err = wallet
+        .txid_resolver()
+        .resolve_witnesses(&[txid(FUNDING), txid(FAILING)])
+        .unwrap_err();
+    assert!(matches!(err, EsploraError::Http { status: 503, .. }));
+
+    {
+        let spender_resolver = wallet.spender_resolver();
+        let funding = Outpoint::new(txid(FUNDING), 0u32);
+        assert_eq!(spender_resolver(funding).unwrap(), Some(txid(TRANSFER)));
+        let transfer = Outpoint::new(txid(TRANSFER), 0u32);
+        assert_eq!(spender_resolver(transfer).unwrap(), None);
+    }
+
+    // Transactions rejected by the node report the node error
+    let tx = transfer_tx();
+    assert_eq!(tx.txid(), txid(TRANSFER));
+    let err = wallet.broadcast(&tx, None).unwrap_err();
+    assert!(
+        matches!(err, EsploraError::Http { status: 400, message } if message.contains("txn-mempool-conflict"))
+    );
+    assert!(matches!(
+        EsploraClient::new("https://blockstream.info/api"),
+        Err(EsploraError::InvalidUrl(_))
+    ));
+}
@@ -0,0 +1,236 @@
+//! Stand-in servers replaying the responses recorded from the Electrum and Esplora servers.
+
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::{fs, thread};
+
+use amplify::confinement::Confined;
+use amplify::{ByteArray, Bytes32};
+use bp::{BlockHash, Outpoint, Sats, ScriptPubkey, SeqNo, Tx, TxIn, TxOut, Txid, WPubkeyHash};
+use rgb::invoice::bp::{Address, AddressNetwork, AddressPayload};
+use serde_json::{json, Value};
+
+/// Derives the addresses of the wallet used to record the responses.
+pub fn derive(index: u32) -> Address {
+    let payload = AddressPayload::Wpkh(WPubkeyHash::from([index as u8 + 1; 20]));
+    Address::new(payload, AddressNetwork::Regtest)
+}
+
+/// Funding transaction, mined in block 101.
+pub const FUNDING: &str = "2ca99022612ac4ab37280644246836bb307865e98da5441cb779954b2b8bbde2";
+/// Transaction spending the funding output, which is in the mempool.
+pub const TRANSFER: &str = "e6364ae3cbec6dcc9e3f32f246078f491b523b7c7ace42d5d6e13d5844cf4a00";
+/// Transaction spending the transfer output, which is broadcasted by the tests.
+pub const RESEND: &str = "3602686e90bab3a8e217128397d683b835205aee680e5152e65182423212c05d";
+/// Transaction unknown to the server.
+pub const UNKNOWN: &str = "eeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeee";
+/// Transaction whose lookup fails on the server side.
+pub const FAILING: &str = "dddddddddddddddddddddddddddddddddddddddddddddddddddddddddddddddd";
+pub const BLOCK_101: &str = "5eb83ee083053e641a347ec14f9be1f05d6a9ad1f7919bf8994c339212e5c1b1";
+pub const BLOCK_101_TIME: i64 = 1_700_060_600;
+
+pub fn txid(txid: &str) -> Txid { txid.parse().unwrap() }
+
+pub fn block_hash(hash: &str) -> Bytes32 {
+    Bytes32::from_byte_array(hash.parse::<BlockHash>().unwrap().to_byte_array())
+}
+
+/// Constructs the transaction spending the funding output to the second wallet address.
+pub fn transfer_tx() -> Tx {
+    Tx {
+        version: bp::TxVer::V2,
+        inputs: Confined::from_checked(vec![TxIn {
+            prev_output: Outpoint::new(txid(FUNDING), 0u32),
+            sig_script: none!(),
+            sequence: SeqNo::from_consensus_u32(0xFFFF_FFFD),
+            witness: none!(),
+        }]),
+        outputs: Confined::from_checked(vec![
+            TxOut::new(derive(1).script_pubkey(), Sats::from(99_000u64)),
+            TxOut::new(ScriptPubkey::op_return(&[0x42; 32]), Sats::ZERO),
+        ]),
+        lock_time: default!(),
+    }
+}
+
+/// Constructs the transaction spending the transfer output back to the first wallet address.
+pub fn resend_tx() -> Tx {
+    Tx {
+        version: bp::TxVer::V2,
+        inputs: Confined::from_checked(vec![TxIn {
+            prev_output: Outpoint::new(txid(TRANSFER), 0u32),
+            sig_script: none!(),
+            sequence: SeqNo::from_consensus_u32(0xFFFF_FFFD),
+            witness: none!(),
+        }]),
+        outputs: Confined::from_checked(vec![TxOut::new(
+            derive(0).script_pubkey(),
+            Sats::from(98_000u64),
+        )]),
+        lock_time: default!(),
+    }
+}
+
+/// Recorded responses, replayed in the order of recording for the same request; the last
+/// response for a request is repeated once all the recorded ones are replayed.
+pub struct Replay {
+    responses: HashMap<String, Vec<Value>>,
+    /// Requests received by the server, grouped by the connection message or HTTP request.
+    pub requests: Vec<Vec<String>>,
+}
+
+impl Replay {
+    fn load(path: &str, key: impl Fn(&Value) -> String) -> Arc<Mutex<Self>> {
+        let recorded: Vec<Value> =
+            serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+        let mut responses = HashMap::<_, Vec<_>>::new();
+        for entry in recorded {
+            responses.entry(key(&entry)).or_default().push(entry);
+        }
+        Arc::new(Mutex::new(Self { responses, requests: vec![] }))
+    }
+
+    fn replay(&mut self, key: &str) -> Option<Value> {
+        let queue = self.responses.get_mut(key)?;
+        Some(if queue.len() > 1 { queue.remove(0) } else { queue[0].clone() })
+    }
+
+    /// Number of the received requests for the method or path.
+    pub fn count(&self, name: &str) -> usize {
+        self.requests
+            .iter()
+            .flatten()
+            .filter(|request| request.starts_with(name))
+            .count()
+    }
+}
+
+fn electrum_key(method: &str, params: &Value) -> String { format!("{method} {params}") }
+
+/// Starts the Electrum server, returning its address and the replay log.
+pub fn electrum(path: &str) -> (String, Arc<Mutex<Replay>>) {
+    let replay = Replay::load(path, |entry| {
+        electrum_key(entry["method"].as_str().unwrap(), &entry["params"])
+    });
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let server = replay.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let replay = server.clone();
+            thread::spawn(move || serve_electrum(&replay, stream.unwrap()));
+        }
+    });
+    (addr, replay)
+}
+
+fn serve_electrum(replay: &Mutex<Replay>, mut stream: TcpStream) {
+    let reader = BufReader::new(stream.try_clone().unwrap());
+    for line in reader.lines() {
+        let Ok(line) = line else { return };
+        let request: Value = serde_json::from_str(&line).unwrap();
+        let mut replay = replay.lock().unwrap();
+        let mut notify = vec![];
+        let mut respond = |request: &Value| {
+            let method = request["method"].as_str().unwrap();
+            let entry = replay
+                .replay(&electrum_key(method, &request["params"]))
+                .unwrap_or_else(|| {
+                    panic!("no recorded response for {method} {}", request["params"])
+                });
+            if let Some(Value::Array(notifications)) = entry.get("notify") {
+                notify.extend(notifications.iter().cloned());
+            }
+            match entry.get("error") {
+                Some(error) => json!({ "jsonrpc": "2.0", "error": error, "id": request["id"] }),
+                None => json!({ "jsonrpc": "2.0", "result": entry["result"], "id": request["id"] }),
+            }
+        };
+        let (methods, response) = match &request {
+            Value::Array(batch) => (
+                batch
+                    .iter()
+                    .map(|req| req["method"].as_str().unwrap().to_owned())
+                    .collect(),
+                Value::Array(batch.iter().map(&mut respond).collect()),
+            ),
+            request => (vec![request["method"].as_str().unwrap().to_owned()], respond(request)),
+        };
+        replay.requests.push(methods);
+        for message in notify.iter().chain([&response]) {
+            stream.write_all(format!("{message}\n").as_bytes()).unwrap();
+        }
+    }
+}
+
+/// Starts the Esplora server, returning its URL and the replay log.
+pub fn esplora(path: &str) -> (String, Arc<Mutex<Replay>>) {
+    let replay = Replay::load(path, |entry| {
+        format!(
+            "{} {} {}",
+            entry["method"].as_str().unwrap(),
+            entry["path"].as_str().unwrap(),
+            entry["request"].as_str().unwrap_or_default()
+        )
+    });
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = replay.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            serve_esplora(&server, stream.unwrap());
+        }
+    });
+    (format!("http://{addr}/api"), replay)
+}
+
+fn serve_esplora(replay: &Mutex<Replay>, mut stream: TcpStream) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut start = String::new();
+    reader.read_line(&mut start).unwrap();
+    let mut parts = start.split(' ');
+    let method = parts.next().unwrap().to_owned();
+    let path = parts
+        .next()
+        .unwrap()
+        .strip_prefix("/api")
+        .unwrap()
+        .to_owned();
+    let mut len = 0usize;
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(": ") {
+            if name.eq_ignore_ascii_case("content-length") {
+                len = value.parse().unwrap();
+            }
+        }
+    }
+    let mut body = vec![0u8; len];
+    reader.read_exact(&mut body).unwrap();
+    let body = String::from_utf8(body).unwrap();
+
+    let mut replay = replay.lock().unwrap();
+    replay.requests.push(vec![format!("{method} {path}")]);
+    let (status, body) = match replay.replay(&format!("{method} {path} {body}")) {
+        Some(entry) => {
+            (entry["status"].as_u64().unwrap(), entry["body"].as_str().unwrap().to_owned())
+        }
+        None => (404, s!("Not found")),
+    };
+    // Send the body in chunks, like servers behind a proxy do
+    let response = format!(
+        "HTTP/1.1 {status} Replay\r\nContent-Type: text/plain\r\nTransfer-Encoding: \
+         chunked\r\n\r\n{:x}\r\n{body}\r\n0\r\n\r\n",
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).unwrap();
+}
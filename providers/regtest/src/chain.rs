@@ -0,0 +1,486 @@
+// Standard Library for RGB smart contracts
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2019-2025 by Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+// Written in 2024-2025 by Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 LNP/BP Laboratories,
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2025 RGB Consortium, Switzerland.
+// Copyright (C) 2019-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap};
+use std::num::NonZeroU64;
+use std::rc::Rc;
+
+use amplify::confinement::Confined;
+use amplify::{ByteArray, Bytes32};
+use bp::{LockTime, Outpoint, Sats, ScriptPubkey, SeqNo, SigScript, Tx, TxIn, TxOut, Txid, Vout};
+use commit_verify::{Digest, DigestExt, Sha256};
+use indexmap::IndexSet;
+use rgb::{BlockInfo, WitnessInfo, WitnessResolver, WitnessStatus};
+
+/// Timestamp of the simulated genesis block.
+pub const GENESIS_TIME: i64 = 1_231_006_505;
+/// Time between two consecutive simulated blocks, in seconds.
+pub const BLOCK_INTERVAL: i64 = 600;
+
+/// Errors happening when a transaction is broadcasted to the simulated chain, or a scripted event
+/// can't be applied.
+#[derive(Clone, Eq, PartialEq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum ChainError {
+    /// transaction {0} spends output {1}, which is not known to the chain.
+    UnknownInput(Txid, Outpoint),
+
+    /// transaction {0} spends output {1}, which is already spent by the mined transaction {2}.
+    DoubleSpend(Txid, Outpoint, Txid),
+
+    /// transaction {0} is neither mined nor present in the mempool.
+    UnknownTx(Txid),
+
+    /// transaction {0} is already mined and can't be replaced.
+    AlreadyMined(Txid),
+
+    /// the chain height {0} is not enough for a re-org of depth {1}.
+    ReorgTooDeep(u64, u64),
+}
+
+/// A block of the simulated chain.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct SimBlock {
+    pub height: u64,
+    pub hash: Bytes32,
+    pub time: i64,
+    /// Transactions mined in the block, in their order within the block.
+    pub txids: Vec<Txid>,
+}
+
+impl SimBlock {
+    /// Returns the block details for a transaction mined at the given position in the block.
+    pub fn info(&self, tx_pos: u32) -> BlockInfo {
+        BlockInfo::new(self.hash, self.time).with_tx_pos(tx_pos)
+    }
+}
+
+#[derive(Debug)]
+struct ChainState {
+    blocks: Vec<SimBlock>,
+    txs: HashMap<Txid, Tx>,
+    mempool: IndexSet<Txid>,
+    /// Height and the position in the block for each of the mined transactions.
+    mined: HashMap<Txid, (u64, u32)>,
+    /// Number of re-orgs, used to produce distinct block hashes on competing branches.
+    forks: u64,
+    /// Number of coinbase transactions, used to produce distinct coinbase txids.
+    coinbases: u64,
+}
+
+impl ChainState {
+    fn new() -> Self {
+        let mut state = Self {
+            blocks: vec![],
+            txs: none!(),
+            mempool: none!(),
+            mined: none!(),
+            forks: 0,
+            coinbases: 0,
+        };
+        state.push_block(vec![]);
+        state
+    }
+
+    fn height(&self) -> u64 { self.blocks.len() as u64 - 1 }
+
+    fn push_block(&mut self, txids: Vec<Txid>) -> &SimBlock {
+        let height = self.blocks.len() as u64;
+        let mut engine = Sha256::new();
+        engine.input_raw(b"regtest-block");
+        if let Some(prev) = self.blocks.last() {
+            engine.input_raw(prev.hash.as_slice());
+        }
+        engine.input_raw(&height.to_le_bytes());
+        engine.input_raw(&self.forks.to_le_bytes());
+        for txid in &txids {
+            engine.input_raw(&txid.to_byte_array());
+        }
+        for (pos, txid) in txids.iter().enumerate() {
+            self.mempool.shift_remove(txid);
+            self.mined.insert(*txid, (height, pos as u32));
+        }
+        self.blocks.push(SimBlock {
+            height,
+            hash: Bytes32::from_byte_array(engine.finish()),
+            time: GENESIS_TIME + height as i64 * BLOCK_INTERVAL,
+            txids,
+        });
+        self.blocks.last().expect("just added")
+    }
+
+    fn is_known(&self, txid: Txid) -> bool {
+        self.mined.contains_key(&txid) || self.mempool.contains(&txid)
+    }
+
+    /// Iterates over all mined and mempool transactions, in the order of their inclusion.
+    fn active_txs(&self) -> impl Iterator<Item = (Txid, &Tx)> {
+        self.blocks
+            .iter()
+            .flat_map(|block| block.txids.iter())
+            .chain(&self.mempool)
+            .map(|txid| (*txid, &self.txs[txid]))
+    }
+
+    /// Checks whether a transaction, which is neither mined nor present in the mempool, can never
+    /// get back to the chain: it is a disconnected coinbase, or it spends outputs which are spent
+    /// by other active transactions or which are created by the transactions which can't get back.
+    fn is_archived(&self, txid: Txid) -> bool {
+        let Some(tx) = self.txs.get(&txid) else {
+            return false;
+        };
+        tx.inputs().any(|input| {
+            let prevout = input.prev_output;
+            prevout.is_coinbase()
+                || self.spender(prevout).is_some_and(|spender| spender != txid)
+                || (!self.is_known(prevout.txid) && self.is_archived(prevout.txid))
+        })
+    }
+
+    fn spender(&self, outpoint: Outpoint) -> Option<Txid> {
+        self.active_txs()
+            .find(|(_, tx)| tx.inputs().any(|input| input.prev_output == outpoint))
+            .map(|(txid, _)| txid)
+    }
+
+    /// Removes the transaction from the mempool, together with all its mempool descendants.
+    fn evict(&mut self, txid: Txid) {
+        if !self.mempool.shift_remove(&txid) {
+            return;
+        }
+        let children = self
+            .mempool
+            .iter()
+            .filter(|child| {
+                self.txs[*child]
+                    .inputs()
+                    .any(|input| input.prev_output.txid == txid)
+            })
+            .copied()
+            .collect::<Vec<_>>();
+        for child in children {
+            self.evict(child);
+        }
+    }
+
+    fn broadcast(&mut self, tx: &Tx) -> Result<Txid, ChainError> {
+        let txid = tx.txid();
+        if self.is_known(txid) {
+            return Ok(txid);
+        }
+        let mut conflicts = IndexSet::new();
+        for input in tx.inputs() {
+            let prevout = input.prev_output;
+            let known = self.is_known(prevout.txid)
+                && self.txs[&prevout.txid].outputs.len() > prevout.vout_usize();
+            if !known {
+                return Err(ChainError::UnknownInput(txid, prevout));
+            }
+            if let Some(spender) = self.spender(prevout) {
+                if self.mined.contains_key(&spender) {
+                    return Err(ChainError::DoubleSpend(txid, prevout, spender));
+                }
+                conflicts.insert(spender);
+            }
+        }
+        // Full RBF: the new transaction always replaces the conflicting ones
+        for conflict in conflicts {
+            self.evict(conflict);
+        }
+        self.txs.insert(txid, tx.clone());
+        self.mempool.insert(txid);
+        Ok(txid)
+    }
+}
+
+/// Deterministic in-process simulator of a bitcoin regtest chain, with a mempool.
+///
+/// The simulator is a cheaply clonable handle, such that multiple wallets (see
+/// [`crate::SimWallet`]) can share the same chain. Block hashes and timestamps depend only on the
+/// sequence of the events applied to the chain, so the simulation is fully reproducible.
+///
+/// The simulator doesn't validate scripts, signatures or amounts; it only tracks which outputs
+/// exist and which of them are spent, so it can be used with transactions which are not signed.
+#[derive(Clone, Debug)]
+pub struct SimChain(Rc<RefCell<ChainState>>);
+
+impl Default for SimChain {
+    fn default() -> Self { Self::new() }
+}
+
+impl SimChain {
+    /// Creates a new chain, containing only the genesis block.
+    pub fn new() -> Self { Self(Rc::new(RefCell::new(ChainState::new()))) }
+
+    /// Height of the chain tip.
+    pub fn height(&self) -> u64 { self.0.borrow().height() }
+
+    /// Returns the block at the given height of the best chain.
+    pub fn block(&self, height: u64) -> Option<SimBlock> {
+        self.0.borrow().blocks.get(height as usize).cloned()
+    }
+
+    /// Returns the block at the chain tip.
+    pub fn tip(&self) -> SimBlock {
+        self.0
+            .borrow()
+            .blocks
+            .last()
+            .cloned()
+            .expect("chain always has a genesis")
+    }
+
+    /// Returns a transaction which was ever broadcasted to the chain, even if it is no more
+    /// mined or present in the mempool.
+    pub fn tx(&self, txid: Txid) -> Option<Tx> { self.0.borrow().txs.get(&txid).cloned() }
+
+    /// Lists the mempool transactions, in the order they were accepted.
+    pub fn mempool(&self) -> Vec<Txid> { self.0.borrow().mempool.iter().copied().collect() }
+
+    /// Returns the status of a transaction, as seen by the chain.
+    ///
+    /// Transactions which are neither mined nor present in the mempool are reported as
+    /// [`WitnessStatus::Archived`] only if they can never get back to the chain, since they
+    /// conflict with the active transactions. Otherwise, like for the transactions which are
+    /// unknown or evicted from the mempool, the status can't be determined, and `None` is returned.
+    pub fn tx_status(&self, txid: Txid) -> Option<WitnessInfo> {
+        let state = self.0.borrow();
+        if let Some((height, pos)) = state.mined.get(&txid) {
+            let block = &state.blocks[*height as usize];
+            let height = NonZeroU64::new(*height).expect("genesis block has no transactions");
+            return Some(WitnessInfo::mined(height, block.info(*pos)));
+        }
+        if state.mempool.contains(&txid) {
+            return Some(WitnessStatus::Tentative.into());
+        }
+        state
+            .is_archived(txid)
+            .then(|| WitnessStatus::Archived.into())
+    }
+
+    /// Lists unspent outputs (including the ones created and not spent by the mempool
+    /// transactions) with script pubkeys matching the `filter`.
+    pub fn unspent(&self, filter: impl Fn(&ScriptPubkey) -> bool) -> BTreeSet<Outpoint> {
+        let state = self.0.borrow();
+        let mut unspent = BTreeSet::new();
+        for (txid, tx) in state.active_txs() {
+            for input in tx.inputs() {
+                unspent.remove(&input.prev_output);
+            }
+            for (vout, output) in tx.outputs().enumerate() {
+                if filter(&output.script_pubkey) {
+                    unspent.insert(Outpoint::new(txid, vout as u32));
+                }
+            }
+        }
+        unspent
+    }
+
+    /// Returns the id of the mined or mempool transaction spending the output, if any.
+    pub fn spender(&self, outpoint: Outpoint) -> Option<Txid> { self.0.borrow().spender(outpoint) }
+
+    /// Returns the transaction output, if the transaction is known to the chain.
+    pub fn txout(&self, outpoint: Outpoint) -> Option<TxOut> {
+        self.0
+            .borrow()
+            .txs
+            .get(&outpoint.txid)?
+            .outputs
+            .get(outpoint.vout_usize())
+            .cloned()
+    }
+
+    /// Adds a transaction to the mempool.
+    ///
+    /// Transactions which are already mined or present in the mempool are ignored. Mempool
+    /// transactions spending the same outputs are replaced, together with their descendants.
+    ///
+    /// # Errors
+    ///
+    /// If the transaction spends outputs which are unknown or already spent by a mined
+    /// transaction.
+    pub fn broadcast(&self, tx: &Tx) -> Result<Txid, ChainError> {
+        self.0.borrow_mut().broadcast(tx)
+    }
+
+    /// Mines `count` blocks, the first of which includes all the mempool transactions.
+    ///
+    /// Returns the new chain height.
+    pub fn mine(&self, count: u64) -> u64 {
+        let mut state = self.0.borrow_mut();
+        for _ in 0..count {
+            let txids = state.mempool.iter().copied().collect();
+            state.push_block(txids);
+        }
+        state.height()
+    }
+
+    /// Mines a block containing only a coinbase transaction paying `sats` to the
+    /// `script_pubkey`.
+    ///
+    /// Returns the coinbase output.
+    pub fn fund(&self, script_pubkey: ScriptPubkey, sats: impl Into<Sats>) -> Outpoint {
+        let mut state = self.0.borrow_mut();
+        state.coinbases += 1;
+        let tx = Tx {
+            version: default!(),
+            inputs: Confined::from_checked(vec![TxIn {
+                prev_output: Outpoint::coinbase(),
+                sig_script: SigScript::from_checked(state.coinbases.to_le_bytes().to_vec()),
+                sequence: SeqNo::from_consensus_u32(u32::MAX),
+                witness: none!(),
+            }]),
+            outputs: Confined::from_checked(vec![TxOut::new(script_pubkey, sats)]),
+            lock_time: LockTime::from_consensus_u32(state.height() as u32 + 1),
+        };
+        let txid = tx.txid();
+        state.txs.insert(txid, tx);
+        state.push_block(vec![txid]);
+        Outpoint::new(txid, Vout::from_u32(0))
+    }
+
+    /// Disconnects `depth` blocks from the chain tip, as it happens in a re-org.
+    ///
+    /// The transactions from the disconnected blocks are returned to the mempool, except the
+    /// coinbase transactions, which are dropped together with all their descendants. Blocks mined
+    /// after the re-org have hashes different from the disconnected ones.
+    pub fn reorg(&self, depth: u64) -> Result<(), ChainError> {
+        let mut state = self.0.borrow_mut();
+        let height = state.height();
+        if depth > height {
+            return Err(ChainError::ReorgTooDeep(height, depth));
+        }
+        let mut returned = IndexSet::new();
+        let mut coinbases = vec![];
+        for block in state.blocks.split_off((height - depth + 1) as usize) {
+            for txid in block.txids {
+                let is_coinbase = state.txs[&txid]
+                    .inputs()
+                    .any(|input| input.prev_output.is_coinbase());
+                if is_coinbase {
+                    coinbases.push(txid);
+                } else {
+                    returned.insert(txid);
+                }
+            }
+        }
+        for txid in returned.iter().chain(&coinbases) {
+            state.mined.remove(txid);
+        }
+        returned.extend(state.mempool.drain(..));
+        state.mempool = returned;
+        for txid in coinbases {
+            // Descendants of the dropped coinbase are no more valid
+            let children = state
+                .mempool
+                .iter()
+                .filter(|child| {
+                    state.txs[*child]
+                        .inputs()
+                        .any(|input| input.prev_output.txid == txid)
+                })
+                .copied()
+                .collect::<Vec<_>>();
+            for child in children {
+                state.evict(child);
+            }
+        }
+        state.forks += 1;
+        Ok(())
+    }
+
+    /// Removes a transaction from the mempool, together with all its descendants, as it happens
+    /// when a transaction is expired or evicted by a node.
+    pub fn drop_tx(&self, txid: Txid) -> Result<(), ChainError> {
+        let mut state = self.0.borrow_mut();
+        if state.mined.contains_key(&txid) {
+            return Err(ChainError::AlreadyMined(txid));
+        }
+        if !state.mempool.contains(&txid) {
+            return Err(ChainError::UnknownTx(txid));
+        }
+        state.evict(txid);
+        Ok(())
+    }
+
+    /// Double-spends a mempool transaction, replacing it with a transaction spending all the same
+    /// inputs into a single output to the `script_pubkey`.
+    ///
+    /// To double-spend an already mined transaction, do a [`Self::reorg`] first.
+    ///
+    /// Returns the id of the replacing transaction.
+    pub fn double_spend(
+        &self,
+        txid: Txid,
+        script_pubkey: ScriptPubkey,
+    ) -> Result<Txid, ChainError> {
+        let mut state = self.0.borrow_mut();
+        if state.mined.contains_key(&txid) {
+            return Err(ChainError::AlreadyMined(txid));
+        }
+        if !state.mempool.contains(&txid) {
+            return Err(ChainError::UnknownTx(txid));
+        }
+        let original = &state.txs[&txid];
+        let sats = original
+            .inputs()
+            .map(|input| {
+                state.txs[&input.prev_output.txid].outputs[input.prev_output.vout_usize()].value
+            })
+            .sum::<Sats>();
+        let tx = Tx {
+            version: original.version,
+            inputs: original.inputs.clone(),
+            outputs: Confined::from_checked(vec![TxOut::new(script_pubkey, sats)]),
+            lock_time: original.lock_time,
+        };
+        state.broadcast(&tx)
+    }
+}
+
+/// Transactions which status can't be determined (see [`SimChain::tx_status`]) are left
+/// unresolved.
+impl WitnessResolver<Txid> for SimChain {
+    type Error = ChainError;
+
+    #[cfg(not(feature = "async"))]
+    fn resolve_witnesses(&self, wids: &[Txid]) -> Result<HashMap<Txid, WitnessInfo>, ChainError> {
+        Ok(wids
+            .iter()
+            .filter_map(|txid| Some((*txid, self.tx_status(*txid)?)))
+            .collect())
+    }
+
+    #[cfg(feature = "async")]
+    async fn resolve_witnesses_async(
+        &self,
+        wids: &[Txid],
+    ) -> Result<HashMap<Txid, WitnessInfo>, ChainError> {
+        Ok(wids
+            .iter()
+            .filter_map(|txid| Some((*txid, self.tx_status(*txid)?)))
+            .collect())
+    }
+}
@@ -0,0 +1,263 @@
+// Standard Library for RGB smart contracts
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2019-2025 by Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+// Written in 2024-2025 by Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 LNP/BP Laboratories,
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2025 RGB Consortium, Switzerland.
+// Copyright (C) 2019-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use amplify::confinement::{Confined, SmallOrdMap};
+use amplify::{ByteArray, Bytes32};
+use bp::seals::{mmb, mpc, WTxoSeal};
+use bp::{LockTime, Outpoint, Sats, ScriptPubkey, SeqNo, Tx, TxIn, TxOut, Vout, WPubkeyHash};
+use commit_verify::{CommitId, Digest, DigestExt, Sha256};
+use indexmap::IndexSet;
+use rgb::invoice::bp::{Address, AddressNetwork, AddressPayload};
+use rgb::popls::bp::{PrefabBundle, WalletProvider};
+use rgb::{AuthToken, RgbSealDef, WitnessResolver};
+
+use crate::{ChainError, SimChain};
+
+/// Wallet operating on top of a simulated chain (see [`SimChain`]).
+///
+/// The wallet derives its addresses deterministically from its name, and owns all the outputs
+/// paying to these addresses. It doesn't sign transactions, since the simulated chain doesn't
+/// validate signatures.
+#[derive(Clone, Debug)]
+pub struct SimWallet {
+    chain: SimChain,
+    seed: Bytes32,
+    scripts: IndexSet<ScriptPubkey>,
+    utxos: BTreeSet<Outpoint>,
+    seals: BTreeMap<AuthToken, WTxoSeal>,
+    nonce: u64,
+}
+
+impl SimWallet {
+    /// Creates a new wallet with the given name, connected to the `chain`.
+    ///
+    /// Wallets with the same name derive the same addresses.
+    pub fn new(chain: &SimChain, name: &str) -> Self {
+        let mut engine = Sha256::new();
+        engine.input_raw(b"regtest-wallet");
+        engine.input_raw(name.as_bytes());
+        Self {
+            chain: chain.clone(),
+            seed: Bytes32::from_byte_array(engine.finish()),
+            scripts: none!(),
+            utxos: none!(),
+            seals: none!(),
+            nonce: 0,
+        }
+    }
+
+    /// The chain the wallet is connected to.
+    pub fn chain(&self) -> &SimChain { &self.chain }
+
+    /// Checks whether a script pubkey belongs to the wallet.
+    pub fn is_mine(&self, script_pubkey: &ScriptPubkey) -> bool {
+        self.scripts.contains(script_pubkey)
+    }
+
+    /// Funds the wallet with a coinbase output to a new wallet address, mining a new block.
+    pub fn fund(&mut self, sats: impl Into<Sats>) -> Outpoint {
+        let script_pubkey = self.next_address().script_pubkey();
+        let outpoint = self.chain.fund(script_pubkey, sats);
+        self.sync_utxos();
+        outpoint
+    }
+
+    /// Constructs an unsigned witness transaction for the bundle, committing to it with an
+    /// `OP_RETURN` output.
+    ///
+    /// The transaction spends all the outputs closed by the bundle, in the order of
+    /// [`PrefabBundle::closes`], and contains the provided `outputs` followed by the commitment
+    /// output. Thus, the vouts used by the bundle seals must match the positions in `outputs`.
+    ///
+    /// Returns the transaction together with the multi-protocol commitment data which has to be
+    /// passed to [`rgb::popls::bp::RgbWallet::include`], and the list of the transaction
+    /// previous outputs.
+    pub fn witness_tx(
+        &mut self,
+        bundle: &PrefabBundle,
+        outputs: impl IntoIterator<Item = TxOut>,
+    ) -> Result<(Tx, mpc::MerkleBlock, Vec<Outpoint>), mpc::Error> {
+        let prevouts = bundle.closes().collect::<IndexSet<_>>();
+
+        let mut messages = mpc::MessageMap::default();
+        for prefab in bundle {
+            let protocol_id = mpc::ProtocolId::from(prefab.operation.contract_id.to_byte_array());
+            let msg = mmb::Message::from_byte_array(prefab.operation.opid().to_byte_array());
+            let map = prefab.closes.iter().map(|prevout| {
+                let pos = prevouts
+                    .get_index_of(prevout)
+                    .expect("prevout is from the bundle");
+                (pos as u32, msg)
+            });
+            let proof = mmb::BundleProof { map: SmallOrdMap::from_iter_checked(map) };
+            messages
+                .insert(protocol_id, mpc::MessageSource::Mmb(proof))
+                .map_err(|_| mpc::Error::TooManyMessages(bundle.len()))?;
+        }
+        let source = mpc::Source {
+            min_depth: mpc::MPC_MINIMAL_DEPTH,
+            entropy: self.next_nonce(),
+            messages,
+        };
+        let tree = source.into_merkle_tree()?;
+        let commitment = tree.commit_id();
+
+        let commitment = TxOut::new(ScriptPubkey::op_return(commitment.as_slice()), Sats::ZERO);
+
+        let tx = Tx {
+            version: default!(),
+            inputs: Confined::from_iter_checked(prevouts.iter().map(|prevout| TxIn {
+                prev_output: *prevout,
+                sig_script: none!(),
+                // Signal replaceability
+                sequence: SeqNo::from_consensus_u32(0xFFFF_FFFD),
+                witness: none!(),
+            })),
+            outputs: Confined::from_iter_checked(outputs.into_iter().chain([commitment])),
+            lock_time: LockTime::from_consensus_u32(self.chain.height() as u32),
+        };
+        Ok((tx, mpc::MerkleBlock::from(tree), prevouts.into_iter().collect()))
+    }
+
+    fn sync_utxos(&mut self) { self.utxos = self.chain.unspent(|spk| self.scripts.contains(spk)); }
+}
+
+impl WalletProvider for SimWallet {
+    type Error = ChainError;
+
+    fn has_utxo(&self, outpoint: Outpoint) -> bool { self.utxos.contains(&outpoint) }
+
+    fn utxos(&self) -> impl Iterator<Item = Outpoint> { self.utxos.iter().copied() }
+
+    #[cfg(not(feature = "async"))]
+    fn update_utxos(&mut self) -> Result<(), Self::Error> {
+        self.sync_utxos();
+        Ok(())
+    }
+
+    #[cfg(feature = "async")]
+    async fn update_utxos_async(&mut self) -> Result<(), Self::Error> {
+        self.sync_utxos();
+        Ok(())
+    }
+
+    fn register_seal(&mut self, seal: WTxoSeal) { self.seals.insert(seal.auth_token(), seal); }
+
+    fn resolve_seals(
+        &self,
+        seals: impl Iterator<Item = AuthToken>,
+    ) -> impl Iterator<Item = WTxoSeal> {
+        seals.filter_map(|auth| self.seals.get(&auth).copied())
+    }
+
+    fn noise_seed(&self) -> Bytes32 { self.seed }
+
+    fn next_address(&mut self) -> Address {
+        let mut engine = Sha256::new();
+        engine.input_raw(self.seed.as_slice());
+        engine.input_raw(&(self.scripts.len() as u32).to_le_bytes());
+        let mut hash = [0u8; 20];
+        hash.copy_from_slice(&engine.finish()[..20]);
+        let payload = AddressPayload::Wpkh(WPubkeyHash::from(hash));
+        self.scripts.insert(payload.script_pubkey());
+        Address::new(payload, AddressNetwork::Regtest)
+    }
+
+    fn next_nonce(&mut self) -> u64 {
+        self.nonce += 1;
+        self.nonce
+    }
+
+    #[cfg(not(feature = "async"))]
+    fn txid_resolver(&self) -> impl WitnessResolver<bp::Txid, Error = Self::Error> {
+        self.chain.clone()
+    }
+
+    #[cfg(feature = "async")]
+    fn txid_resolver_async(&self) -> impl WitnessResolver<bp::Txid, Error = Self::Error> {
+        self.chain.clone()
+    }
+
+    #[cfg(not(feature = "async"))]
+    fn block_resolver(&self) -> impl Fn(u64) -> Result<Option<Bytes32>, Self::Error> {
+        let chain = self.chain.clone();
+        move |height| Ok(chain.block(height).map(|block| block.hash))
+    }
+
+    #[cfg(feature = "async")]
+    fn block_resolver_async(&self) -> impl AsyncFn(u64) -> Result<Option<Bytes32>, Self::Error> {
+        let chain = self.chain.clone();
+        async move |height| Ok(chain.block(height).map(|block| block.hash))
+    }
+
+    #[cfg(not(feature = "async"))]
+    fn spender_resolver(&self) -> impl Fn(Outpoint) -> Result<Option<bp::Txid>, Self::Error> {
+        let chain = self.chain.clone();
+        move |outpoint| Ok(chain.spender(outpoint))
+    }
+
+    #[cfg(feature = "async")]
+    fn spender_resolver_async(
+        &self,
+    ) -> impl AsyncFn(Outpoint) -> Result<Option<bp::Txid>, Self::Error> {
+        let chain = self.chain.clone();
+        async move |outpoint| Ok(chain.spender(outpoint))
+    }
+
+    #[cfg(not(feature = "async"))]
+    fn last_block_height(&self) -> Result<u64, Self::Error> { Ok(self.chain.height()) }
+
+    #[cfg(feature = "async")]
+    async fn last_block_height_async(&self) -> Result<u64, Self::Error> { Ok(self.chain.height()) }
+
+    #[cfg(not(feature = "async"))]
+    fn broadcast(&mut self, tx: &Tx, change: Option<(Vout, u32, u32)>) -> Result<(), Self::Error> {
+        if let Some((vout, _, _)) = change {
+            if let Some(output) = tx.outputs.get(vout.into_usize()) {
+                self.scripts.insert(output.script_pubkey.clone());
+            }
+        }
+        self.chain.broadcast(tx)?;
+        self.sync_utxos();
+        Ok(())
+    }
+
+    #[cfg(feature = "async")]
+    async fn broadcast_async(
+        &mut self,
+        tx: &Tx,
+        change: Option<(Vout, u32, u32)>,
+    ) -> Result<(), Self::Error> {
+        if let Some((vout, _, _)) = change {
+            if let Some(output) = tx.outputs.get(vout.into_usize()) {
+                self.scripts.insert(output.script_pubkey.clone());
+            }
+        }
+        self.chain.broadcast(tx)?;
+        self.sync_utxos();
+        Ok(())
+    }
+}
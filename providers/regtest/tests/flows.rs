@@ -0,0 +1,1008 @@
+#![cfg(all(not(target_arch = "wasm32"), not(feature = "async")))]
+
+#[macro_use]
+extern crate amplify;
+#[macro_use]
+extern crate strict_types;
+
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::convert::Infallible;
+use std::fs;
+use std::num::NonZeroU64;
+use std::path::PathBuf;
+
+use amplify::confinement::Confined;
+use amplify::MultiError;
+use bp::seals::TxoSeal;
+use bp::{Sats, SeqNo, SigScript, Tx, TxIn, TxOut, Vout, Witness};
+use rgb::popls::bp::{
+    Balance, HistoryQuery, OpRequest, PrefabBundle, PrefabSeal, RbfError, RgbWallet,
+    TransferDirection, TransferEntry, UsedState, WalletProvider, WitnessConflict,
+};
+use rgb::{
+    Assignment, AuthToken, CellAddr, Consensus, ContractId, ContractState, Contracts, CreateParams,
+    EitherSeal, ExpiryPolicy, Issuer, NamedState, PromoteError, SyncCheckpoint, WitnessDirection,
+    WitnessResolver, WitnessStatus,
+};
+use rgb_persist_fs::StockpileDir;
+use rgb_provider_regtest::{ChainError, SimChain, SimWallet, BLOCK_INTERVAL, GENESIS_TIME};
+use strict_types::StrictVal;
+
+fn spend(wallet: &mut SimWallet, outpoint: bp::Outpoint) -> Tx {
+    let script_pubkey = wallet.next_address().script_pubkey();
+    Tx {
+        version: default!(),
+        inputs: Confined::from_checked(vec![TxIn {
+            prev_output: outpoint,
+            sig_script: none!(),
+            sequence: SeqNo::from_consensus_u32(0xFFFF_FFFD),
+            witness: none!(),
+        }]),
+        outputs: Confined::from_checked(vec![TxOut::new(script_pubkey, Sats::from(1000u64))]),
+        lock_time: default!(),
+    }
+}
+
+#[test]
+fn deterministic() {
+    let build = || {
+        let chain = SimChain::new();
+        let mut wallet = SimWallet::new(&chain, "alice");
+        let outpoint = wallet.fund(100_000u64);
+        let tx = spend(&mut wallet, outpoint);
+        chain.broadcast(&tx).unwrap();
+        chain.mine(2);
+        chain.reorg(1).unwrap();
+        chain.mine(1);
+        (chain.tip(), wallet.utxos().collect::<Vec<_>>())
+    };
+    assert_eq!(build(), build());
+}
+
+#[test]
+fn chain_events() {
+    let chain = SimChain::new();
+    let mut wallet = SimWallet::new(&chain, "alice");
+    let funding = wallet.fund(100_000u64);
+    assert_eq!(chain.height(), 1);
+    assert_eq!(chain.tip().time, GENESIS_TIME + BLOCK_INTERVAL);
+    assert!(wallet.has_utxo(funding));
+
+    let tx = spend(&mut wallet, funding);
+    let txid = tx.txid();
+    wallet.broadcast(&tx, None).unwrap();
+    assert_eq!(chain.mempool(), vec![txid]);
+    assert_eq!(chain.tx_status(txid).unwrap().status, WitnessStatus::Tentative);
+    assert!(!wallet.has_utxo(funding));
+    assert_eq!(wallet.utxos().count(), 1);
+
+    assert_eq!(chain.mine(1), 2);
+    let mined = chain.tx_status(txid).unwrap();
+    let block = chain.tip();
+    assert_eq!(mined.status, WitnessStatus::Mined(NonZeroU64::new(2).unwrap()));
+    assert_eq!(mined.block, Some(block.info(0)));
+    assert!(chain.mempool().is_empty());
+
+    // Mined outputs can't be double-spent
+    let conflict = spend(&mut wallet, funding);
+    assert_eq!(
+        chain.broadcast(&conflict),
+        Err(ChainError::DoubleSpend(conflict.txid(), funding, txid))
+    );
+    assert_eq!(
+        chain.double_spend(txid, conflict.outputs[0].script_pubkey.clone()),
+        Err(ChainError::AlreadyMined(txid))
+    );
+
+    // Re-org returns the transaction to the mempool
+    chain.reorg(1).unwrap();
+    assert_eq!(chain.height(), 1);
+    assert_eq!(chain.tx_status(txid).unwrap().status, WitnessStatus::Tentative);
+    chain.mine(1);
+    let remined = chain.tx_status(txid).unwrap();
+    assert_eq!(remined.status, mined.status);
+    assert_ne!(remined.block.unwrap().hash, mined.block.unwrap().hash);
+
+    // Double-spend the transaction after the re-org
+    chain.reorg(1).unwrap();
+    let script_pubkey = wallet.next_address().script_pubkey();
+    let replacement = chain.double_spend(txid, script_pubkey).unwrap();
+    assert_eq!(chain.mempool(), vec![replacement]);
+    assert_eq!(chain.tx_status(txid).unwrap().status, WitnessStatus::Archived);
+    wallet.update_utxos().unwrap();
+    assert_eq!(wallet.utxos().collect::<Vec<_>>(), vec![bp::Outpoint::new(replacement, 0u32)]);
+
+    // Re-orging the funding block drops the coinbase and its descendants
+    chain.reorg(1).unwrap();
+    assert!(chain.mempool().is_empty());
+    assert_eq!(chain.tx_status(funding.txid).unwrap().status, WitnessStatus::Archived);
+    assert_eq!(chain.tx_status(replacement).unwrap().status, WitnessStatus::Archived);
+    wallet.update_utxos().unwrap();
+    assert_eq!(wallet.utxos().count(), 0);
+    assert_eq!(chain.reorg(1), Err(ChainError::ReorgTooDeep(0, 1)));
+}
+
+#[test]
+fn batched_resolver() {
+    let chain = SimChain::new();
+    let mut wallet = SimWallet::new(&chain, "alice");
+    let funding = wallet.fund(100_000u64);
+    let tx = spend(&mut wallet, funding);
+    chain.broadcast(&tx).unwrap();
+
+    let unknown = spend(&mut wallet, funding).txid();
+    let resolved = chain
+        .resolve_witnesses(&[funding.txid, tx.txid(), unknown])
+        .unwrap();
+    assert_eq!(resolved.len(), 2);
+    assert!(resolved[&funding.txid].status.is_mined());
+    assert_eq!(resolved[&tx.txid()].status, WitnessStatus::Tentative);
+    // The status of the transactions unknown to the chain can't be determined
+    assert!(!resolved.contains_key(&unknown));
+
+    // Evicted transactions may get back to the mempool, so they are not archived
+    chain.drop_tx(tx.txid()).unwrap();
+    let resolved = chain.resolve_witnesses(&[tx.txid()]).unwrap();
+    assert!(resolved.is_empty());
+
+    let block_resolver = wallet.block_resolver();
+    assert_eq!(block_resolver(1).unwrap(), Some(chain.tip().hash));
+    assert_eq!(block_resolver(2).unwrap(), None);
+}
+
+type Wallet = RgbWallet<SimWallet, StockpileDir<TxoSeal>>;
+
+struct Transfer {
+    chain: SimChain,
+    rgb: Wallet,
+    contract_id: ContractId,
+    genesis_addr: CellAddr,
+    funding: bp::Outpoint,
+    bundle: PrefabBundle,
+    tx: Tx,
+}
+
+impl Transfer {
+    /// Issues a contract and transfers its state to the outputs of a broadcasted witness
+    /// transaction.
+    fn new(name: &str) -> Self { Self::with(name, false, |_| ()) }
+
+    /// Issues a contract and transfers its state to the outputs of an offchain witness
+    /// transaction, which is not broadcasted.
+    fn new_offchain(name: &str) -> Self { Self::with(name, true, |_| ()) }
+
+    /// Issues a contract and transfers its state to the outputs of a broadcasted witness
+    /// transaction, which inputs are signed with `sign`.
+    fn new_signed(name: &str, sign: fn(&mut Tx)) -> Self { Self::with(name, false, sign) }
+
+    fn with(name: &str, offchain: bool, sign: fn(&mut Tx)) -> Self {
+        let dir = PathBuf::from(format!("tests/data/{name}.stockpile"));
+        fs::remove_dir_all(&dir).ok();
+        fs::create_dir_all(&dir).unwrap();
+        let stockpile = StockpileDir::load(dir, Consensus::Bitcoin, true).unwrap();
+        let mut contracts = Contracts::load(stockpile);
+        let issuer =
+            Issuer::load("../../tests/data/Test.issuer", |_, _, _| -> Result<_, Infallible> {
+                unreachable!()
+            })
+            .unwrap();
+        let codex_id = contracts.import_issuer(issuer).unwrap();
+
+        let chain = SimChain::new();
+        let mut wallet = SimWallet::new(&chain, "alice");
+        let funding = wallet.fund(100_000u64);
+        let mut rgb = RgbWallet::with_components(wallet, contracts);
+
+        // Issue
+        let mut params = CreateParams::new_bitcoin_testnet(codex_id, "Test");
+        params.push_owned_unlocked("amount", Assignment::new_internal(funding, 100u64));
+        let contract_id = rgb.issue(params).unwrap();
+        let genesis_opid = rgb.contracts.contract_articles(contract_id).genesis_opid();
+        let genesis_addr = CellAddr::new(genesis_opid, 0);
+
+        // Transfer to the outputs of a witness transaction
+        let request = OpRequest {
+            contract_id,
+            method: vname!("transfer"),
+            reading: none!(),
+            using: vec![UsedState { addr: genesis_addr, outpoint: funding, satisfaction: None }],
+            global: none!(),
+            owned: [(0u32, 30u64), (1, 70)]
+                .into_iter()
+                .map(|(vout, amount)| NamedState {
+                    name: vname!("amount"),
+                    state: Assignment {
+                        seal: EitherSeal::Alt(PrefabSeal {
+                            vout: Vout::from_u32(vout),
+                            noise: None,
+                        }),
+                        data: svnum!(amount),
+                    },
+                })
+                .collect(),
+        };
+        let bundle = rgb.bundle([request], None).unwrap();
+        let outputs = (0..2)
+            .map(|_| TxOut::new(rgb.wallet.next_address().script_pubkey(), Sats::from(1000u64)))
+            .collect::<Vec<_>>();
+        let (mut tx, mpc, prevouts) = rgb.wallet.witness_tx(&bundle, outputs).unwrap();
+        sign(&mut tx);
+        if offchain {
+            rgb.include_offchain(&bundle, &tx, mpc, None, &prevouts)
+                .unwrap();
+        } else {
+            rgb.include(&bundle, &tx, mpc, None, &prevouts).unwrap();
+            rgb.wallet.broadcast(&tx, None).unwrap();
+        }
+
+        Transfer { chain, rgb, contract_id, genesis_addr, funding, bundle, tx }
+    }
+
+    /// State owned by the wallet UTXOs.
+    fn owned(&self) -> Vec<(bp::Outpoint, StrictVal, WitnessStatus)> {
+        Self::amounts(self.rgb.wallet_contract_state(self.contract_id))
+    }
+
+    /// Valid state assigned to any outputs, including the outputs of the witnesses which are not
+    /// published, like the offchain ones.
+    fn assigned(&self) -> Vec<(bp::Outpoint, StrictVal, WitnessStatus)> {
+        let mut assigned = Self::amounts(
+            self.rgb
+                .contract_state_full(self.contract_id)
+                .map(|seal| seal.primary),
+        );
+        assigned.retain(|(_, _, status)| status.is_valid());
+        assigned
+    }
+
+    fn amounts(
+        mut state: ContractState<bp::Outpoint>,
+    ) -> Vec<(bp::Outpoint, StrictVal, WitnessStatus)> {
+        let mut owned = state
+            .owned
+            .remove("amount")
+            .unwrap_or_default()
+            .into_iter()
+            .map(|owned| (owned.assignment.seal, owned.assignment.data, owned.status))
+            .collect::<Vec<_>>();
+        owned.sort_by_key(|(outpoint, _, _)| *outpoint);
+        owned
+    }
+
+    fn transferred(&self, status: WitnessStatus) -> Vec<(bp::Outpoint, StrictVal, WitnessStatus)> {
+        let txid = self.tx.txid();
+        let mut expected = vec![
+            (bp::Outpoint::new(txid, 0u32), svnum!(30u64), status),
+            (bp::Outpoint::new(txid, 1u32), svnum!(70u64), status),
+        ];
+        expected.sort_by_key(|(outpoint, _, _)| *outpoint);
+        expected
+    }
+
+    /// Checks that the transfer is rolled back, returning the state to the genesis seal.
+    fn assert_rolled_back(&self) {
+        let state = self.rgb.contracts.contract_state(self.contract_id);
+        let amount = &state.owned["amount"];
+        assert_eq!(amount.len(), 1);
+        assert_eq!(amount[0].addr, self.genesis_addr);
+        assert_eq!(amount[0].status, WitnessStatus::Genesis);
+    }
+}
+
+/// Creates a wallet with a separate stockpile, importing the test issuer.
+fn wallet(chain: &SimChain, name: &str) -> Wallet {
+    let dir = PathBuf::from(format!("tests/data/{name}.stockpile"));
+    fs::remove_dir_all(&dir).ok();
+    fs::create_dir_all(&dir).unwrap();
+    let stockpile = StockpileDir::load(dir, Consensus::Bitcoin, true).unwrap();
+    let mut contracts = Contracts::load(stockpile);
+    let issuer = Issuer::load("../../tests/data/Test.issuer", |_, _, _| -> Result<_, Infallible> {
+        unreachable!()
+    })
+    .unwrap();
+    contracts.import_issuer(issuer).unwrap();
+    RgbWallet::with_components(SimWallet::new(chain, name), contracts)
+}
+
+/// Pays `amount` from the cell owned by the `sender` to an auth token invoiced by the `receiver`,
+/// assigning the rest to the change, and passes the consignment to the `receiver`.
+fn pay(
+    sender: &mut Wallet,
+    receiver: &mut Wallet,
+    contract_id: ContractId,
+    using: UsedState,
+    amount: u64,
+    name: &str,
+) -> Tx {
+    let auth = receiver.auth_token(None).unwrap();
+    let change = Vout::from_u32(0);
+    let prev = sender.contracts.contract_state(contract_id).owned["amount"]
+        .iter()
+        .find(|owned| owned.addr == using.addr)
+        .map(|owned| owned.assignment.data.clone())
+        .unwrap();
+    let rest = prev.unwrap_num().unwrap_uint::<u64>() - amount;
+    let request = OpRequest {
+        contract_id,
+        method: vname!("transfer"),
+        reading: none!(),
+        using: vec![using],
+        global: none!(),
+        owned: vec![
+            NamedState {
+                name: vname!("amount"),
+                state: Assignment { seal: EitherSeal::Token(auth), data: svnum!(amount) },
+            },
+            NamedState {
+                name: vname!("amount"),
+                state: Assignment {
+                    seal: EitherSeal::Alt(PrefabSeal { vout: change, noise: None }),
+                    data: svnum!(rest),
+                },
+            },
+        ],
+    };
+    let bundle = sender.bundle([request], Some(change)).unwrap();
+    let outputs = [TxOut::new(sender.wallet.next_address().script_pubkey(), Sats::from(1000u64))];
+    let (tx, mpc, prevouts) = sender.wallet.witness_tx(&bundle, outputs).unwrap();
+    sender.include(&bundle, &tx, mpc, None, &prevouts).unwrap();
+    sender.wallet.broadcast(&tx, None).unwrap();
+
+    let consignment = format!("tests/data/{name}.rgb");
+    fs::remove_file(&consignment).ok();
+    sender
+        .contracts
+        .consign_to_file(&consignment, contract_id, [auth])
+        .unwrap();
+    receiver
+        .consume_from_file(false, &consignment, |_, _, _| -> Result<_, Infallible> {
+            unreachable!()
+        })
+        .unwrap();
+    tx
+}
+
+#[test]
+fn transfer_history() {
+    let chain = SimChain::new();
+    let mut alice = wallet(&chain, "HistoryAlice");
+    let mut bob = wallet(&chain, "HistoryBob");
+    let codex_id = alice.contracts.codex_ids().next().unwrap();
+    let funding = alice.wallet.fund(100_000u64);
+    bob.wallet.fund(100_000u64);
+    alice.wallet.fund(100_000u64);
+
+    let mut params = CreateParams::new_bitcoin_testnet(codex_id, "Test");
+    params.push_owned_unlocked("amount", Assignment::new_internal(funding, 100u64));
+    let contract_id = alice.issue(params).unwrap();
+    let genesis_opid = alice
+        .contracts
+        .contract_articles(contract_id)
+        .genesis_opid();
+    let genesis_addr = CellAddr::new(genesis_opid, 0);
+    // The wallet seals are resolved only when consuming into an already known contract
+    let consignment = "tests/data/HistoryGenesis.rgb";
+    fs::remove_file(consignment).ok();
+    alice
+        .contracts
+        .consign_to_file(consignment, contract_id, Vec::<AuthToken>::new())
+        .unwrap();
+    bob.consume_from_file(true, consignment, |_, _, _| -> Result<_, Infallible> { unreachable!() })
+        .unwrap();
+
+    // Alice pays 30 to Bob, and Bob pays 10 back, spending the state received from Alice
+    let using = UsedState { addr: genesis_addr, outpoint: funding, satisfaction: None };
+    let tx1 = pay(&mut alice, &mut bob, contract_id, using, 30, "HistoryToBob");
+    chain.mine(1);
+    let bob_cell = bob.contracts.contract_state(contract_id).owned["amount"]
+        .iter()
+        .find(|owned| owned.assignment.data == svnum!(30u64))
+        .map(|owned| (owned.addr, owned.assignment.seal.primary))
+        .unwrap();
+    let using = UsedState { addr: bob_cell.0, outpoint: bob_cell.1, satisfaction: None };
+    let tx2 = pay(&mut bob, &mut alice, contract_id, using, 10, "HistoryToAlice");
+    chain.mine(1);
+    alice.update(1).unwrap();
+    bob.update(1).unwrap();
+
+    let summary = |entries: Vec<TransferEntry>| {
+        entries
+            .into_iter()
+            .map(|entry| (entry.direction, entry.witness_id, entry.received, entry.sent))
+            .collect::<Vec<_>>()
+    };
+    let history = alice.history(&HistoryQuery::default()).unwrap();
+    assert_eq!(summary(history.clone()), vec![
+        (TransferDirection::Issue, None, vec![svnum!(100u64)], vec![]),
+        // Bob has spent the received state since, but the transfer stays outgoing
+        (TransferDirection::Outgoing, Some(tx1.txid()), vec![], vec![svnum!(30u64)]),
+        (TransferDirection::Incoming, Some(tx2.txid()), vec![svnum!(10u64)], vec![]),
+    ]);
+    // Alice learned the seal of Bob from his consignment
+    assert_eq!(history[1].counterparties, vec![EitherSeal::Alt(bob_cell.1)]);
+    assert!(history[2].counterparties.is_empty());
+
+    // Bob doesn't own the genesis state, and his own transfer is outgoing
+    assert_eq!(summary(bob.history(&HistoryQuery::default()).unwrap()), vec![
+        (TransferDirection::Incoming, Some(tx1.txid()), vec![svnum!(30u64)], vec![]),
+        (TransferDirection::Outgoing, Some(tx2.txid()), vec![], vec![svnum!(10u64)]),
+    ]);
+
+    // Filtering and pagination
+    let query = HistoryQuery { direction: Some(TransferDirection::Incoming), ..default!() };
+    assert_eq!(summary(alice.history(&query).unwrap()), summary(vec![history[2].clone()]));
+    let query = HistoryQuery { offset: 1, limit: Some(1), ..default!() };
+    assert_eq!(alice.history(&query).unwrap(), vec![history[1].clone()]);
+    let query = HistoryQuery { offset: 2, limit: Some(5), ..default!() };
+    assert_eq!(alice.history(&query).unwrap(), vec![history[2].clone()]);
+    let query = HistoryQuery { offset: 3, ..default!() };
+    assert!(alice.history(&query).unwrap().is_empty());
+    let query = HistoryQuery { limit: Some(0), ..default!() };
+    assert!(alice.history(&query).unwrap().is_empty());
+}
+
+#[test]
+fn issue_transfer_reorg() {
+    let mut transfer = Transfer::new("IssueTransferReorg");
+    let chain = transfer.chain.clone();
+    let txid = transfer.tx.txid();
+
+    assert!(transfer.rgb.update(1).unwrap().is_empty());
+    assert_eq!(transfer.owned(), transfer.transferred(WitnessStatus::Tentative));
+
+    // Mining
+    let height = NonZeroU64::new(chain.mine(1)).unwrap();
+    transfer.rgb.update(1).unwrap();
+    assert_eq!(transfer.owned(), transfer.transferred(WitnessStatus::Mined(height)));
+
+    // The witness transaction closes the seals properly, so the consignment is valid
+    let consignment = "tests/data/IssueTransferReorg.rgb";
+    fs::remove_file(consignment).ok();
+    let terminals = transfer
+        .bundle
+        .iter()
+        .flat_map(|prefab| {
+            prefab
+                .operation
+                .destructible_out
+                .iter()
+                .map(|cell| cell.auth)
+        })
+        .collect::<Vec<_>>();
+    transfer
+        .rgb
+        .contracts
+        .consign_to_file(consignment, transfer.contract_id, terminals)
+        .unwrap();
+    let dir = PathBuf::from("tests/data/IssueTransferReorg.bob.stockpile");
+    fs::remove_dir_all(&dir).ok();
+    fs::create_dir_all(&dir).unwrap();
+    let stockpile = StockpileDir::load(dir, Consensus::Bitcoin, true).unwrap();
+    let mut bob = RgbWallet::<_, _>::with_components(
+        SimWallet::new(&chain, "bob"),
+        Contracts::load(stockpile),
+    );
+    bob.consume_from_file(true, consignment, |_, _, _| -> Result<_, Infallible> { unreachable!() })
+        .unwrap();
+    assert!(bob.contracts.has_contract(transfer.contract_id));
+
+    // Re-org returns the witness to the mempool, and then it gets mined in a different block
+    chain.reorg(1).unwrap();
+    transfer.rgb.update(1).unwrap();
+    assert_eq!(transfer.owned(), transfer.transferred(WitnessStatus::Tentative));
+    chain.mine(1);
+    transfer.rgb.update(1).unwrap();
+    assert_eq!(transfer.owned(), transfer.transferred(WitnessStatus::Mined(height)));
+
+    // A deeply mined witness is re-orged and double-spent
+    chain.mine(10);
+    transfer.rgb.update(1).unwrap();
+    chain.reorg(11).unwrap();
+    let replacement = chain
+        .double_spend(txid, bp::ScriptPubkey::op_return(&[]))
+        .unwrap();
+    chain.mine(11);
+    // The witness was mined before the update, so it is not reported as a conflict
+    assert!(transfer.rgb.update(1).unwrap().is_empty());
+    assert!(transfer.owned().is_empty());
+    assert!(!transfer.rgb.wallet.has_utxo(transfer.funding));
+    assert_eq!(chain.spender(transfer.funding), Some(replacement));
+    transfer.assert_rolled_back();
+}
+
+#[test]
+fn conflicting_witness() {
+    let mut transfer = Transfer::new("ConflictingWitness");
+    let chain = transfer.chain.clone();
+    let txid = transfer.tx.txid();
+
+    assert!(transfer.rgb.update(1).unwrap().is_empty());
+    assert_eq!(transfer.owned(), transfer.transferred(WitnessStatus::Tentative));
+
+    // The tentative witness gets replaced
+    let replacement = chain
+        .double_spend(txid, bp::ScriptPubkey::op_return(&[]))
+        .unwrap();
+    let conflicts = transfer.rgb.update(1).unwrap();
+    assert_eq!(conflicts, vec![WitnessConflict {
+        witness_id: txid,
+        prevout: transfer.funding,
+        spender: replacement,
+    }]);
+    assert!(transfer.owned().is_empty());
+    transfer.assert_rolled_back();
+
+    // Archived witnesses are not reported again
+    chain.mine(1);
+    assert!(transfer.rgb.update(1).unwrap().is_empty());
+    transfer.assert_rolled_back();
+}
+
+#[test]
+fn balance() {
+    let mut transfer = Transfer::new("Balance");
+    let chain = transfer.chain.clone();
+    let contract_id = transfer.contract_id;
+    let total = vec![svnum!(100u64)];
+
+    // The transferred state is pending until the witness gets mined
+    transfer.rgb.update(1).unwrap();
+    let balance = transfer.rgb.balance(contract_id, "amount", 1).unwrap();
+    assert_eq!(balance, Balance { tentative: total.clone(), total: total.clone(), ..default!() });
+
+    // Mined state is spendable only once it gets the required number of confirmations
+    chain.mine(1);
+    transfer.rgb.update(1).unwrap();
+    let balance = transfer.rgb.balance(contract_id, "amount", 1).unwrap();
+    assert_eq!(balance, Balance { confirmed: total.clone(), total: total.clone(), ..default!() });
+    let balance = transfer.rgb.balance(contract_id, "amount", 2).unwrap();
+    assert_eq!(balance, Balance {
+        unconfirmed: total.clone(),
+        total: total.clone(),
+        ..default!()
+    });
+    chain.mine(1);
+    let balance = transfer.rgb.balance(contract_id, "amount", 2).unwrap();
+    assert_eq!(balance, Balance { confirmed: total.clone(), total, ..default!() });
+}
+
+#[test]
+fn balance_archived() {
+    let mut transfer = Transfer::new("BalanceArchived");
+    let txid = transfer.tx.txid();
+
+    // The state assigned by an archived witness is not counted, and the genesis state is spent by
+    // the replacement transaction
+    transfer
+        .chain
+        .double_spend(txid, bp::ScriptPubkey::op_return(&[]))
+        .unwrap();
+    transfer.rgb.update(1).unwrap();
+    let balance = transfer
+        .rgb
+        .balance(transfer.contract_id, "amount", 1)
+        .unwrap();
+    assert_eq!(balance, Balance::default());
+}
+
+#[test]
+fn fee_bump_replacement_mined() {
+    let mut transfer = Transfer::new("FeeBumpReplacementMined");
+    let chain = transfer.chain.clone();
+    let txid = transfer.tx.txid();
+    let bundle = transfer.bundle.clone();
+
+    let Err(MultiError::A(err)) =
+        transfer
+            .rgb
+            .bump_fee(&bundle, txid, Vout::from_u32(1), Sats::from(1001u64))
+    else {
+        panic!("fee bump must fail");
+    };
+    assert_eq!(err, RbfError::InsufficientValue(Vout::from_u32(1), Sats::from(1001u64)));
+    let Err(MultiError::A(err)) =
+        transfer
+            .rgb
+            .bump_fee(&bundle, txid, Vout::from_u32(3), Sats::from(100u64))
+    else {
+        panic!("fee bump must fail");
+    };
+    assert_eq!(err, RbfError::NoOutput(Vout::from_u32(3)));
+
+    let replacement = transfer
+        .rgb
+        .bump_fee(&bundle, txid, Vout::from_u32(1), Sats::from(100u64))
+        .unwrap();
+    assert_ne!(replacement.txid(), txid);
+    assert_eq!(replacement.inputs, transfer.tx.inputs);
+    assert_eq!(replacement.outputs[1].value, Sats::from(900u64));
+    // Until one of the witnesses is seen replacing the other, the state follows the original one
+    assert_eq!(transfer.owned(), transfer.transferred(WitnessStatus::Tentative));
+
+    // The replacement evicts the original witness from the mempool, and the chain reports the
+    // original as archived; the replacement is not taken as a conflicting transaction
+    transfer.rgb.wallet.broadcast(&replacement, None).unwrap();
+    assert!(transfer.rgb.update(1).unwrap().is_empty());
+    transfer.tx = replacement;
+    assert_eq!(transfer.owned(), transfer.transferred(WitnessStatus::Tentative));
+    assert_eq!(transfer.assigned(), transfer.transferred(WitnessStatus::Tentative));
+
+    let height = NonZeroU64::new(chain.mine(1)).unwrap();
+    assert!(transfer.rgb.update(1).unwrap().is_empty());
+    assert_eq!(transfer.owned(), transfer.transferred(WitnessStatus::Mined(height)));
+
+    // Mined witnesses can't be replaced
+    let Err(MultiError::A(err)) =
+        transfer
+            .rgb
+            .bump_fee(&bundle, transfer.tx.txid(), Vout::from_u32(1), Sats::from(100u64))
+    else {
+        panic!("fee bump must fail");
+    };
+    assert_eq!(err, RbfError::NotTentative(transfer.tx.txid()));
+}
+
+#[test]
+fn fee_bump_before_broadcast() {
+    let mut transfer = Transfer::new("FeeBumpBeforeBroadcast");
+    let chain = transfer.chain.clone();
+    let original = transfer.tx.clone();
+    let bundle = transfer.bundle.clone();
+
+    let replacement = transfer
+        .rgb
+        .bump_fee(&bundle, original.txid(), Vout::from_u32(1), Sats::from(100u64))
+        .unwrap();
+
+    // The original witness is still seen as the spender, which doesn't archive the replacement
+    assert!(transfer.rgb.update(1).unwrap().is_empty());
+    let mut both = transfer.transferred(WitnessStatus::Tentative);
+    transfer.tx = replacement.clone();
+    both.extend(transfer.transferred(WitnessStatus::Tentative));
+    both.sort_by_key(|(outpoint, _, _)| *outpoint);
+    assert_eq!(transfer.assigned(), both);
+
+    // Once broadcasted and mined, the replacement wins
+    transfer.rgb.wallet.broadcast(&replacement, None).unwrap();
+    let height = NonZeroU64::new(chain.mine(1)).unwrap();
+    let conflicts = transfer.rgb.update(1).unwrap();
+    assert_eq!(conflicts, vec![WitnessConflict {
+        witness_id: original.txid(),
+        prevout: transfer.funding,
+        spender: replacement.txid(),
+    }]);
+    assert_eq!(transfer.assigned(), transfer.transferred(WitnessStatus::Mined(height)));
+}
+
+#[test]
+fn fee_bump_signed_inputs() {
+    let mut transfer = Transfer::new_signed("FeeBumpSignedInputs", |tx| {
+        for input in tx.inputs.iter_mut() {
+            input.sig_script = SigScript::from_checked(vec![0x16; 23]);
+            input.witness = Witness::from_consensus_stack([vec![0x30; 71], vec![0x02; 33]]);
+        }
+    });
+    let txid = transfer.tx.txid();
+    let bundle = transfer.bundle.clone();
+
+    // Signatures are removed from the replacement
+    let replacement = transfer
+        .rgb
+        .bump_fee(&bundle, txid, Vout::from_u32(1), Sats::from(100u64))
+        .unwrap();
+    assert!(replacement
+        .inputs()
+        .all(|input| input.sig_script.is_empty() && input.witness.is_empty()));
+    assert_eq!(replacement.outputs[1].value, Sats::from(900u64));
+
+    // Re-signing non-segwit inputs would change the id of the replacement
+    let mut transfer = Transfer::new_signed("FeeBumpNonSegwit", |tx| {
+        for input in tx.inputs.iter_mut() {
+            input.sig_script = SigScript::from_checked(vec![0x51]);
+        }
+    });
+    let txid = transfer.tx.txid();
+    let Err(MultiError::A(err)) = transfer.rgb.bump_fee(
+        &transfer.bundle.clone(),
+        txid,
+        Vout::from_u32(1),
+        Sats::from(100u64),
+    ) else {
+        panic!("fee bump must fail");
+    };
+    assert_eq!(err, RbfError::NonSegwitInput(transfer.funding));
+}
+
+#[test]
+fn fee_bump_original_mined() {
+    let mut transfer = Transfer::new("FeeBumpOriginalMined");
+    let chain = transfer.chain.clone();
+    let txid = transfer.tx.txid();
+    let bundle = transfer.bundle.clone();
+
+    let replacement = transfer
+        .rgb
+        .bump_fee(&bundle, txid, Vout::from_u32(0), Sats::from(100u64))
+        .unwrap();
+    transfer.rgb.wallet.broadcast(&replacement, None).unwrap();
+
+    // The original witness gets back to the mempool and is mined instead of the replacement
+    transfer
+        .rgb
+        .wallet
+        .broadcast(&transfer.tx.clone(), None)
+        .unwrap();
+    let height = NonZeroU64::new(chain.mine(1)).unwrap();
+    let conflicts = transfer.rgb.update(1).unwrap();
+    assert_eq!(conflicts, vec![WitnessConflict {
+        witness_id: replacement.txid(),
+        prevout: transfer.funding,
+        spender: txid,
+    }]);
+    assert_eq!(transfer.owned(), transfer.transferred(WitnessStatus::Mined(height)));
+
+    // Archived witnesses can't be replaced
+    let Err(MultiError::A(err)) =
+        transfer
+            .rgb
+            .bump_fee(&bundle, replacement.txid(), Vout::from_u32(0), Sats::from(100u64))
+    else {
+        panic!("fee bump must fail");
+    };
+    assert_eq!(err, RbfError::NotTentative(replacement.txid()));
+}
+
+#[test]
+fn tentative_expiry() {
+    let mut transfer = Transfer::new("TentativeExpiry");
+    let chain = transfer.chain.clone();
+    let txid = transfer.tx.txid();
+    let update = |transfer: &mut Transfer| {
+        let block_resolver = transfer.rgb.wallet.block_resolver();
+        transfer
+            .rgb
+            .contracts
+            .update_witnesses_with_blocks(chain.clone(), block_resolver, chain.height(), 1)
+            .unwrap();
+    };
+
+    transfer
+        .rgb
+        .contracts
+        .set_expiry_policy(ExpiryPolicy::after_blocks(3).only(WitnessDirection::Incoming));
+    update(&mut transfer);
+    assert_eq!(transfer.owned(), transfer.transferred(WitnessStatus::Tentative));
+
+    // The witness gets evicted from the mempool; without expiry it would stay tentative forever
+    chain.drop_tx(txid).unwrap();
+    chain.mine(3);
+    update(&mut transfer);
+    assert_eq!(transfer.owned(), transfer.transferred(WitnessStatus::Tentative));
+
+    // The witness is outgoing, and it was last seen three blocks ago
+    transfer
+        .rgb
+        .contracts
+        .set_expiry_policy(ExpiryPolicy::after_blocks(4).only(WitnessDirection::Outgoing));
+    update(&mut transfer);
+    assert_eq!(transfer.owned(), transfer.transferred(WitnessStatus::Tentative));
+    chain.mine(1);
+    update(&mut transfer);
+    assert!(transfer.owned().is_empty());
+    transfer.assert_rolled_back();
+
+    // Once re-broadcasted, the witness gets back
+    transfer
+        .rgb
+        .wallet
+        .broadcast(&transfer.tx.clone(), None)
+        .unwrap();
+    update(&mut transfer);
+    assert_eq!(transfer.owned(), transfer.transferred(WitnessStatus::Tentative));
+
+    // Time-based expiry
+    chain.drop_tx(txid).unwrap();
+    transfer
+        .rgb
+        .contracts
+        .set_expiry_policy(ExpiryPolicy::after_seconds(0));
+    update(&mut transfer);
+    transfer.assert_rolled_back();
+}
+
+#[test]
+fn default_expiry_untracked() {
+    let mut transfer = Transfer::new("DefaultExpiry");
+    let chain = transfer.chain.clone();
+    let update = |transfer: &mut Transfer| {
+        let block_resolver = transfer.rgb.wallet.block_resolver();
+        transfer
+            .rgb
+            .contracts
+            .update_witnesses_with_blocks(chain.clone(), block_resolver, chain.height(), 1)
+            .unwrap();
+    };
+
+    // The default policy doesn't track when the witnesses were last seen
+    update(&mut transfer);
+    chain.drop_tx(transfer.tx.txid()).unwrap();
+    chain.mine(3);
+    update(&mut transfer);
+    assert_eq!(transfer.owned(), transfer.transferred(WitnessStatus::Tentative));
+
+    // ... so once the expiry is enabled, the witness is counted as seen at the current height
+    transfer
+        .rgb
+        .contracts
+        .set_expiry_policy(ExpiryPolicy::after_blocks(3));
+    update(&mut transfer);
+    assert_eq!(transfer.owned(), transfer.transferred(WitnessStatus::Tentative));
+    chain.mine(3);
+    update(&mut transfer);
+    transfer.assert_rolled_back();
+}
+
+#[test]
+fn sync_checkpoint() {
+    let mut transfer = Transfer::new("SyncCheckpoint");
+    let chain = transfer.chain.clone();
+    let txid = transfer.tx.txid();
+    let requested = RefCell::new(vec![]);
+    let update = |transfer: &mut Transfer| {
+        requested.borrow_mut().clear();
+        let resolver = |txid: bp::Txid| -> Result<_, ChainError> {
+            requested.borrow_mut().push(txid);
+            Ok(chain.tx_status(txid).expect("broadcasted transaction"))
+        };
+        let block_resolver = transfer.rgb.wallet.block_resolver();
+        transfer
+            .rgb
+            .contracts
+            .update_witnesses_with_blocks(resolver, block_resolver, chain.height(), 1)
+            .unwrap();
+        requested.borrow().clone()
+    };
+    let checkpoint = |pending: &[bp::Txid]| SyncCheckpoint {
+        tip_height: chain.height(),
+        tip_hash: chain.tip().hash,
+        min_confirmations: 1,
+        pending: pending.iter().copied().collect::<BTreeSet<_>>(),
+    };
+
+    // The tentative witness stays pending
+    assert_eq!(transfer.rgb.contracts.sync_checkpoint(transfer.contract_id), None);
+    assert_eq!(update(&mut transfer), vec![txid]);
+    assert_eq!(
+        transfer.rgb.contracts.sync_checkpoint(transfer.contract_id),
+        Some(checkpoint(&[txid]))
+    );
+
+    // The witness is mined deeper than the threshold, so nothing is pending anymore
+    let height = NonZeroU64::new(chain.mine(1)).unwrap();
+    chain.mine(2);
+    assert_eq!(update(&mut transfer), vec![txid]);
+    let expected = checkpoint(&[]);
+    assert_eq!(
+        transfer.rgb.contracts.sync_checkpoint(transfer.contract_id),
+        Some(expected.clone())
+    );
+    assert_eq!(transfer.owned(), transfer.transferred(WitnessStatus::Mined(height)));
+
+    // The contract without pending witnesses is skipped, keeping the checkpoint as is
+    chain.mine(1);
+    assert!(update(&mut transfer).is_empty());
+    assert_eq!(transfer.rgb.contracts.sync_checkpoint(transfer.contract_id), Some(expected));
+
+    // Truncated checkpoint files are ignored
+    let file = fs::read_dir("tests/data/SyncCheckpoint.stockpile")
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .find(|path| path.extension().is_some_and(|ext| ext == "contract"))
+        .unwrap()
+        .join("sync.dat");
+    let data = fs::read(&file).unwrap();
+    fs::write(&file, &data[..data.len() - 1]).unwrap();
+    assert_eq!(transfer.rgb.contracts.sync_checkpoint(transfer.contract_id), None);
+    fs::write(&file, data).unwrap();
+
+    // A re-org of the checkpoint tip block forces the full scan, detecting the re-orged witness
+    chain.reorg(4).unwrap();
+    chain.mine(4);
+    assert_eq!(update(&mut transfer), vec![txid]);
+    assert_eq!(transfer.rgb.contracts.sync_checkpoint(transfer.contract_id), Some(checkpoint(&[])));
+    assert_eq!(transfer.owned(), transfer.transferred(WitnessStatus::Mined(height)));
+
+    // A checkpoint which can't be removed prevents changes to the contract
+    chain.mine(1);
+    update(&mut transfer);
+    fs::remove_file(&file).unwrap();
+    fs::create_dir(&file).unwrap();
+    assert!(matches!(transfer.rgb.contracts.archive_witnesses(&[txid]), Err(MultiError::C(_))));
+    assert_eq!(transfer.owned(), transfer.transferred(WitnessStatus::Mined(height)));
+    fs::remove_dir(&file).unwrap();
+
+    // Any change to the contract invalidates the checkpoint
+    update(&mut transfer);
+    assert!(transfer
+        .rgb
+        .contracts
+        .sync_checkpoint(transfer.contract_id)
+        .is_some());
+    transfer.rgb.contracts.archive_witnesses(&[txid]).unwrap();
+    assert_eq!(transfer.rgb.contracts.sync_checkpoint(transfer.contract_id), None);
+    assert_eq!(update(&mut transfer), vec![txid]);
+    assert_eq!(transfer.owned(), transfer.transferred(WitnessStatus::Mined(height)));
+}
+
+#[test]
+fn offchain_lifecycle() {
+    let mut transfer = Transfer::new_offchain("OffchainLifecycle");
+    let chain = transfer.chain.clone();
+    let first = transfer.tx.clone();
+
+    // Offchain witnesses are unknown to the chain, but this doesn't archive them
+    assert!(transfer.rgb.update(1).unwrap().is_empty());
+    assert_eq!(transfer.assigned(), transfer.transferred(WitnessStatus::Offchain));
+
+    // The next channel state re-anchors the same operations into a new commitment transaction
+    let bundle = transfer.bundle.clone();
+    let outputs = first.outputs().take(2).cloned().collect::<Vec<_>>();
+    let (second, mpc, prevouts) = transfer.rgb.wallet.witness_tx(&bundle, outputs).unwrap();
+    transfer
+        .rgb
+        .include_offchain(&bundle, &second, mpc, None, &prevouts)
+        .unwrap();
+    let archived = transfer
+        .rgb
+        .contracts
+        .archive_offchain(|witness| witness.id != second.txid())
+        .unwrap();
+    assert_eq!(archived, vec![first.txid()]);
+    transfer.tx = second.clone();
+    assert_eq!(transfer.assigned(), transfer.transferred(WitnessStatus::Offchain));
+
+    // Only offchain witnesses get promoted, so the archived commitment is left intact
+    transfer
+        .rgb
+        .contracts
+        .promote_witnesses(&[(first.txid(), WitnessStatus::Tentative.into())])
+        .unwrap();
+    assert!(transfer.rgb.update(1).unwrap().is_empty());
+    assert_eq!(transfer.assigned(), transfer.transferred(WitnessStatus::Offchain));
+
+    // Offchain witnesses can't be promoted to other statuses
+    for status in [WitnessStatus::Archived, WitnessStatus::Offchain] {
+        let err = transfer
+            .rgb
+            .contracts
+            .promote_witnesses(&[(second.txid(), status.into())])
+            .unwrap_err();
+        assert!(matches!(err, MultiError::A(PromoteError::InvalidStatus(s)) if s == status));
+    }
+    assert_eq!(transfer.assigned(), transfer.transferred(WitnessStatus::Offchain));
+
+    // The channel gets closed with the latest commitment
+    transfer.rgb.wallet.broadcast(&second, None).unwrap();
+    transfer
+        .rgb
+        .contracts
+        .promote_witnesses(&[(second.txid(), WitnessStatus::Tentative.into())])
+        .unwrap();
+    assert_eq!(transfer.owned(), transfer.transferred(WitnessStatus::Tentative));
+    let height = NonZeroU64::new(chain.mine(1)).unwrap();
+    assert!(transfer.rgb.update(1).unwrap().is_empty());
+    assert_eq!(transfer.owned(), transfer.transferred(WitnessStatus::Mined(height)));
+}
+
+#[test]
+fn offchain_force_close() {
+    let mut transfer = Transfer::new_offchain("OffchainForceClose");
+    let chain = transfer.chain.clone();
+
+    // The commitment gets published by the counterparty and is detected by the wallet update
+    chain.broadcast(&transfer.tx).unwrap();
+    let height = NonZeroU64::new(chain.mine(1)).unwrap();
+    assert!(transfer.rgb.update(1).unwrap().is_empty());
+    assert_eq!(transfer.owned(), transfer.transferred(WitnessStatus::Mined(height)));
+}
@@ -26,7 +26,10 @@ use alloc::collections::BTreeMap;
 use core::borrow::Borrow;
 use core::error::Error;
 use core::marker::PhantomData;
+use std::collections::{HashMap, HashSet};
 use std::io;
+use std::sync::mpsc::Receiver;
+use std::sync::OnceLock;
 
 use amplify::confinement::SmallOrdMap;
 use amplify::{IoError, MultiError};
@@ -35,8 +38,8 @@ use commit_verify::{ReservedBytes, StrictHash};
 use hypersonic::{
     AcceptError, Articles, AuthToken, CallParams, CellAddr, Codex, Consensus, ContractId,
     CoreParams, DataCell, EffectiveState, IssueError, IssueParams, Ledger, LibRepo, Memory,
-    MethodName, NamedState, Operation, Opid, SemanticError, Semantics, SigBlob, StateAtom,
-    StateName, Stock, Transition,
+    MethodName, NamedState, Operation, Opid, ProcessedState, RawState, SemanticError, Semantics,
+    SigBlob, StateAtom, StateName, Stock, Transition,
 };
 use indexmap::{IndexMap, IndexSet};
 use rgb::{
@@ -50,9 +53,14 @@ use strict_encoding::{
 };
 use strict_types::StrictVal;
 
+use crate::events::Observers;
+use crate::graph::{self, OpGraph};
+use crate::status::OpStatuses;
 use crate::{
-    parse_consignment, Consignment, ContractMeta, Identity, Issue, Issuer, IssuerError, IssuerSpec,
-    OpRels, Pile, VerifiedOperation, Witness, WitnessStatus,
+    parse_consignment, BlockInfo, Confirmation, ConfirmationDepth, Consignment, ContractEvent,
+    ContractMeta, ExpiryPolicy, Identity, Issue, Issuer, IssuerError, IssuerSpec, OpRels, Pile,
+    SealIndex, VerifiedOperation, Witness, WitnessDirection, WitnessInfo, WitnessSeen,
+    WitnessStatus,
 };
 
 #[derive(Copy, Clone, PartialEq, Eq, Debug, From)]
@@ -147,6 +155,18 @@ pub struct ImmutableState {
     pub status: WitnessStatus,
 }
 
+impl<Seal> OwnedState<Seal> {
+    pub fn confirmation(&self, depth: ConfirmationDepth) -> Confirmation {
+        depth.classify(self.status)
+    }
+}
+
+impl ImmutableState {
+    pub fn confirmation(&self, depth: ConfirmationDepth) -> Confirmation {
+        depth.classify(self.status)
+    }
+}
+
 #[derive(Clone, Eq, PartialEq, Debug, Default)]
 #[cfg_attr(
     feature = "serde",
@@ -188,6 +208,31 @@ impl<Seal> ContractState<Seal> {
         }
     }
 
+    /// Leaves only the state elements which confirmation class at a given depth is accepted by
+    /// the `accept` filter.
+    ///
+    /// # Nota bene
+    ///
+    /// The aggregated state is not filtered and is kept as is.
+    pub fn filter_confirmed(
+        mut self,
+        depth: ConfirmationDepth,
+        accept: impl Fn(Confirmation) -> bool,
+    ) -> Self {
+        for state in self.immutable.values_mut() {
+            state.retain(|state| accept(state.confirmation(depth)));
+        }
+        for state in self.owned.values_mut() {
+            state.retain(|state| accept(state.confirmation(depth)));
+        }
+        self
+    }
+
+    /// Leaves only the state elements which are final at a given depth.
+    pub fn finalized(self, depth: ConfirmationDepth) -> Self {
+        self.filter_confirmed(depth, Confirmation::is_final)
+    }
+
     pub fn filter_map<To>(self, f: impl Fn(Seal) -> Option<To>) -> ContractState<To> {
         ContractState {
             immutable: self.immutable,
@@ -216,6 +261,155 @@ impl<Seal> ContractState<Seal> {
     }
 }
 
+impl<Seal: Clone + Eq> ContractState<Seal> {
+    /// Compares this (old) contract state with a `new` one, returning the state elements which
+    /// were created and destroyed, and the aggregated state which has changed.
+    ///
+    /// Owned state elements are matched by their cell address and seal, and global state
+    /// elements by their cell address; changes in the statuses of the state elements are not
+    /// reported.
+    pub fn diff(&self, new: &Self) -> StateDiff<Seal> {
+        let same_seal =
+            |a: &OwnedState<Seal>, b: &OwnedState<Seal>| a.assignment.seal == b.assignment.seal;
+        let mut aggregated = BTreeMap::new();
+        for name in self.aggregated.keys().chain(new.aggregated.keys()) {
+            let old = self.aggregated.get(name);
+            let new = new.aggregated.get(name);
+            if old != new {
+                aggregated.insert(name.clone(), AggregatedChange {
+                    old: old.cloned(),
+                    new: new.cloned(),
+                });
+            }
+        }
+        StateDiff {
+            created: state_missing(&new.owned, &self.owned, |s| s.addr, same_seal),
+            destroyed: state_missing(&self.owned, &new.owned, |s| s.addr, same_seal),
+            global_added: state_missing(&new.immutable, &self.immutable, |s| s.addr, |_, _| true),
+            global_removed: state_missing(&self.immutable, &new.immutable, |s| s.addr, |_, _| true),
+            aggregated,
+        }
+    }
+}
+
+/// Returns state elements from `state` which are not present in `other`.
+fn state_missing<T: Clone>(
+    state: &BTreeMap<StateName, Vec<T>>,
+    other: &BTreeMap<StateName, Vec<T>>,
+    addr: impl Fn(&T) -> CellAddr,
+    same: impl Fn(&T, &T) -> bool,
+) -> BTreeMap<StateName, Vec<T>> {
+    let mut missing = BTreeMap::new();
+    for (name, state) in state {
+        let mut index = BTreeMap::<CellAddr, Vec<&T>>::new();
+        for item in other.get(name).into_iter().flatten() {
+            index.entry(addr(item)).or_default().push(item);
+        }
+        let items = state
+            .iter()
+            .filter(|item| {
+                !index
+                    .get(&addr(item))
+                    .is_some_and(|others| others.iter().any(|other| same(item, other)))
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        if !items.is_empty() {
+            missing.insert(name.clone(), items);
+        }
+    }
+    missing
+}
+
+/// Change in the value of an aggregated state (see [`StateDiff`]).
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct AggregatedChange {
+    /// The value before the change, if the state was defined.
+    pub old: Option<StrictVal>,
+    /// The value after the change, if the state is still defined.
+    pub new: Option<StrictVal>,
+}
+
+/// Difference between two contract states, produced by [`ContractState::diff`].
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(
+        rename_all = "camelCase",
+        bound = "Seal: serde::Serialize + for<'d> serde::Deserialize<'d>"
+    )
+)]
+pub struct StateDiff<Seal> {
+    /// Owned state which is present only in the new state.
+    pub created: BTreeMap<StateName, Vec<OwnedState<Seal>>>,
+    /// Owned state which is present only in the old state, i.e. it was spent or rolled back.
+    pub destroyed: BTreeMap<StateName, Vec<OwnedState<Seal>>>,
+    /// Global state which is present only in the new state.
+    pub global_added: BTreeMap<StateName, Vec<ImmutableState>>,
+    /// Global state which is present only in the old state; this may happen after a rollback.
+    pub global_removed: BTreeMap<StateName, Vec<ImmutableState>>,
+    /// Aggregated state which value has changed.
+    pub aggregated: BTreeMap<StateName, AggregatedChange>,
+}
+
+impl<Seal> StateDiff<Seal> {
+    /// Detects whether the compared states are the same.
+    pub fn is_empty(&self) -> bool {
+        self.created.is_empty()
+            && self.destroyed.is_empty()
+            && self.global_added.is_empty()
+            && self.global_removed.is_empty()
+            && self.aggregated.is_empty()
+    }
+}
+
+/// A step in the provenance of a contract state, describing a single operation in the history of
+/// the state (see [`Contract::provenance`]).
+#[derive(Clone, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize),
+    serde(
+        rename_all = "camelCase",
+        bound = "Seal::WitnessId: serde::Serialize, Seal::Definition: serde::Serialize"
+    )
+)]
+pub struct ProvenanceStep<Seal: RgbSeal> {
+    pub opid: Opid,
+    /// Name of the contract method which has created the operation, if known to the contract API.
+    pub method: Option<MethodName>,
+    pub operation: Operation,
+    /// The state consumed by the operation.
+    pub consumed: Vec<CellAddr>,
+    /// Seal definitions for the operation outputs known to the contract.
+    pub seals: SmallOrdMap<u16, Seal::Definition>,
+    /// All known witnesses of the operation with their statuses; empty for genesis.
+    pub witnesses: Vec<(Seal::WitnessId, WitnessStatus)>,
+}
+
+/// A value of the global state in the contract history (see [`Contract::global_history`]).
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize),
+    serde(rename_all = "camelCase", bound = "Seal::WitnessId: serde::Serialize")
+)]
+pub struct GlobalStateEntry<Seal: RgbSeal> {
+    pub addr: CellAddr,
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub data: StateAtom,
+    /// Status of the best witness of the operation defining the value.
+    pub status: WitnessStatus,
+    /// The best witness of the operation defining the value; `None` for genesis.
+    pub witness_id: Option<Seal::WitnessId>,
+    /// Timestamp of the block mining the witness, or the contract issue timestamp for genesis.
+    ///
+    /// `None` if the witness is not mined, or the resolver hasn't provided the block details.
+    pub timestamp: Option<DateTime<Utc>>,
+}
+
 /// Parameters used by RGB for contract creation operations.
 ///
 /// Differs from [`IssueParams`] in the fact that it uses full seal data instead of
@@ -282,8 +476,12 @@ impl<Seal: Clone> CreateParams<Seal> {
 pub struct Contract<S: Stock, P: Pile> {
     /// Cached contract id
     contract_id: ContractId,
-    ledger: Ledger<S>,
+    pub(crate) ledger: Ledger<S>,
     pile: P,
+    observers: Observers<P::Seal>,
+    /// Effective operation statuses, which are computed on the first access to the contract state
+    /// and then updated incrementally.
+    statuses: OnceLock<OpStatuses>,
 }
 
 impl<S: Stock, P: Pile> Contract<S, P> {
@@ -307,7 +505,13 @@ impl<S: Stock, P: Pile> Contract<S, P> {
         let conf: S::Conf = ledger.config();
         let mut pile = P::new(conf.into()).map_err(MultiError::C)?;
         pile.add_seals(genesis_opid, none!());
-        let mut contract = Self { ledger, pile, contract_id };
+        let mut contract = Self {
+            ledger,
+            pile,
+            contract_id,
+            observers: none!(),
+            statuses: none!(),
+        };
         contract
             .evaluate_commit(consignment.into_operations())
             .map_err(MultiError::from_a)?;
@@ -372,7 +576,13 @@ impl<S: Stock, P: Pile> Contract<S, P> {
         let mut pile = P::new(conf.into()).map_err(MultiError::C)?;
         pile.add_seals(ledger.articles().genesis_opid(), seals);
 
-        Ok(Self { ledger, pile, contract_id })
+        Ok(Self {
+            ledger,
+            pile,
+            contract_id,
+            observers: none!(),
+            statuses: none!(),
+        })
     }
 
     pub fn load(
@@ -382,7 +592,13 @@ impl<S: Stock, P: Pile> Contract<S, P> {
         let ledger = Ledger::load(stock_conf).map_err(MultiError::A)?;
         let contract_id = ledger.contract_id();
         let pile = P::load(pile_conf).map_err(MultiError::B)?;
-        Ok(Self { ledger, pile, contract_id })
+        Ok(Self {
+            ledger,
+            pile,
+            contract_id,
+            observers: none!(),
+            statuses: none!(),
+        })
     }
 
     #[inline]
@@ -390,8 +606,22 @@ impl<S: Stock, P: Pile> Contract<S, P> {
         self.pile.witness_status(wid)
     }
 
+    /// Get the tracking details of the witness, used to expire stale tentative witnesses (see
+    /// [`ExpiryPolicy`]).
+    #[inline]
+    pub fn witness_seen(&self, wid: <P::Seal as RgbSeal>::WitnessId) -> Option<WitnessSeen> {
+        self.pile.witness_seen(wid)
+    }
+
+    /// Get the details of the block mining the witness, if the witness is mined and the details
+    /// were provided by the resolver.
+    #[inline]
+    pub fn witness_block(&self, wid: <P::Seal as RgbSeal>::WitnessId) -> Option<BlockInfo> {
+        self.pile.witness_block(wid)
+    }
+
     /// Get the best mining status for a given operation ("best" means "the most deeply mined").
-    fn best_op_status(&self, opid: Opid) -> WitnessStatus {
+    pub(crate) fn best_op_status(&self, opid: Opid) -> WitnessStatus {
         self.pile
             .op_witness_ids(opid)
             .map(|wid| self.witness_status(wid))
@@ -400,6 +630,67 @@ impl<S: Stock, P: Pile> Contract<S, P> {
             .unwrap_or(WitnessStatus::Genesis)
     }
 
+    /// Get the best witness for a given operation together with its status ("best" means "the
+    /// most deeply mined"); `None` for genesis and operations without witnesses.
+    pub(crate) fn best_op_witness(
+        &self,
+        opid: Opid,
+    ) -> Option<(<P::Seal as RgbSeal>::WitnessId, WitnessStatus)> {
+        self.pile
+            .op_witness_ids(opid)
+            .map(|wid| (wid, self.witness_status(wid)))
+            .reduce(|best, other| if best.1.is_better(other.1) { best } else { other })
+    }
+
+    /// Get the effective operation statuses, computing them on the first call.
+    pub(crate) fn statuses(&self) -> &OpStatuses {
+        self.statuses.get_or_init(|| {
+            let genesis_opid = self.articles().genesis_opid();
+            let mut statuses = OpStatuses::default();
+            statuses.add(genesis_opid, []);
+            let mut changes = vec![(genesis_opid, WitnessStatus::Genesis)];
+            for (opid, op) in self.ledger.operations() {
+                statuses.add(opid, op_parents(&op));
+                changes.push((opid, self.best_op_status(opid)));
+            }
+            statuses.update(changes);
+            statuses
+        })
+    }
+
+    /// Ids of the operations in the ledger order, starting from the `pos`-th one, which are taken
+    /// from the in-memory index without reading the ledger; genesis is not included.
+    pub(crate) fn op_ids_from(&self, pos: u64) -> &[Opid] {
+        // Genesis is registered in the statuses before all other operations
+        let pos = usize::try_from(pos).unwrap_or(usize::MAX).saturating_add(1);
+        self.statuses().ops_from(pos)
+    }
+
+    /// Registers a new operation in the effective operation statuses.
+    fn add_op_status(&mut self, opid: Opid, parents: impl IntoIterator<Item = Opid>) {
+        let status = self.best_op_status(opid);
+        // If the statuses are not computed yet, they will include the operation once computed
+        if let Some(statuses) = self.statuses.get_mut() {
+            statuses.add(opid, parents);
+            statuses.update([(opid, status)]);
+        }
+    }
+
+    /// Updates the effective statuses of the operations, which witnesses were changed, and of all
+    /// their descendants.
+    fn update_op_statuses(&mut self, opids: impl IntoIterator<Item = Opid>) {
+        if self.statuses.get().is_none() {
+            return;
+        }
+        let changes = opids
+            .into_iter()
+            .map(|opid| (opid, self.best_op_status(opid)))
+            .collect::<Vec<_>>();
+        if let Some(statuses) = self.statuses.get_mut() {
+            statuses.update(changes);
+        }
+    }
+
     fn retrieve(&self, opid: Opid) -> Option<SealWitness<P::Seal>> {
         let (status, wid) = self
             .pile
@@ -416,8 +707,45 @@ impl<S: Stock, P: Pile> Contract<S, P> {
 
     pub fn contract_id(&self) -> ContractId { self.contract_id }
 
+    /// Subscribes to the events of this contract.
+    ///
+    /// Events are sent to the returned receiver for as long as this contract instance exists.
+    pub fn subscribe(&mut self) -> Receiver<ContractEvent<P::Seal>> { self.observers.subscribe() }
+
+    pub(crate) fn observers_mut(&mut self) -> &mut Observers<P::Seal> { &mut self.observers }
+
     pub fn articles(&self) -> &Articles { self.ledger.articles() }
 
+    /// Resolves the name of the method called by an operation using the default contract API.
+    ///
+    /// Since multiple API methods may call the same verifier, the first matching method name is
+    /// returned.
+    pub fn op_method(&self, op: &Operation) -> Option<MethodName> {
+        graph::op_method(self.articles(), op)
+    }
+
+    /// Get the graph of all operations known to the contract, including genesis and the
+    /// operations which were rolled back.
+    pub fn graph(&self) -> OpGraph {
+        let articles = self.articles();
+        let genesis_opid = articles.genesis_opid();
+        let genesis = articles.genesis().to_operation(self.contract_id);
+        let mut graph = OpGraph::default();
+        graph.add_operation(
+            articles,
+            genesis_opid,
+            &genesis,
+            Some(WitnessStatus::Genesis),
+            Some(true),
+        );
+        for (opid, op) in self.ledger.operations() {
+            let status = self.best_op_status(opid);
+            let valid = self.ledger.is_valid(opid);
+            graph.add_operation(articles, opid, &op, Some(status), Some(valid));
+        }
+        graph
+    }
+
     /// # Nota bene
     ///
     /// Does not include genesis
@@ -444,6 +772,20 @@ impl<S: Stock, P: Pile> Contract<S, P> {
         self.pile.witnesses()
     }
 
+    /// Get a witness by its id, if it is known to the contract.
+    pub fn witness(&self, wid: <P::Seal as RgbSeal>::WitnessId) -> Option<Witness<P::Seal>> {
+        if !self.pile.has_witness(wid) {
+            return None;
+        }
+        Some(Witness {
+            id: wid,
+            published: self.pile.pub_witness(wid),
+            client: self.pile.cli_witness(wid),
+            status: self.pile.witness_status(wid),
+            opids: self.pile.ops_by_witness_id(wid).collect(),
+        })
+    }
+
     pub fn ops_by_witness_id(
         &self,
         wid: <P::Seal as RgbSeal>::WitnessId,
@@ -455,6 +797,115 @@ impl<S: Stock, P: Pile> Contract<S, P> {
         self.pile.op_relations(opid, up_to)
     }
 
+    /// Get the provenance ("chain of custody") of a contract state defined by the cell `addr`.
+    ///
+    /// Walks the contract history from the operation defining the state back to genesis, returning
+    /// a step for the operation itself and each of its ancestors; genesis always comes last. The
+    /// ancestors include both the operations which state was consumed and which global state was
+    /// read.
+    ///
+    /// Returns `None` if the operation defining the cell is not known to the contract, or it
+    /// doesn't have an output with the cell number.
+    pub fn provenance(&self, addr: CellAddr) -> Option<Vec<ProvenanceStep<P::Seal>>> {
+        let articles = self.ledger.articles();
+        let genesis_opid = articles.genesis_opid();
+        let operation = |opid: Opid| {
+            if opid == genesis_opid {
+                articles.genesis().to_operation(self.contract_id)
+            } else {
+                self.ledger.operation(opid)
+            }
+        };
+        if addr.opid != genesis_opid && !self.ledger.has_operation(addr.opid) {
+            return None;
+        }
+        if operation(addr.opid).destructible_out.len_u16() <= addr.pos {
+            return None;
+        }
+
+        let steps = self
+            .ledger
+            .ancestors([addr.opid])
+            .filter(|opid| *opid != genesis_opid)
+            .chain([genesis_opid])
+            .map(|opid| {
+                let op = operation(opid);
+                ProvenanceStep {
+                    opid,
+                    method: self.op_method(&op),
+                    consumed: op.destructible_in.iter().map(|input| input.addr).collect(),
+                    seals: self.pile.seals(opid, op.destructible_out.len_u16()),
+                    witnesses: self
+                        .pile
+                        .op_witness_ids(opid)
+                        .map(|wid| (wid, self.pile.witness_status(wid)))
+                        .collect(),
+                    operation: op,
+                }
+            })
+            .collect();
+        Some(steps)
+    }
+
+    /// Get the history of the global state `name`.
+    ///
+    /// The values are ordered by the block height of the witnesses of the operations defining
+    /// them: genesis values come first, followed by the values mined in each of the blocks, and
+    /// then by the values which are not mined yet. Values with the same height are ordered by
+    /// their position in the contract history.
+    pub fn global_history(&self, name: impl Into<StateName>) -> Vec<GlobalStateEntry<P::Seal>> {
+        let name = name.into();
+        let articles = self.articles();
+        let genesis_opid = articles.genesis_opid();
+        let Some(state) = self.ledger.state().main.global.get(&name) else {
+            return vec![];
+        };
+        let positions = self
+            .ledger
+            .operations()
+            .enumerate()
+            .map(|(pos, (opid, _))| (opid, pos + 1))
+            .collect::<HashMap<_, _>>();
+
+        let mut history = state
+            .iter()
+            .map(|(addr, data)| {
+                let witness = self.best_op_witness(addr.opid);
+                let (status, timestamp) = match witness {
+                    None if addr.opid == genesis_opid => (
+                        WitnessStatus::Genesis,
+                        DateTime::from_timestamp(articles.contract_meta().timestamp, 0),
+                    ),
+                    None => (WitnessStatus::Genesis, None),
+                    Some((wid, status)) => (
+                        status,
+                        self.witness_block(wid)
+                            .filter(|_| status.is_mined())
+                            .and_then(|block| block.time()),
+                    ),
+                };
+                GlobalStateEntry {
+                    addr: *addr,
+                    data: data.clone(),
+                    status,
+                    witness_id: witness.map(|(wid, _)| wid),
+                    timestamp,
+                }
+            })
+            .collect::<Vec<_>>();
+        history.sort_by_key(|entry| {
+            let height = match entry.status {
+                WitnessStatus::Genesis => 0,
+                WitnessStatus::Mined(height) => height.get(),
+                _ => u64::MAX,
+            };
+            // Genesis is not a part of the operations and always comes first
+            let pos = positions.get(&entry.addr.opid).copied().unwrap_or(0);
+            (height, pos, entry.addr.pos)
+        });
+        history
+    }
+
     pub fn seal(&self, seal: &<P::Seal as RgbSeal>::Definition) -> Option<CellAddr> {
         let auth = seal.auth_token();
         self.ledger.state().raw.auth.get(&auth).copied()
@@ -464,60 +915,255 @@ impl<S: Stock, P: Pile> Contract<S, P> {
     ///
     /// The call does not recompute the contract state, but does a seal resolution,
     /// taking into account the status of the witnesses in the whole history.
+    ///
+    /// The statuses of the operations are computed on the first call and then updated
+    /// incrementally on each sync, method call or included witness.
     pub fn state(&self) -> ContractState<P::Seal> {
-        let mut cache = bmap! {};
-        let mut ancestor_cache = bmap! {};
-        let mut cached_status = |opid: Opid| {
-            *cache
-                .entry(opid)
-                .or_insert_with(|| self.best_op_status(opid))
-        };
-        let mut get_status = |opid: Opid, or: WitnessStatus| {
-            (*ancestor_cache.entry(opid).or_insert_with(|| {
-                self.ledger
-                    .ancestors([opid])
-                    .map(|ancestor| self.best_op_status(ancestor))
-                    .fold(WitnessStatus::Genesis, |worst, other| worst.worst(other))
-            }))
-            .worst(or)
-        };
-        let state = self.ledger.state().main.clone();
+        self.resolve_state(
+            self.ledger.state().main.clone(),
+            |wid| self.witness_status(wid),
+            self.statuses(),
+        )
+    }
+
+    /// Get the contract state with the owned state limited to the seals with the given primary
+    /// components (for bitcoin, transaction outpoints).
+    ///
+    /// Equals to filtering the result of [`Self::state`], but resolves only the owned state found
+    /// with the seal index of the pile (see [`Self::owned_by_primary`]).
+    pub fn state_by_primary(
+        &self,
+        primaries: impl IntoIterator<Item = <P::Seal as SealIndex>::Primary>,
+    ) -> ContractState<P::Seal>
+    where
+        P::Seal: SealIndex,
+    {
+        let state = &self.ledger.state().main;
+        let mut owned = state
+            .owned
+            .keys()
+            .map(|name| (name.clone(), vec![]))
+            .collect::<BTreeMap<_, _>>();
+        for primary in primaries.into_iter().collect::<HashSet<_>>() {
+            for (name, state) in self.owned_by_primary(primary) {
+                owned.entry(name).or_default().extend(state);
+            }
+        }
+        // Keeping the order of the full state
+        for state in owned.values_mut() {
+            state.sort_by_key(|owned| owned.addr);
+        }
+        ContractState {
+            immutable: self.resolve_immutable(state.global.clone(), self.statuses()),
+            owned,
+            aggregated: state.aggregated.clone(),
+        }
+    }
+
+    /// Get the contract state as it was at a given block `height`.
+    ///
+    /// The state is computed by re-evaluating the contract history as if only genesis and the
+    /// operations having witnesses mined at or below the `height` were valid (see
+    /// [`WitnessStatus::at_height`]). Operations without any witness are not known to the
+    /// blockchain and are not included.
+    ///
+    /// The call does not modify the ledger and its stored state.
+    pub fn state_at(&self, height: u64) -> ContractState<P::Seal> {
+        let witness_status = |wid| self.witness_status(wid).at_height(height);
+        self.replay_state(witness_status, |opid| {
+            self.pile
+                .op_witness_ids(opid)
+                .map(witness_status)
+                .any(|status| status.is_valid())
+        })
+    }
+
+    /// Get the contract state as if the operations `opids` (and, thus, all their descendants) were
+    /// never applied.
+    ///
+    /// Together with [`Self::state`] this gives the state before and after the operations (see
+    /// [`Self::state_diff_ops`]).
+    ///
+    /// The call does not modify the ledger and its stored state.
+    pub fn state_without(&self, opids: impl IntoIterator<Item = Opid>) -> ContractState<P::Seal> {
+        let opids = opids.into_iter().collect::<HashSet<_>>();
+        self.replay_state(
+            |wid| self.witness_status(wid),
+            |opid| !opids.contains(&opid) && self.ledger.is_valid(opid),
+        )
+    }
+
+    /// Compare the contract state at block height `from` with the state at block height `to`.
+    ///
+    /// See [`Self::state_at`] and [`ContractState::diff`] for the details.
+    pub fn state_diff_at(&self, from: u64, to: u64) -> StateDiff<P::Seal> {
+        self.state_at(from).diff(&self.state_at(to))
+    }
+
+    /// Compare the contract state before and after the operations `opids` were applied.
+    ///
+    /// See [`Self::state_without`] and [`ContractState::diff`] for the details.
+    pub fn state_diff_ops(&self, opids: impl IntoIterator<Item = Opid>) -> StateDiff<P::Seal> {
+        self.state_without(opids).diff(&self.state())
+    }
+
+    /// Re-evaluates the contract history, replaying genesis and the operations accepted by the
+    /// `is_included` filter, and resolves the state using the provided witness statuses.
+    ///
+    /// Operations which parents were not replayed are skipped.
+    fn replay_state(
+        &self,
+        witness_status: impl Fn(<P::Seal as RgbSeal>::WitnessId) -> WitnessStatus,
+        is_included: impl Fn(Opid) -> bool,
+    ) -> ContractState<P::Seal> {
+        let articles = self.ledger.articles();
+        let genesis_opid = articles.genesis_opid();
+        let mut raw = RawState::default();
+        replay(&mut raw, genesis_opid, articles.genesis().to_operation(self.contract_id));
+
+        let mut valid = HashSet::new();
+        valid.insert(genesis_opid);
+        let mut statuses = OpStatuses::default();
+        statuses.add(genesis_opid, []);
+        let mut changes = vec![(genesis_opid, WitnessStatus::Genesis)];
+        for (opid, op) in self.ledger.operations() {
+            let op_status = self
+                .pile
+                .op_witness_ids(opid)
+                .map(&witness_status)
+                .reduce(|best, other| best.best(other))
+                .unwrap_or(WitnessStatus::Genesis);
+            statuses.add(opid, op_parents(&op));
+            changes.push((opid, op_status));
+            // Operations in the ledger stash are ordered topologically, so all parents of an
+            // operation are already processed at this point.
+            let is_valid = is_included(opid)
+                && op
+                    .immutable_in
+                    .iter()
+                    .all(|addr| valid.contains(&addr.opid))
+                && op
+                    .destructible_in
+                    .iter()
+                    .all(|input| raw.owned.contains_key(&input.addr));
+            if is_valid {
+                valid.insert(opid);
+                replay(&mut raw, opid, op);
+            }
+        }
+
+        statuses.update(changes);
+
+        let state = EffectiveState::with_raw_state(raw, articles);
+        self.resolve_state(state.main, witness_status, &statuses)
+    }
+
+    /// Resolves seals for the processed contract state, attributing each of the state elements
+    /// with its status computed from the provided witness and effective operation statuses.
+    fn resolve_state(
+        &self,
+        state: ProcessedState,
+        witness_status: impl Fn(<P::Seal as RgbSeal>::WitnessId) -> WitnessStatus,
+        statuses: &OpStatuses,
+    ) -> ContractState<P::Seal> {
         let mut owned = bmap! {};
         for (name, map) in state.owned {
             let mut state = vec![];
             for (addr, data) in map {
-                let Some(seal) = self.pile.seal(addr) else {
-                    continue;
-                };
-                if let Some(seal) = seal.to_src() {
-                    state.push(OwnedState {
-                        addr,
-                        assignment: Assignment { seal, data },
-                        status: get_status(addr.opid, cached_status(addr.opid)),
-                    });
-                } else {
-                    // We insert a copy of state for each of the witnesses created for the operation
-                    for wid in self.pile.op_witness_ids(addr.opid) {
-                        state.push(OwnedState {
-                            addr,
-                            assignment: Assignment { seal: seal.resolve(wid), data: data.clone() },
-                            status: get_status(addr.opid, self.pile.witness_status(wid)),
-                        });
-                    }
-                }
+                self.resolve_owned(addr, data, &witness_status, statuses, &mut state);
             }
             owned.insert(name, state);
         }
+        let immutable = self.resolve_immutable(state.global, statuses);
+        ContractState { immutable, owned, aggregated: state.aggregated }
+    }
+
+    fn resolve_immutable(
+        &self,
+        global: BTreeMap<StateName, BTreeMap<CellAddr, StateAtom>>,
+        statuses: &OpStatuses,
+    ) -> BTreeMap<StateName, Vec<ImmutableState>> {
         let mut immutable = bmap! {};
-        for (name, map) in state.global {
+        for (name, map) in global {
             let mut state = vec![];
             for (addr, data) in map {
-                let status = get_status(addr.opid, cached_status(addr.opid));
+                let status = statuses.effective(addr.opid);
                 state.push(ImmutableState { addr, data, status });
             }
             immutable.insert(name, state);
         }
-        ContractState { immutable, owned, aggregated: state.aggregated }
+        immutable
+    }
+
+    /// Resolves the seal of the owned state at `addr`, adding the resolved state to `state`.
+    ///
+    /// If the seal depends on the witness, adds a copy of state for each of the witnesses created
+    /// for the operation.
+    fn resolve_owned(
+        &self,
+        addr: CellAddr,
+        data: StrictVal,
+        witness_status: &impl Fn(<P::Seal as RgbSeal>::WitnessId) -> WitnessStatus,
+        statuses: &OpStatuses,
+        state: &mut Vec<OwnedState<P::Seal>>,
+    ) {
+        let Some(seal) = self.pile.seal(addr) else {
+            return;
+        };
+        if let Some(seal) = seal.to_src() {
+            state.push(OwnedState {
+                addr,
+                assignment: Assignment { seal, data },
+                status: statuses.effective(addr.opid),
+            });
+        } else {
+            for wid in self.pile.op_witness_ids(addr.opid) {
+                state.push(OwnedState {
+                    addr,
+                    assignment: Assignment { seal: seal.resolve(wid), data: data.clone() },
+                    status: statuses.effective(addr.opid).worst(witness_status(wid)),
+                });
+            }
+        }
+    }
+
+    /// Get the unspent owned state assigned to the seals with the given primary component (for
+    /// bitcoin, a transaction outpoint).
+    ///
+    /// Unlike filtering the result of [`Self::state`], uses the seal index of the pile, and thus
+    /// scales with the number of cells ever assigned to the primary component, not with the size
+    /// of the contract state.
+    pub fn owned_by_primary(
+        &self,
+        primary: <P::Seal as SealIndex>::Primary,
+    ) -> BTreeMap<StateName, Vec<OwnedState<P::Seal>>>
+    where
+        P::Seal: SealIndex,
+    {
+        let owned = &self.ledger.state().main.owned;
+        let witness_status = |wid| self.witness_status(wid);
+        let statuses = self.statuses();
+        let mut res = BTreeMap::<_, Vec<_>>::new();
+        let cells: Box<dyn Iterator<Item = CellAddr>> = match self.pile.cells_by_primary(primary) {
+            Some(cells) => Box::new(cells),
+            // Piles without the seal index require scanning all the owned state
+            None => Box::new(owned.values().flat_map(|map| map.keys().copied())),
+        };
+        for addr in cells {
+            let Some((name, data)) = owned
+                .iter()
+                .find_map(|(name, map)| map.get(&addr).map(|data| (name, data)))
+            else {
+                continue;
+            };
+            let mut state = vec![];
+            self.resolve_owned(addr, data.clone(), &witness_status, statuses, &mut state);
+            state.retain(|owned| owned.assignment.seal.primary() == primary);
+            if !state.is_empty() {
+                res.entry(name.clone()).or_default().extend(state);
+            }
+        }
+        res
     }
 
     pub fn full_state(&self) -> &EffectiveState { self.ledger.state() }
@@ -529,15 +1175,26 @@ impl<S: Stock, P: Pile> Contract<S, P> {
     /// If the contract id is not known.
     pub fn sync(
         &mut self,
-        changed: impl IntoIterator<Item = (<P::Seal as RgbSeal>::WitnessId, WitnessStatus)>,
+        changed: impl IntoIterator<Item = (<P::Seal as RgbSeal>::WitnessId, impl Into<WitnessInfo>)>,
     ) -> Result<(), MultiError<AcceptError, S::Error>> {
         // Step 1: Sanitize the list of changed wids
         let mut affected_wids = IndexMap::new();
-        for (wid, status) in changed {
+        for (wid, info) in changed {
             if !self.pile.has_witness(wid) {
                 continue;
             }
+            let WitnessInfo { status, block } = info.into();
             let prev_status = self.pile.witness_status(wid);
+            let prev_block = self.pile.witness_block(wid);
+            // Resolvers may not provide block details, in which case we keep the known ones
+            let block = match status {
+                WitnessStatus::Mined(_) if status == prev_status => block.or(prev_block),
+                WitnessStatus::Mined(_) => block,
+                _ => None,
+            };
+            if block != prev_block {
+                self.pile.update_witness_block(wid, block);
+            }
             if status == prev_status {
                 continue;
             }
@@ -565,9 +1222,18 @@ impl<S: Stock, P: Pile> Contract<S, P> {
         // witnesses and operations, such that one witness change may affect other operation witness
         // status.
         for (wid, status) in affected_wids {
+            let old = self.pile.witness_status(wid);
             self.pile.update_witness_status(wid, status);
+            self.observers.notify(ContractEvent::WitnessStatusChanged {
+                contract_id: self.contract_id,
+                witness_id: wid,
+                old,
+                new: status,
+            });
         }
 
+        self.update_op_statuses(affected_ops.keys().copied());
+
         // Step 4: Filter opids and leave only those whose status has changed after the witness
         // update
         let mut roll_back = IndexSet::new();
@@ -586,17 +1252,77 @@ impl<S: Stock, P: Pile> Contract<S, P> {
         debug_assert_eq!(forward.intersection(&roll_back).count(), 0);
 
         // Step 5: Perform rollback and forward operations
-        self.ledger.rollback(roll_back).map_err(MultiError::B)?;
+        self.ledger
+            .rollback(roll_back.iter().copied())
+            .map_err(MultiError::B)?;
         // Ledger has already committed as a part of `rollback`
         self.pile.commit_transaction();
+        let contract_id = self.contract_id;
+        for opid in roll_back {
+            self.observers
+                .notify(ContractEvent::OperationRolledBack { contract_id, opid });
+        }
 
-        self.ledger.forward(forward)?;
+        self.ledger.forward(forward.iter().copied())?;
         // Ledger has already committed as a part of `forward`
         self.pile.commit_transaction();
+        for opid in forward {
+            self.observers
+                .notify(ContractEvent::OperationForwarded { contract_id, opid });
+        }
 
         Ok(())
     }
 
+    /// Archives tentative witnesses which expired under the `policy`, rolling back the operations
+    /// which are no longer valid.
+    ///
+    /// The witnesses for which `seen` returns `true` were seen in the mempool or in the blockchain
+    /// during the current sync at the blockchain `height` and time `timestamp`, and thus do not
+    /// expire. Witnesses without tracking details, which were added before the tracking was
+    /// introduced, are considered incoming.
+    ///
+    /// Under a policy which never expires witnesses, the seen witnesses are not tracked.
+    pub(crate) fn expire_witnesses(
+        &mut self,
+        seen: impl Fn(<P::Seal as RgbSeal>::WitnessId) -> bool,
+        policy: ExpiryPolicy,
+        height: u64,
+        timestamp: i64,
+    ) -> Result<(), MultiError<AcceptError, S::Error>> {
+        // Without the expiration there is no need to track when the witnesses were seen
+        if policy.never_expires() {
+            return Ok(());
+        }
+        let mut tracked = false;
+        let mut expired = vec![];
+        for wid in self.pile.witness_ids().collect::<Vec<_>>() {
+            if !self.pile.witness_status(wid).is_tentative() {
+                continue;
+            }
+            let mut record = self
+                .pile
+                .witness_seen(wid)
+                .unwrap_or(WitnessSeen::new(WitnessDirection::Incoming));
+            if record.last_seen.is_none() || seen(wid) {
+                record.last_seen = Some((height, timestamp));
+                self.pile.update_witness_seen(wid, record);
+                tracked = true;
+            }
+            if policy.is_expired(record, height, timestamp) {
+                expired.push(wid);
+            }
+        }
+        if tracked {
+            self.pile.commit_transaction();
+        }
+        self.sync(
+            expired
+                .into_iter()
+                .map(|wid| (wid, WitnessStatus::Archived)),
+        )
+    }
+
     /// Do a call to the contract method, creating and operation.
     ///
     /// The operation is automatically included in the contract history.
@@ -613,16 +1339,58 @@ impl<S: Stock, P: Pile> Contract<S, P> {
         debug_assert_eq!(operation.opid(), opid);
         self.pile.add_seals(opid, seals);
         debug_assert_eq!(operation.contract_id, self.contract_id());
+        self.add_op_status(opid, op_parents(&operation));
+        self.observers
+            .notify(ContractEvent::OperationAdded { contract_id: self.contract_id, opid });
         Ok(operation)
     }
 
     /// Include an operation and its witness to the history of known operations and the contract
     /// state.
+    ///
+    /// The witness is considered to be an outgoing one (see [`WitnessDirection`]), unless it was
+    /// already known.
     pub fn include(
         &mut self,
         opid: Opid,
         anchor: <P::Seal as RgbSeal>::Client,
         published: &<P::Seal as RgbSeal>::Published,
+    ) {
+        self.include_witness(
+            opid,
+            anchor,
+            published,
+            WitnessStatus::Tentative,
+            WitnessDirection::Outgoing,
+        )
+    }
+
+    /// Include an operation with an offchain witness, like a commitment transaction of a payment
+    /// channel, which is published only if the channel gets closed.
+    ///
+    /// If the witness was already known, its status is kept.
+    pub fn include_offchain(
+        &mut self,
+        opid: Opid,
+        anchor: <P::Seal as RgbSeal>::Client,
+        published: &<P::Seal as RgbSeal>::Published,
+    ) {
+        self.include_witness(
+            opid,
+            anchor,
+            published,
+            WitnessStatus::Offchain,
+            WitnessDirection::Outgoing,
+        )
+    }
+
+    fn include_witness(
+        &mut self,
+        opid: Opid,
+        anchor: <P::Seal as RgbSeal>::Client,
+        published: &<P::Seal as RgbSeal>::Published,
+        status: WitnessStatus,
+        direction: WitnessDirection,
     ) {
         let wid = published.pub_id();
         let anchor = if self.pile.has_witness(wid) {
@@ -637,9 +1405,19 @@ impl<S: Stock, P: Pile> Contract<S, P> {
         } else {
             anchor
         };
-        self.pile
-            .add_witness(opid, wid, published, &anchor, WitnessStatus::Tentative);
+        self.pile.add_witness(opid, wid, published, &anchor, status);
+        if self.pile.witness_seen(wid).is_none() {
+            self.pile
+                .update_witness_seen(wid, WitnessSeen::new(direction));
+        }
+        // During consignment evaluation witnesses are applied before their operations, in which
+        // case the seals get indexed once they are added
+        if self.ledger.has_operation(opid) {
+            let up_to = self.ledger.operation(opid).destructible_out.len_u16();
+            self.pile.index_witness_seals(opid, up_to, wid);
+        }
         self.pile.commit_transaction();
+        self.update_op_statuses([opid]);
     }
 
     fn aux<W: WriteRaw>(
@@ -783,9 +1561,14 @@ impl<S: Stock, P: Pile> Contract<S, P> {
         // so in the future we can have arbitrary extensions
         // put here with no backward compatibility issues.
 
-        self.ledger
+        let upgraded = self
+            .ledger
             .upgrade_apis(articles)
             .map_err(MultiError::from_other_a)?;
+        if upgraded {
+            self.observers
+                .notify(ContractEvent::ArticlesUpgraded(self.contract_id));
+        }
         Ok(())
     }
 
@@ -805,6 +1588,35 @@ impl<S: Stock, P: Pile> Contract<S, P> {
     }
 }
 
+/// Operations which state is spent or read by the operation.
+fn op_parents(op: &Operation) -> impl Iterator<Item = Opid> + use<'_> {
+    op.immutable_in
+        .iter()
+        .map(|addr| addr.opid)
+        .chain(op.destructible_in.iter().map(|input| input.addr.opid))
+}
+
+/// Applies an already verified operation to the raw state, without re-running its verification.
+fn replay(raw: &mut RawState, opid: Opid, op: Operation) {
+    for input in op.destructible_in {
+        if let Ok(Some(cell)) = raw.owned.remove(&input.addr) {
+            let _ = raw.auth.remove(&cell.auth);
+        }
+    }
+    for (no, cell) in op.destructible_out.into_iter().enumerate() {
+        let addr = CellAddr::new(opid, no as u16);
+        raw.auth
+            .insert(cell.auth, addr)
+            .expect("too many authentication tokens");
+        raw.owned.insert(addr, cell).expect("state too large");
+    }
+    for (no, data) in op.immutable_out.into_iter().enumerate() {
+        raw.global
+            .insert(CellAddr::new(opid, no as u16), data)
+            .expect("state too large");
+    }
+}
+
 pub struct OpReader<
     'r,
     Seal: RgbSeal,
@@ -866,7 +1678,12 @@ impl<S: Stock, P: Pile> ContractApi<P::Seal> for Contract<S, P> {
     fn is_known(&self, opid: Opid) -> bool { self.ledger.is_valid(opid) }
 
     fn apply_operation(&mut self, op: VerifiedOperation) {
+        let opid = op.opid();
+        let parents = op_parents(op.as_operation()).collect::<Vec<_>>();
         self.ledger.apply(op).expect("unable to apply operation");
+        self.add_op_status(opid, parents);
+        self.observers
+            .notify(ContractEvent::OperationAdded { contract_id: self.contract_id, opid });
     }
 
     fn apply_seals(
@@ -878,7 +1695,13 @@ impl<S: Stock, P: Pile> ContractApi<P::Seal> for Contract<S, P> {
     }
 
     fn apply_witness(&mut self, opid: Opid, witness: SealWitness<P::Seal>) {
-        self.include(opid, witness.client, &witness.published)
+        self.include_witness(
+            opid,
+            witness.client,
+            &witness.published,
+            WitnessStatus::Tentative,
+            WitnessDirection::Incoming,
+        )
     }
 }
 
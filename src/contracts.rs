@@ -22,14 +22,16 @@
 // or implied. See the License for the specific language governing permissions and limitations under
 // the License.
 
-use alloc::collections::BTreeMap;
+use alloc::collections::{BTreeMap, BTreeSet};
 use core::borrow::Borrow;
 use core::cell::RefCell;
 use std::collections::HashMap;
 use std::io;
+use std::sync::mpsc::Receiver;
 
 use amplify::confinement::{KeyedCollection, SmallOrdMap};
-use amplify::MultiError;
+use amplify::{Bytes32, MultiError};
+use chrono::Utc;
 use commit_verify::StrictHash;
 use hypersonic::{
     AcceptError, AuthToken, CallParams, CodexId, ContractId, ContractName, Opid, Stock,
@@ -41,10 +43,13 @@ use strict_encoding::{
 };
 use strict_types::StrictVal;
 
+use crate::events::Observers;
 use crate::{
-    parse_consignment, Articles, Consensus, Consignment, ConsumeError, Contract, ContractRef,
-    ContractState, CreateParams, Identity, ImmutableState, Issuer, Operation, OwnedState, Pile,
-    SigBlob, StateName, Stockpile, WitnessStatus,
+    parse_consignment, Articles, Consensus, Consignment, ConsumeError, Contract, ContractEvent,
+    ContractRef, ContractState, CreateParams, ExpiryPolicy, Identity, ImmutableState, Issuer,
+    OpCursor, OpPage, OpQuery, Operation, OwnedState, Pile, SealIndex, SigBlob, StateDiff,
+    StateName, Stockpile, SyncCheckpoint, TypedStateError, Witness, WitnessInfo, WitnessResolver,
+    WitnessStatus,
 };
 
 pub const CONSIGN_VERSION: u16 = 0;
@@ -130,6 +135,8 @@ pub struct Contracts<
     issuers: RefCell<S>,
     contracts: RefCell<C>,
     persistence: Sp,
+    observers: Observers<<Sp::Pile as Pile>::Seal>,
+    expiry: ExpiryPolicy,
 }
 
 impl<Sp, S, C> Contracts<Sp, S, C>
@@ -143,10 +150,16 @@ where
         S: Default,
         C: Default,
     {
-        Self { issuers: none!(), contracts: none!(), persistence }
+        Self {
+            issuers: none!(),
+            contracts: none!(),
+            persistence,
+            observers: none!(),
+            expiry: default!(),
+        }
     }
 
-    fn with_contract<R>(
+    pub(crate) fn with_contract<R>(
         &self,
         id: ContractId,
         f: impl FnOnce(&Contract<Sp::Stock, Sp::Pile>) -> R,
@@ -175,11 +188,21 @@ where
     ) -> R {
         // We need this bullshit due to a failed rust `RefCell` implementation which panics if we do
         // this block any other way.
+        // Contract-specific observers are kept, and the observers of all contracts are attached
+        // for the duration of the call only; the ones which were dropped during the call are
+        // removed from the observers of all contracts.
         if self.contracts.borrow().contains_key(&id) {
-            return f(self.contracts.borrow_mut().get_mut(&id).unwrap());
+            let mut contracts = self.contracts.borrow_mut();
+            let contract = contracts.get_mut(&id).unwrap();
+            contract.observers_mut().attach(&self.observers);
+            let res = f(contract);
+            self.observers = contract.observers_mut().detach();
+            return res;
         }
         if let Some(mut contract) = self.persistence.contract(id) {
+            contract.observers_mut().attach(&self.observers);
             let res = f(&mut contract);
+            self.observers = contract.observers_mut().detach();
             self.contracts.borrow_mut().insert(id, contract);
             res
         } else {
@@ -187,25 +210,13 @@ where
         }
     }
 
-    #[cfg(feature = "async")]
-    #[allow(clippy::await_holding_refcell_ref)]
-    async fn with_contract_mut_async<R>(
-        &mut self,
-        id: ContractId,
-        f: impl AsyncFnOnce(&mut Contract<Sp::Stock, Sp::Pile>) -> R,
-    ) -> R {
-        // We need this bullshit due to a failed rust `RefCell` implementation which panics if we do
-        // this block any other way.
-        if self.contracts.borrow().contains_key(&id) {
-            return f(self.contracts.borrow_mut().get_mut(&id).unwrap()).await;
-        }
-        if let Some(mut contract) = self.persistence.contract(id) {
-            let res = f(&mut contract).await;
-            self.contracts.borrow_mut().insert(id, contract);
-            res
-        } else {
-            panic!("Contract {id} not found")
-        }
+    /// Removes the sync checkpoint of the contract before adding or changing its witnesses or
+    /// operations, which would be unknown to the checkpoint.
+    ///
+    /// The next sync resolves all the witnesses of the contract then; if the checkpoint can't be
+    /// removed, the change must not happen, since the new witnesses would never be resolved.
+    fn reset_sync_checkpoint(&mut self, id: ContractId) -> Result<(), <Sp::Pile as Pile>::Error> {
+        self.persistence.set_sync_checkpoint(id, None)
     }
 }
 
@@ -215,6 +226,27 @@ where
     S: KeyedCollection<Key = CodexId, Value = Issuer>,
     C: KeyedCollection<Key = ContractId, Value = Contract<Sp::Stock, Sp::Pile>>,
 {
+    /// Subscribes to the events of all contracts, including contract import and purge.
+    pub fn subscribe(&mut self) -> Receiver<ContractEvent<<Sp::Pile as Pile>::Seal>> {
+        self.observers.subscribe()
+    }
+
+    /// Policy for archiving stale tentative witnesses on [`Self::update_witnesses`].
+    pub fn expiry_policy(&self) -> ExpiryPolicy { self.expiry }
+
+    /// Sets the policy for archiving stale tentative witnesses on [`Self::update_witnesses`].
+    pub fn set_expiry_policy(&mut self, policy: ExpiryPolicy) { self.expiry = policy; }
+
+    /// Checkpoint of the last witness synchronization of the contract, if it wasn't invalidated
+    /// by the changes to the contract since then.
+    #[allow(clippy::type_complexity)]
+    pub fn sync_checkpoint(
+        &self,
+        contract_id: ContractId,
+    ) -> Option<SyncCheckpoint<<<Sp::Pile as Pile>::Seal as RgbSeal>::WitnessId>> {
+        self.persistence.sync_checkpoint(contract_id)
+    }
+
     pub fn codex_ids(&self) -> impl Iterator<Item = CodexId> + use<'_, Sp, S, C> {
         self.persistence.codex_ids()
     }
@@ -263,10 +295,151 @@ where
         self.with_contract(contract_id, |contract| contract.state(), None)
     }
 
+    /// Get the contract state with the owned state limited to the seals with the given primary
+    /// components (for bitcoin, transaction outpoints).
+    ///
+    /// See [`Contract::state_by_primary`] for the details.
+    ///
+    /// # Panics
+    ///
+    /// If the contract id is not known.
+    pub fn contract_state_by_primary(
+        &self,
+        contract_id: ContractId,
+        primaries: impl IntoIterator<Item = <<Sp::Pile as Pile>::Seal as SealIndex>::Primary>,
+    ) -> ContractState<<Sp::Pile as Pile>::Seal>
+    where
+        <Sp::Pile as Pile>::Seal: SealIndex,
+    {
+        self.with_contract(contract_id, |contract| contract.state_by_primary(primaries), None)
+    }
+
+    /// Get the contract state as it was at a given block `height`.
+    ///
+    /// See [`Contract::state_at`] for the details.
+    ///
+    /// # Panics
+    ///
+    /// If the contract id is not known.
+    pub fn contract_state_at(
+        &self,
+        contract_id: ContractId,
+        height: u64,
+    ) -> ContractState<<Sp::Pile as Pile>::Seal> {
+        self.with_contract(contract_id, |contract| contract.state_at(height), None)
+    }
+
+    /// Compare the contract state at block height `from` with the state at block height `to`.
+    ///
+    /// See [`Contract::state_diff_at`] for the details.
+    ///
+    /// # Panics
+    ///
+    /// If the contract id is not known.
+    pub fn contract_state_diff_at(
+        &self,
+        contract_id: ContractId,
+        from: u64,
+        to: u64,
+    ) -> StateDiff<<Sp::Pile as Pile>::Seal> {
+        self.with_contract(contract_id, |contract| contract.state_diff_at(from, to), None)
+    }
+
+    /// Get a page of the contract operations matching the query, starting from the `cursor`.
+    ///
+    /// See [`Contract::query_operations`] for the details.
+    ///
+    /// # Panics
+    ///
+    /// If the contract id is not known.
+    pub fn contract_operations(
+        &self,
+        contract_id: ContractId,
+        query: &OpQuery,
+        cursor: OpCursor,
+    ) -> OpPage<<Sp::Pile as Pile>::Seal> {
+        self.with_contract(contract_id, |contract| contract.query_operations(query, cursor), None)
+    }
+
+    /// Get the state of all contracts as it was at a given block `height`.
+    ///
+    /// See [`Contract::state_at`] for the details.
+    pub fn wallet_state_at(&self, height: u64) -> WalletState<<Sp::Pile as Pile>::Seal> {
+        let iter = self
+            .contract_ids()
+            .map(|id| (id, self.contract_state_at(id, height)));
+        WalletState::from_contracts_state(iter)
+    }
+
+    /// Find contracts having unspent owned state assigned to the seals with the given primary
+    /// component (for bitcoin, a transaction outpoint).
+    ///
+    /// Contracts which don't have any state bound to the primary component are not included. See
+    /// [`Contract::owned_by_primary`] for the details.
+    pub fn owned_by_primary(
+        &self,
+        primary: <<Sp::Pile as Pile>::Seal as SealIndex>::Primary,
+    ) -> BTreeMap<ContractId, BTreeMap<StateName, Vec<OwnedState<<Sp::Pile as Pile>::Seal>>>>
+    where
+        <Sp::Pile as Pile>::Seal: SealIndex,
+    {
+        self.contract_ids()
+            .map(|id| {
+                (id, self.with_contract(id, |contract| contract.owned_by_primary(primary), None))
+            })
+            .filter(|(_, owned)| !owned.is_empty())
+            .collect()
+    }
+
     pub fn contract_articles(&self, contract_id: ContractId) -> Articles {
         self.with_contract(contract_id, |contract| contract.articles().clone(), None)
     }
 
+    /// Get all values of the global state `name` of a contract, decoded into type `T`.
+    ///
+    /// See [`Contract::global`] for the details.
+    ///
+    /// # Panics
+    ///
+    /// If the contract id is not known.
+    pub fn contract_global<T: StrictDecode + StrictEncode + StrictDumb>(
+        &self,
+        contract_id: ContractId,
+        name: impl Into<StateName>,
+    ) -> Result<Vec<T>, TypedStateError> {
+        self.with_contract(contract_id, |contract| contract.global(name), None)
+    }
+
+    /// Get the latest value of the global state `name` of a contract, decoded into type `T`.
+    ///
+    /// See [`Contract::global_latest`] for the details.
+    ///
+    /// # Panics
+    ///
+    /// If the contract id is not known.
+    pub fn contract_global_latest<T: StrictDecode + StrictEncode + StrictDumb>(
+        &self,
+        contract_id: ContractId,
+        name: impl Into<StateName>,
+    ) -> Result<T, TypedStateError> {
+        self.with_contract(contract_id, |contract| contract.global_latest(name), None)
+    }
+
+    /// Get the aggregated state `name` of a contract, decoded into type `T`.
+    ///
+    /// See [`Contract::aggregated`] for the details.
+    ///
+    /// # Panics
+    ///
+    /// If the contract id is not known.
+    pub fn contract_aggregated<T: StrictDecode + StrictEncode + StrictDumb>(
+        &self,
+        contract_id: ContractId,
+        name: impl Into<StateName>,
+    ) -> Result<T, TypedStateError> {
+        self.with_contract(contract_id, |contract| contract.aggregated(name), None)
+    }
+
     pub fn find_contract_id(&self, r: impl Into<ContractRef>) -> Option<ContractId> {
         match r.into() {
             ContractRef::Id(id) if self.has_contract(id) => Some(id),
@@ -329,6 +502,7 @@ where
         let contract = self.persistence.issue(params)?;
         let id = contract.contract_id();
         self.contracts.borrow_mut().insert(id, contract);
+        self.observers.notify(ContractEvent::Imported(id));
         Ok(id)
     }
 
@@ -351,89 +525,426 @@ where
         self.with_contract_mut(contract_id, |contract| contract.call(call, seals))
     }
 
-    #[cfg(not(feature = "async"))]
-    /// Update the status of all witnesses and single-use seal definitions.
+    /// Returns the sync checkpoints of the contracts, which may be used for the sync at the
+    /// `last_block_height` with the given `min_conformations`.
+    #[allow(clippy::type_complexity)]
+    fn sync_checkpoints(
+        &self,
+        last_block_height: u64,
+        min_conformations: u32,
+    ) -> HashMap<ContractId, SyncCheckpoint<<<Sp::Pile as Pile>::Seal as RgbSeal>::WitnessId>> {
+        self.persistence
+            .contract_ids()
+            .filter_map(|contract_id| {
+                let checkpoint = self.persistence.sync_checkpoint(contract_id)?;
+                (checkpoint.min_confirmations == min_conformations
+                    && checkpoint.tip_height <= last_block_height)
+                    .then_some((contract_id, checkpoint))
+            })
+            .collect()
+    }
+
+    /// Collects the witness ids of each of the contracts which has to be synchronized, together
+    /// with the list of unique witness ids across all contracts.
     ///
-    /// Applies rollbacks or forwards if required and recomputes the state of the affected
-    /// contracts.
-    pub fn update_witnesses<E: core::error::Error>(
-        &mut self,
-        resolver: impl Fn(<<Sp::Pile as Pile>::Seal as RgbSeal>::WitnessId) -> Result<WitnessStatus, E>,
+    /// Contracts which have a sync checkpoint with the tip block still present in the
+    /// `best_blocks` contribute only their pending witnesses, and are not loaded if they have
+    /// none.
+    ///
+    /// For each of the unique witnesses, the list of the blocks recorded for it by the contracts
+    /// where it is mined deeper than `min_conformations` is returned; these witnesses do not
+    /// require resolution unless one of those blocks got re-orged. `None` means that the witness
+    /// must be resolved in any case.
+    #[allow(clippy::type_complexity)]
+    fn witness_checks(
+        &self,
         last_block_height: u64,
         min_conformations: u32,
-    ) -> Result<(), MultiError<SyncError<E>, <Sp::Stock as Stock>::Error>> {
-        let mut changed_statuses = IndexMap::<_, WitnessStatus>::new();
-        let contract_ids = self.persistence.contract_ids().collect::<IndexSet<_>>();
-        for contract_id in contract_ids {
-            self.with_contract_mut(
+        mut checkpoints: HashMap<
+            ContractId,
+            SyncCheckpoint<<<Sp::Pile as Pile>::Seal as RgbSeal>::WitnessId>,
+        >,
+        best_blocks: &HashMap<u64, Option<Bytes32>>,
+    ) -> (
+        Vec<(ContractId, Vec<<<Sp::Pile as Pile>::Seal as RgbSeal>::WitnessId>)>,
+        IndexMap<<<Sp::Pile as Pile>::Seal as RgbSeal>::WitnessId, Option<Vec<(u64, Bytes32)>>>,
+    ) {
+        let mut contract_wids = vec![];
+        let mut checks = IndexMap::<_, Option<Vec<_>>>::new();
+        for contract_id in self.persistence.contract_ids().collect::<IndexSet<_>>() {
+            let checkpoint = checkpoints.remove(&contract_id).filter(|checkpoint| {
+                best_blocks.get(&checkpoint.tip_height) == Some(&Some(checkpoint.tip_hash))
+            });
+            let pending = checkpoint.map(|checkpoint| checkpoint.pending);
+            if pending.as_ref().is_some_and(BTreeSet::is_empty) {
+                continue;
+            }
+            let wids = self.with_contract(
                 contract_id,
-                |contract| -> Result<(), MultiError<SyncError<E>, <Sp::Stock as Stock>::Error>> {
-                    for witness_id in contract.witness_ids() {
-                        let old_status = contract.witness_status(witness_id);
-                        if matches!(old_status, WitnessStatus::Mined(height) if last_block_height - height.get() > min_conformations as u64) {
-                            continue
-                        }
-                        let new_status = match changed_statuses.get(&witness_id) {
-                            None => resolver(witness_id)
-                                .map_err(SyncError::Status)
-                                .map_err(MultiError::A),
-                            Some(witness_id) => Ok(*witness_id),
-                        }?;
-                        if new_status != old_status {
-                            changed_statuses.insert(witness_id, new_status);
+                |contract| {
+                    let wids = match pending {
+                        Some(pending) => pending.into_iter().collect::<Vec<_>>(),
+                        None => contract.witness_ids().collect(),
+                    };
+                    for wid in &wids {
+                        let check = match contract.witness_status(*wid) {
+                            WitnessStatus::Mined(height)
+                                if last_block_height.saturating_sub(height.get())
+                                    > min_conformations as u64 =>
+                            {
+                                // Deeply mined witnesses are re-checked only if their block was
+                                // re-orged
+                                Some(
+                                    contract
+                                        .witness_block(*wid)
+                                        .map(|block| (height.get(), block.hash)),
+                                )
+                            }
+                            _ => None,
+                        };
+                        match (checks.entry(*wid).or_insert_with(|| Some(vec![])), check) {
+                            (Some(blocks), Some(block)) => blocks.extend(block),
+                            (entry, _) => *entry = None,
                         }
                     }
+                    wids
+                },
+                None,
+            );
+            contract_wids.push((contract_id, wids));
+        }
+        (contract_wids, checks)
+    }
+
+    /// Synchronizes each of the contracts with the statuses of its resolved witnesses, and then
+    /// archives the tentative witnesses which expired under the expiry policy.
+    ///
+    /// Saves a new sync checkpoint for each of the synchronized contracts, if the hash of the
+    /// blockchain tip is known.
+    #[allow(clippy::type_complexity)]
+    fn sync_resolved<E: core::error::Error>(
+        &mut self,
+        contract_wids: Vec<(ContractId, Vec<<<Sp::Pile as Pile>::Seal as RgbSeal>::WitnessId>)>,
+        resolved: &HashMap<<<Sp::Pile as Pile>::Seal as RgbSeal>::WitnessId, WitnessInfo>,
+        last_block_height: u64,
+        min_conformations: u32,
+        tip_hash: Option<Bytes32>,
+    ) -> Result<(), MultiError<SyncError<E>, <Sp::Stock as Stock>::Error>> {
+        let policy = self.expiry;
+        let timestamp = Utc::now().timestamp();
+        for (contract_id, wids) in contract_wids {
+            let changed = wids
+                .into_iter()
+                .filter_map(|wid| resolved.get(&wid).map(|info| (wid, *info)))
+                .collect::<Vec<_>>();
+            self.with_contract_mut(contract_id, |contract| {
+                // Offchain witnesses are not expected to be known to the resolver
+                let changed = changed
+                    .into_iter()
+                    .filter(|(wid, info)| {
+                        !info.status.is_archived() || !contract.witness_status(*wid).is_offchain()
+                    })
+                    .collect::<Vec<_>>();
+                if !changed.is_empty() {
+                    contract.sync(changed)?;
+                }
+                contract.expire_witnesses(
+                    |wid| {
+                        resolved.get(&wid).is_some_and(|info| {
+                            info.status.is_tentative() || info.status.is_mined()
+                        })
+                    },
+                    policy,
+                    last_block_height,
+                    timestamp,
+                )
+            })
+            .map_err(MultiError::from_other_a)?;
+
+            let Some(tip_hash) = tip_hash else {
+                continue;
+            };
+            let pending = self.with_contract(
+                contract_id,
+                |contract| {
                     contract
-                        .sync(changed_statuses.iter().map(|(id, status)| (*id, *status)))
-                        .map_err(MultiError::from_other_a)?;
-                    Ok(())
+                        .witness_ids()
+                        .filter(|wid| match contract.witness_status(*wid) {
+                            WitnessStatus::Mined(height) => {
+                                last_block_height.saturating_sub(height.get())
+                                    <= min_conformations as u64
+                            }
+                            _ => true,
+                        })
+                        .collect()
                 },
-            )?;
+                None,
+            );
+            let checkpoint = SyncCheckpoint {
+                tip_height: last_block_height,
+                tip_hash,
+                min_confirmations: min_conformations,
+                pending,
+            };
+            // The checkpoint only speeds up the next sync, so failing to save it is not an error
+            let _ = self
+                .persistence
+                .set_sync_checkpoint(contract_id, Some(&checkpoint));
         }
         Ok(())
     }
 
+    /// Heights of the best chain blocks required to check the sync checkpoints.
+    #[allow(clippy::type_complexity)]
+    fn checkpoint_heights(
+        checkpoints: &HashMap<
+            ContractId,
+            SyncCheckpoint<<<Sp::Pile as Pile>::Seal as RgbSeal>::WitnessId>,
+        >,
+        last_block_height: u64,
+    ) -> BTreeSet<u64> {
+        checkpoints
+            .values()
+            .map(|checkpoint| checkpoint.tip_height)
+            .chain([last_block_height])
+            .collect()
+    }
+
+    /// Heights of the blocks recorded for the deeply mined witnesses, which are not yet known
+    /// among the `best_blocks`.
+    #[allow(clippy::type_complexity)]
+    fn witness_heights(
+        checks: &IndexMap<
+            <<Sp::Pile as Pile>::Seal as RgbSeal>::WitnessId,
+            Option<Vec<(u64, Bytes32)>>,
+        >,
+        best_blocks: &HashMap<u64, Option<Bytes32>>,
+    ) -> BTreeSet<u64> {
+        checks
+            .values()
+            .flatten()
+            .flatten()
+            .map(|(height, _)| *height)
+            .filter(|height| !best_blocks.contains_key(height))
+            .collect()
+    }
+
+    /// Selects the witnesses which have to be resolved: the ones which are not mined deep enough,
+    /// and the ones which blocks were re-orged from the best chain.
+    #[allow(clippy::type_complexity)]
+    fn witnesses_to_resolve(
+        checks: IndexMap<
+            <<Sp::Pile as Pile>::Seal as RgbSeal>::WitnessId,
+            Option<Vec<(u64, Bytes32)>>,
+        >,
+        best_blocks: &HashMap<u64, Option<Bytes32>>,
+    ) -> Vec<<<Sp::Pile as Pile>::Seal as RgbSeal>::WitnessId> {
+        checks
+            .into_iter()
+            .filter(|(_, blocks)| match blocks {
+                None => true,
+                Some(blocks) => blocks
+                    .iter()
+                    .any(|(height, hash)| best_blocks.get(height) != Some(&Some(*hash))),
+            })
+            .map(|(wid, _)| wid)
+            .collect()
+    }
+
+    #[cfg(not(feature = "async"))]
+    /// Update the status of all witnesses and single-use seal definitions.
+    ///
+    /// Collects the unique witness ids across all contracts and resolves them with a single call
+    /// to the `resolver`; then applies rollbacks or forwards if required and recomputes the state
+    /// of the affected contracts.
+    ///
+    /// Witnesses mined deeper than `min_conformations` are not resolved again, unless the hash of
+    /// the block recorded for them doesn't match the hash of the block at the same height in the
+    /// best chain, as reported by the `block_resolver`.
+    ///
+    /// The sync is incremental: each synchronized contract gets a persisted checkpoint (see
+    /// [`SyncCheckpoint`]), and while the checkpoint tip stays in the best chain, the next sync
+    /// checks only the pending witnesses of the contract, not loading the contracts without
+    /// pending witnesses at all.
+    ///
+    /// Tentative witnesses which are not reported by the `resolver` are archived once they expire
+    /// under the expiry policy (see [`Self::set_expiry_policy`]).
+    ///
+    /// Offchain witnesses are promoted once the `resolver` reports them as tentative or mined,
+    /// but are never archived by it (see [`Self::archive_offchain`]).
+    pub fn update_witnesses_with_blocks<R>(
+        &mut self,
+        resolver: R,
+        block_resolver: impl Fn(u64) -> Result<Option<Bytes32>, R::Error>,
+        last_block_height: u64,
+        min_conformations: u32,
+    ) -> Result<(), MultiError<SyncError<R::Error>, <Sp::Stock as Stock>::Error>>
+    where
+        R: WitnessResolver<<<Sp::Pile as Pile>::Seal as RgbSeal>::WitnessId>,
+    {
+        let checkpoints = self.sync_checkpoints(last_block_height, min_conformations);
+        let mut best_blocks = HashMap::new();
+        for height in Self::checkpoint_heights(&checkpoints, last_block_height) {
+            let best = block_resolver(height)
+                .map_err(SyncError::Status)
+                .map_err(MultiError::A)?;
+            best_blocks.insert(height, best);
+        }
+        let (contract_wids, checks) =
+            self.witness_checks(last_block_height, min_conformations, checkpoints, &best_blocks);
+        for height in Self::witness_heights(&checks, &best_blocks) {
+            let best = block_resolver(height)
+                .map_err(SyncError::Status)
+                .map_err(MultiError::A)?;
+            best_blocks.insert(height, best);
+        }
+
+        let wids = Self::witnesses_to_resolve(checks, &best_blocks);
+        let resolved = resolver
+            .resolve_witnesses(&wids)
+            .map_err(SyncError::Status)
+            .map_err(MultiError::A)?;
+        let tip_hash = best_blocks[&last_block_height];
+        self.sync_resolved(contract_wids, &resolved, last_block_height, min_conformations, tip_hash)
+    }
+
     #[cfg(feature = "async")]
     /// Update the status of all witnesses and single-use seal definitions.
     ///
-    /// Applies rollbacks or forwards if required and recomputes the state of the affected
-    /// contracts.
-    pub async fn update_witnesses_async<E: core::error::Error>(
+    /// Collects the unique witness ids across all contracts and resolves them with a single call
+    /// to the `resolver`; then applies rollbacks or forwards if required and recomputes the state
+    /// of the affected contracts.
+    ///
+    /// Witnesses mined deeper than `min_conformations` are not resolved again, unless the hash of
+    /// the block recorded for them doesn't match the hash of the block at the same height in the
+    /// best chain, as reported by the `block_resolver`.
+    ///
+    /// The sync is incremental: each synchronized contract gets a persisted checkpoint (see
+    /// [`SyncCheckpoint`]), and while the checkpoint tip stays in the best chain, the next sync
+    /// checks only the pending witnesses of the contract, not loading the contracts without
+    /// pending witnesses at all.
+    ///
+    /// Tentative witnesses which are not reported by the `resolver` are archived once they expire
+    /// under the expiry policy (see [`Self::set_expiry_policy`]).
+    ///
+    /// Offchain witnesses are promoted once the `resolver` reports them as tentative or mined,
+    /// but are never archived by it (see [`Self::archive_offchain`]).
+    pub async fn update_witnesses_with_blocks_async<R>(
         &mut self,
-        resolver: impl AsyncFn(
-            <<Sp::Pile as Pile>::Seal as RgbSeal>::WitnessId,
-        ) -> Result<WitnessStatus, E>,
+        resolver: R,
+        block_resolver: impl AsyncFn(u64) -> Result<Option<Bytes32>, R::Error>,
         last_block_height: u64,
         min_conformations: u32,
-    ) -> Result<(), MultiError<SyncError<E>, <Sp::Stock as Stock>::Error>> {
-        let mut changed_statuses = IndexMap::<_, WitnessStatus>::new();
-        let contract_ids = self.persistence.contract_ids().collect::<IndexSet<_>>();
-        for contract_id in contract_ids {
-            self.with_contract_mut_async(
+    ) -> Result<(), MultiError<SyncError<R::Error>, <Sp::Stock as Stock>::Error>>
+    where
+        R: WitnessResolver<<<Sp::Pile as Pile>::Seal as RgbSeal>::WitnessId>,
+    {
+        let checkpoints = self.sync_checkpoints(last_block_height, min_conformations);
+        let mut best_blocks = HashMap::new();
+        for height in Self::checkpoint_heights(&checkpoints, last_block_height) {
+            let best = block_resolver(height)
+                .await
+                .map_err(SyncError::Status)
+                .map_err(MultiError::A)?;
+            best_blocks.insert(height, best);
+        }
+        let (contract_wids, checks) =
+            self.witness_checks(last_block_height, min_conformations, checkpoints, &best_blocks);
+        for height in Self::witness_heights(&checks, &best_blocks) {
+            let best = block_resolver(height)
+                .await
+                .map_err(SyncError::Status)
+                .map_err(MultiError::A)?;
+            best_blocks.insert(height, best);
+        }
+
+        let wids = Self::witnesses_to_resolve(checks, &best_blocks);
+        let resolved = resolver
+            .resolve_witnesses_async(&wids)
+            .await
+            .map_err(SyncError::Status)
+            .map_err(MultiError::A)?;
+        let tip_hash = best_blocks[&last_block_height];
+        self.sync_resolved(contract_wids, &resolved, last_block_height, min_conformations, tip_hash)
+    }
+
+    #[cfg(not(feature = "async"))]
+    /// Update the status of all witnesses and single-use seal definitions, without knowing the
+    /// blocks of the best chain.
+    ///
+    /// Since re-orgs of the blocks can't be detected, the witnesses are resolved each time, even
+    /// if they are mined deep enough, and no sync checkpoints are saved. Prefer
+    /// [`Self::update_witnesses_with_blocks`] with a block resolver.
+    pub fn update_witnesses<R>(
+        &mut self,
+        resolver: R,
+        last_block_height: u64,
+        min_conformations: u32,
+    ) -> Result<(), MultiError<SyncError<R::Error>, <Sp::Stock as Stock>::Error>>
+    where
+        R: WitnessResolver<<<Sp::Pile as Pile>::Seal as RgbSeal>::WitnessId>,
+    {
+        self.update_witnesses_with_blocks(
+            resolver,
+            |_| Ok(None),
+            last_block_height,
+            min_conformations,
+        )
+    }
+
+    #[cfg(feature = "async")]
+    /// Update the status of all witnesses and single-use seal definitions, without knowing the
+    /// blocks of the best chain.
+    ///
+    /// Since re-orgs of the blocks can't be detected, the witnesses are resolved each time, even
+    /// if they are mined deep enough, and no sync checkpoints are saved. Prefer
+    /// [`Self::update_witnesses_with_blocks_async`] with a block resolver.
+    pub async fn update_witnesses_async<R>(
+        &mut self,
+        resolver: R,
+        last_block_height: u64,
+        min_conformations: u32,
+    ) -> Result<(), MultiError<SyncError<R::Error>, <Sp::Stock as Stock>::Error>>
+    where
+        R: WitnessResolver<<<Sp::Pile as Pile>::Seal as RgbSeal>::WitnessId>,
+    {
+        self.update_witnesses_with_blocks_async(
+            resolver,
+            async |_| Ok(None),
+            last_block_height,
+            min_conformations,
+        )
+        .await
+    }
+
+    /// Marks the witnesses as archived in all the contracts which know them, rolling back the
+    /// operations which are no longer valid.
+    ///
+    /// Used for the witnesses which are known to be invalid while the witness resolvers may not
+    /// report them as such, like the transactions conflicting with other transactions.
+    #[allow(clippy::type_complexity)]
+    pub fn archive_witnesses(
+        &mut self,
+        wids: &[<<Sp::Pile as Pile>::Seal as RgbSeal>::WitnessId],
+    ) -> Result<(), MultiError<AcceptError, <Sp::Stock as Stock>::Error, <Sp::Pile as Pile>::Error>>
+    {
+        if wids.is_empty() {
+            return Ok(());
+        }
+        for contract_id in self.persistence.contract_ids().collect::<IndexSet<_>>() {
+            let known = self.with_contract(
                 contract_id,
-                async |contract| -> Result<(), MultiError<SyncError<E>, <Sp::Stock as Stock>::Error>> {
-                    for witness_id in contract.witness_ids() {
-                        let old_status = contract.witness_status(witness_id);
-                        if matches!(old_status, WitnessStatus::Mined(height) if last_block_height - height.get() > min_conformations as u64) {
-                            continue
-                        }
-                        let new_status = match changed_statuses.get(&witness_id) {
-                            None => resolver(witness_id)
-                                .await
-                                .map_err(SyncError::Status)
-                                .map_err(MultiError::A),
-                            Some(witness_id) => Ok(*witness_id),
-                        }?;
-                        if new_status != old_status {
-                            changed_statuses.insert(witness_id, new_status);
-                        }
-                    }
-                    contract
-                        .sync(changed_statuses.iter().map(|(id, status)| (*id, *status)))
-                        .map_err(MultiError::from_other_a)?;
-                    Ok(())
-                },
-            ).await?;
+                |contract| contract.witness_ids().any(|wid| wids.contains(&wid)),
+                None,
+            );
+            if known {
+                self.reset_sync_checkpoint(contract_id)
+                    .map_err(MultiError::C)?;
+                self.with_contract_mut(contract_id, |contract| {
+                    contract.sync(wids.iter().map(|wid| (*wid, WitnessStatus::Archived)))
+                })
+                .map_err(MultiError::with_third)?;
+            }
         }
         Ok(())
     }
@@ -444,14 +955,124 @@ where
     /// # Panics
     ///
     /// If the contract id is not known.
+    ///
+    /// # Errors
+    ///
+    /// If the sync checkpoint of the contract can't be removed; the witness is not included then.
     pub fn include(
         &mut self,
         contract_id: ContractId,
         opid: Opid,
         pub_witness: &<<Sp::Pile as Pile>::Seal as RgbSeal>::Published,
         anchor: <<Sp::Pile as Pile>::Seal as RgbSeal>::Client,
-    ) {
-        self.with_contract_mut(contract_id, |contract| contract.include(opid, anchor, pub_witness))
+    ) -> Result<(), <Sp::Pile as Pile>::Error> {
+        self.reset_sync_checkpoint(contract_id)?;
+        self.with_contract_mut(contract_id, |contract| contract.include(opid, anchor, pub_witness));
+        Ok(())
+    }
+
+    /// Include an operation and its offchain witness, like a commitment transaction of a payment
+    /// channel (see [`Contract::include_offchain`]).
+    ///
+    /// # Panics
+    ///
+    /// If the contract id is not known.
+    ///
+    /// # Errors
+    ///
+    /// If the sync checkpoint of the contract can't be removed; the witness is not included then.
+    pub fn include_offchain(
+        &mut self,
+        contract_id: ContractId,
+        opid: Opid,
+        pub_witness: &<<Sp::Pile as Pile>::Seal as RgbSeal>::Published,
+        anchor: <<Sp::Pile as Pile>::Seal as RgbSeal>::Client,
+    ) -> Result<(), <Sp::Pile as Pile>::Error> {
+        self.reset_sync_checkpoint(contract_id)?;
+        self.with_contract_mut(contract_id, |contract| {
+            contract.include_offchain(opid, anchor, pub_witness)
+        });
+        Ok(())
+    }
+
+    /// Promotes offchain witnesses which got published, like a commitment transaction broadcasted
+    /// on a channel close, to the tentative or mined status in all the contracts which know them.
+    ///
+    /// Witnesses which are not offchain are left intact, since their status is maintained by
+    /// [`Self::update_witnesses`].
+    ///
+    /// # Errors
+    ///
+    /// If any of the statuses is neither [`WitnessStatus::Tentative`] nor
+    /// [`WitnessStatus::Mined`]; no witnesses are promoted then.
+    #[allow(clippy::type_complexity)]
+    pub fn promote_witnesses(
+        &mut self,
+        witnesses: &[(<<Sp::Pile as Pile>::Seal as RgbSeal>::WitnessId, WitnessInfo)],
+    ) -> Result<(), MultiError<PromoteError, <Sp::Stock as Stock>::Error, <Sp::Pile as Pile>::Error>>
+    {
+        if let Some((_, info)) = witnesses
+            .iter()
+            .find(|(_, info)| !info.status.is_tentative() && !info.status.is_mined())
+        {
+            return Err(MultiError::A(PromoteError::InvalidStatus(info.status)));
+        }
+        for contract_id in self.persistence.contract_ids().collect::<IndexSet<_>>() {
+            let promoted = self.with_contract(
+                contract_id,
+                |contract| {
+                    witnesses
+                        .iter()
+                        .filter(|(wid, _)| {
+                            contract.witness_ids().any(|id| id == *wid)
+                                && contract.witness_status(*wid).is_offchain()
+                        })
+                        .copied()
+                        .collect::<Vec<_>>()
+                },
+                None,
+            );
+            if !promoted.is_empty() {
+                self.reset_sync_checkpoint(contract_id)
+                    .map_err(MultiError::C)?;
+                self.with_contract_mut(contract_id, |contract| contract.sync(promoted))
+                    .map_err(|e| MultiError::from_other_a(MultiError::with_third(e)))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Archives offchain witnesses selected by the `superseded` filter in all the contracts, like
+    /// the commitment transactions of the past channel states, rolling back the operations which
+    /// are no longer valid.
+    ///
+    /// Returns the ids of the archived witnesses.
+    #[allow(clippy::type_complexity)]
+    pub fn archive_offchain(
+        &mut self,
+        superseded: impl Fn(&Witness<<Sp::Pile as Pile>::Seal>) -> bool,
+    ) -> Result<
+        Vec<<<Sp::Pile as Pile>::Seal as RgbSeal>::WitnessId>,
+        MultiError<AcceptError, <Sp::Stock as Stock>::Error, <Sp::Pile as Pile>::Error>,
+    > {
+        let mut wids = IndexSet::new();
+        for contract_id in self.persistence.contract_ids().collect::<IndexSet<_>>() {
+            self.with_contract(
+                contract_id,
+                |contract| {
+                    wids.extend(
+                        contract
+                            .witnesses()
+                            .filter(|witness| witness.status.is_offchain() && superseded(witness))
+                            .map(|witness| witness.id),
+                    )
+                },
+                None,
+            );
+        }
+        let wids = wids.into_iter().collect::<Vec<_>>();
+        self.archive_witnesses(&wids)?;
+        Ok(wids)
     }
 
     /// Export a contract to a strictly encoded stream.
@@ -481,6 +1102,7 @@ where
     pub fn purge(&mut self, contract_id: ContractId) -> Result<(), Sp::Error> {
         self.contracts.borrow_mut().remove(&contract_id);
         self.persistence.purge(contract_id)?;
+        self.observers.notify(ContractEvent::Purged(contract_id));
         Ok(())
     }
 
@@ -567,11 +1189,14 @@ where
 
                 let contract = self.persistence.import_contract(articles, consignment)?;
                 self.contracts.borrow_mut().insert(contract_id, contract);
+                self.observers.notify(ContractEvent::Imported(contract_id));
                 Ok(())
             } else {
                 Err(MultiError::A(ConsumeError::UnknownContract(contract_id)))
             }
         } else {
+            self.reset_sync_checkpoint(contract_id)
+                .map_err(MultiError::C)?;
             self.with_contract_mut(contract_id, |contract| {
                 contract.consume_internal(reader, seal_resolver, sig_validator)
             })
@@ -734,3 +1359,15 @@ pub enum SyncError<E: core::error::Error> {
     #[display(inner)]
     Forward(AcceptError),
 }
+
+#[derive(Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum PromoteError {
+    /// offchain witnesses can be promoted only to the tentative or mined status, while {0} status
+    /// was requested.
+    InvalidStatus(WitnessStatus),
+
+    #[from]
+    #[display(inner)]
+    Forward(AcceptError),
+}
@@ -0,0 +1,159 @@
+// Standard Library for RGB smart contracts
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2019-2025 by Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+// Written in 2024-2025 by Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 LNP/BP Laboratories,
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2025 RGB Consortium, Switzerland.
+// Copyright (C) 2019-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+use hypersonic::{ContractId, Opid};
+use rgb::RgbSeal;
+
+use crate::WitnessStatus;
+
+/// Event notifying about a change in a contract, which can be received by subscribing to a
+/// [`crate::Contract`] or [`crate::Contracts`].
+#[derive(Clone, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize),
+    serde(rename_all = "camelCase", bound = "Seal::WitnessId: serde::Serialize")
+)]
+pub enum ContractEvent<Seal: RgbSeal> {
+    /// Contract was issued or imported from a consignment.
+    Imported(ContractId),
+
+    /// Contract was purged.
+    Purged(ContractId),
+
+    /// A new operation was added to the contract, either by a method call or from a consignment.
+    OperationAdded { contract_id: ContractId, opid: Opid },
+
+    /// Operation was rolled back during the synchronization, since all its witnesses were
+    /// archived.
+    OperationRolledBack { contract_id: ContractId, opid: Opid },
+
+    /// Previously rolled back operation was re-applied during the synchronization, since one of
+    /// its witnesses became valid again.
+    OperationForwarded { contract_id: ContractId, opid: Opid },
+
+    /// Witness status was changed during the synchronization.
+    WitnessStatusChanged {
+        contract_id: ContractId,
+        witness_id: Seal::WitnessId,
+        old: WitnessStatus,
+        new: WitnessStatus,
+    },
+
+    /// Contract APIs were upgraded with the articles from a consignment.
+    ArticlesUpgraded(ContractId),
+}
+
+impl<Seal: RgbSeal> ContractEvent<Seal> {
+    /// The contract the event relates to.
+    pub fn contract_id(&self) -> ContractId {
+        match self {
+            Self::Imported(contract_id)
+            | Self::Purged(contract_id)
+            | Self::ArticlesUpgraded(contract_id)
+            | Self::OperationAdded { contract_id, .. }
+            | Self::OperationRolledBack { contract_id, .. }
+            | Self::OperationForwarded { contract_id, .. }
+            | Self::WitnessStatusChanged { contract_id, .. } => *contract_id,
+        }
+    }
+}
+
+/// A set of subscribers receiving contract events via channels.
+///
+/// Subscribers which have dropped their receiver are removed on the next notification.
+///
+/// Besides its own subscribers, the set may have subscribers attached from another set for a
+/// duration of some operation (see [`Self::attach`] and [`Self::detach`]).
+#[derive(Clone, Debug)]
+pub(crate) struct Observers<Seal: RgbSeal> {
+    own: Vec<Sender<ContractEvent<Seal>>>,
+    attached: Vec<Sender<ContractEvent<Seal>>>,
+}
+
+impl<Seal: RgbSeal> Default for Observers<Seal> {
+    fn default() -> Self { Self { own: vec![], attached: vec![] } }
+}
+
+impl<Seal: RgbSeal> Observers<Seal> {
+    pub fn subscribe(&mut self) -> Receiver<ContractEvent<Seal>> {
+        let (sender, receiver) = channel();
+        self.own.push(sender);
+        receiver
+    }
+
+    pub fn notify(&mut self, event: ContractEvent<Seal>) {
+        // The receiver may be dropped, which is not an error
+        self.own
+            .retain(|observer| observer.send(event.clone()).is_ok());
+        self.attached
+            .retain(|observer| observer.send(event.clone()).is_ok());
+    }
+
+    /// Attaches all own subscribers from the other set, replacing the previously attached ones.
+    pub fn attach(&mut self, other: &Self) { self.attached = other.own.clone(); }
+
+    /// Detaches the subscribers attached with [`Self::attach`], returning the set of those of them
+    /// which are still subscribed.
+    pub fn detach(&mut self) -> Self {
+        Self { own: core::mem::take(&mut self.attached), attached: vec![] }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![cfg_attr(coverage_nightly, coverage(off))]
+
+    use bp::seals::TxoSeal;
+    use hypersonic::ContractId;
+
+    use super::*;
+
+    #[test]
+    fn dropped_subscribers() {
+        let mut global = Observers::<TxoSeal>::default();
+        let global_rx = global.subscribe();
+        let dropped_global = global.subscribe();
+
+        let mut observers = Observers::<TxoSeal>::default();
+        let rx = observers.subscribe();
+        drop(observers.subscribe());
+        observers.attach(&global);
+        drop(dropped_global);
+
+        let contract_id = ContractId::from([0xAC; 32]);
+        observers.notify(ContractEvent::Imported(contract_id));
+        assert_eq!(observers.own.len(), 1);
+        assert_eq!(observers.attached.len(), 1);
+        assert_eq!(rx.try_recv().unwrap().contract_id(), contract_id);
+        assert_eq!(global_rx.try_recv().unwrap().contract_id(), contract_id);
+
+        let global = observers.detach();
+        assert_eq!(global.own.len(), 1);
+        assert!(observers.attached.is_empty());
+        assert_eq!(observers.own.len(), 1);
+    }
+}
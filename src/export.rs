@@ -0,0 +1,376 @@
+// Standard Library for RGB smart contracts
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2019-2025 by Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+// Written in 2024-2025 by Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 LNP/BP Laboratories,
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2025 RGB Consortium, Switzerland.
+// Copyright (C) 2019-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+//! Spreadsheet-friendly export of the contract state and history.
+//!
+//! Both the state and the history are exported as a list of [`ExportRecord`]s, wrapping the
+//! [`OwnedState`] and [`ImmutableState`], which can be written as CSV ([`write_csv`]) or JSON
+//! Lines ([`write_jsonl`]). In CSV, the state is flattened into a fixed set of columns.
+
+use alloc::collections::BTreeMap;
+use core::fmt::Display;
+use std::io;
+
+use amplify::confinement::KeyedCollection;
+use chrono::{DateTime, Utc};
+use hypersonic::{CellAddr, ContractId, Issuer, StateName, Stock};
+use rgb::{RgbSeal, RgbSealDef};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Assignment, CodexId, Contract, ContractState, Contracts, ImmutableState, OwnedState, Pile,
+    SealIndex, Stockpile, WalletState, WitnessStatus,
+};
+
+/// Errors happening during writing export records.
+#[derive(Debug, Display, Error, From)]
+#[display(inner)]
+pub enum ExportError {
+    #[from]
+    Io(io::Error),
+
+    #[from]
+    Csv(csv::Error),
+
+    #[from]
+    Json(serde_json::Error),
+}
+
+/// Exported element of the contract state.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize)]
+#[serde(
+    rename_all = "camelCase",
+    tag = "kind",
+    bound = "Seal: Serialize + for<'d> Deserialize<'d>"
+)]
+pub enum ExportedState<Seal> {
+    /// Owned state, assigned to a single-use seal.
+    ///
+    /// The seal is `None` for the state defined by the history operations and assigned to seals
+    /// not known to the wallet.
+    Owned(OwnedState<Option<Seal>>),
+
+    /// Global (immutable) state.
+    Global(ImmutableState),
+}
+
+impl<Seal> ExportedState<Seal> {
+    pub fn addr(&self) -> CellAddr {
+        match self {
+            Self::Owned(state) => state.addr,
+            Self::Global(state) => state.addr,
+        }
+    }
+
+    pub fn status(&self) -> WitnessStatus {
+        match self {
+            Self::Owned(state) => state.status,
+            Self::Global(state) => state.status,
+        }
+    }
+}
+
+/// A single record of the contract state or history export.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize)]
+#[serde(
+    rename_all = "camelCase",
+    bound = "Seal: Serialize + for<'d> Deserialize<'d>, Seal::WitnessId: Serialize"
+)]
+pub struct ExportRecord<Seal: RgbSeal> {
+    #[serde(with = "serde_with::rust::display_fromstr")]
+    pub contract_id: ContractId,
+    pub contract_name: String,
+    #[serde(with = "serde_with::rust::display_fromstr")]
+    pub state_name: StateName,
+    #[serde(flatten)]
+    pub state: ExportedState<Seal>,
+    /// The best witness of the operation defining the state ("best" means "the most deeply
+    /// mined"); `None` for genesis.
+    pub witness_id: Option<Seal::WitnessId>,
+    /// Timestamp of the block mining the state, or the contract issue timestamp for genesis.
+    ///
+    /// `None` if the state is not mined, or the resolver hasn't provided the block details.
+    pub timestamp: Option<DateTime<Utc>>,
+}
+
+impl<Seal: RgbSeal> ExportRecord<Seal> {
+    /// Block height mining the state, taken from its status.
+    pub fn height(&self) -> Option<u64> {
+        match self.state.status() {
+            WitnessStatus::Mined(height) => Some(height.get()),
+            _ => None,
+        }
+    }
+}
+
+/// A row of the CSV export; the order of the fields defines the order of the columns.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CsvRow {
+    contract_id: String,
+    contract_name: String,
+    state_name: String,
+    kind: &'static str,
+    addr: String,
+    outpoint: Option<String>,
+    value: String,
+    status: &'static str,
+    height: Option<u64>,
+    witness_id: Option<String>,
+    timestamp: Option<DateTime<Utc>>,
+}
+
+impl<Seal: SealIndex> From<&ExportRecord<Seal>> for CsvRow
+where
+    Seal::Primary: Display,
+    Seal::WitnessId: Display,
+{
+    fn from(record: &ExportRecord<Seal>) -> Self {
+        let (kind, outpoint, value) = match &record.state {
+            ExportedState::Owned(state) => (
+                "owned",
+                state
+                    .assignment
+                    .seal
+                    .as_ref()
+                    .map(|seal| seal.primary().to_string()),
+                state.assignment.data.to_string(),
+            ),
+            ExportedState::Global(state) => ("global", None, state.data.verified.to_string()),
+        };
+        CsvRow {
+            contract_id: record.contract_id.to_string(),
+            contract_name: record.contract_name.clone(),
+            state_name: record.state_name.to_string(),
+            kind,
+            addr: record.state.addr().to_string(),
+            outpoint,
+            value,
+            status: status_name(record.state.status()),
+            height: record.height(),
+            witness_id: record.witness_id.as_ref().map(ToString::to_string),
+            timestamp: record.timestamp,
+        }
+    }
+}
+
+/// Writes the records as CSV, including the header row.
+///
+/// The owned and global state is flattened into the same set of columns; `outpoint` is the
+/// primary component of the seal (for bitcoin, an outpoint) and is empty for the global state.
+pub fn write_csv<'r, Seal: SealIndex + 'r>(
+    records: impl IntoIterator<Item = &'r ExportRecord<Seal>>,
+    writer: impl io::Write,
+) -> Result<(), ExportError>
+where
+    Seal::Primary: Display,
+    Seal::WitnessId: Display,
+{
+    let mut writer = csv::Writer::from_writer(writer);
+    for record in records {
+        writer.serialize(CsvRow::from(record))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes the records as JSON Lines, one JSON object per line.
+pub fn write_jsonl<'r, Seal: RgbSeal + Serialize + for<'d> Deserialize<'d> + 'r>(
+    records: impl IntoIterator<Item = &'r ExportRecord<Seal>>,
+    mut writer: impl io::Write,
+) -> Result<(), ExportError>
+where
+    Seal::WitnessId: Serialize,
+{
+    for record in records {
+        serde_json::to_writer(&mut writer, record)?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn status_name(status: WitnessStatus) -> &'static str {
+    match status {
+        WitnessStatus::Genesis => "genesis",
+        WitnessStatus::Mined(_) => "mined",
+        WitnessStatus::Offchain => "offchain",
+        WitnessStatus::Tentative => "tentative",
+        WitnessStatus::Archived => "archived",
+    }
+}
+
+impl<S: Stock, P: Pile> Contract<S, P> {
+    fn export_record(
+        &self,
+        state_name: &StateName,
+        state: ExportedState<P::Seal>,
+    ) -> ExportRecord<P::Seal> {
+        let articles = self.articles();
+        let meta = articles.contract_meta();
+        let witness_id = self.best_op_witness(state.addr().opid).map(|(wid, _)| wid);
+        let timestamp = match (state.status(), witness_id) {
+            (WitnessStatus::Genesis, _) => DateTime::from_timestamp(meta.timestamp, 0),
+            (WitnessStatus::Mined(_), Some(wid)) => {
+                self.witness_block(wid).and_then(|block| block.time())
+            }
+            _ => None,
+        };
+        ExportRecord {
+            contract_id: self.contract_id(),
+            contract_name: meta.name.to_string(),
+            state_name: state_name.clone(),
+            state,
+            witness_id,
+            timestamp,
+        }
+    }
+
+    /// Converts the contract state into export records.
+    ///
+    /// The owned state comes first, followed by the global state; the aggregated state is not
+    /// exported since it is not bound to any cell.
+    pub fn state_records(&self, state: &ContractState<P::Seal>) -> Vec<ExportRecord<P::Seal>> {
+        let owned = state.owned.iter().flat_map(|(name, states)| {
+            states.iter().map(move |owned| {
+                let owned = OwnedState {
+                    addr: owned.addr,
+                    assignment: Assignment {
+                        seal: Some(owned.assignment.seal.clone()),
+                        data: owned.assignment.data.clone(),
+                    },
+                    status: owned.status,
+                };
+                self.export_record(name, ExportedState::Owned(owned))
+            })
+        });
+        let global = state.immutable.iter().flat_map(|(name, states)| {
+            states
+                .iter()
+                .map(move |global| self.export_record(name, ExportedState::Global(global.clone())))
+        });
+        owned.chain(global).collect()
+    }
+
+    /// Converts the contract history into export records.
+    ///
+    /// Lists each of the owned and global state elements ever defined by the contract operations,
+    /// including the state which was spent since, starting with genesis. The status of each
+    /// record is the effective status of the operation defining it, like in [`OwnedState`].
+    pub fn history_records(&self) -> Vec<ExportRecord<P::Seal>> {
+        let contract_id = self.contract_id();
+        let articles = self.articles();
+        let api = articles.default_api();
+        let types = articles.types();
+        let genesis_opid = articles.genesis_opid();
+        let genesis = articles.genesis().to_operation(contract_id);
+        let genesis_rels = self.op_seals(genesis_opid, genesis.destructible_out.len_u16());
+
+        let mut records = vec![];
+        for (opid, op, rels) in [(genesis_opid, genesis, genesis_rels)]
+            .into_iter()
+            .chain(self.operations())
+        {
+            let status = self.statuses().effective(opid);
+            let witness_id = self.best_op_witness(opid).map(|(wid, _)| wid);
+            for (no, cell) in op.destructible_out.iter().enumerate() {
+                let Ok(Some((name, data))) = api.convert_owned(cell.data, types) else {
+                    continue;
+                };
+                let seal = rels.defines.get(&(no as u16)).and_then(|seal| {
+                    seal.to_src()
+                        .or_else(|| witness_id.map(|wid| seal.resolve(wid)))
+                });
+                let addr = CellAddr::new(opid, no as u16);
+                let owned = OwnedState { addr, assignment: Assignment { seal, data }, status };
+                records.push(self.export_record(&name, ExportedState::Owned(owned)));
+            }
+            for (no, data) in op.immutable_out.iter().enumerate() {
+                let Ok(Some((name, data))) = api.convert_global(data, types) else {
+                    continue;
+                };
+                let addr = CellAddr::new(opid, no as u16);
+                let global = ImmutableState { addr, data, status };
+                records.push(self.export_record(&name, ExportedState::Global(global)));
+            }
+        }
+        records
+    }
+}
+
+impl<Sp, S, C> Contracts<Sp, S, C>
+where
+    Sp: Stockpile,
+    S: KeyedCollection<Key = CodexId, Value = Issuer>,
+    C: KeyedCollection<Key = ContractId, Value = Contract<Sp::Stock, Sp::Pile>>,
+{
+    /// Converts the wallet state into export records.
+    ///
+    /// See [`Contract::state_records`] for the details.
+    ///
+    /// # Panics
+    ///
+    /// If the state contains a contract id which is not known.
+    pub fn wallet_state_records(
+        &self,
+        state: &WalletState<<Sp::Pile as Pile>::Seal>,
+    ) -> Vec<ExportRecord<<Sp::Pile as Pile>::Seal>> {
+        let empty = || ContractState { immutable: bmap! {}, owned: bmap! {}, aggregated: bmap! {} };
+        let mut states = BTreeMap::<ContractId, ContractState<_>>::new();
+        for (name, owned) in &state.owned {
+            states
+                .entry(name.contract_id)
+                .or_insert_with(empty)
+                .owned
+                .insert(name.state_name.clone(), owned.clone());
+        }
+        for (name, immutable) in &state.immutable {
+            states
+                .entry(name.contract_id)
+                .or_insert_with(empty)
+                .immutable
+                .insert(name.state_name.clone(), immutable.clone());
+        }
+        states
+            .into_iter()
+            .flat_map(|(contract_id, state)| {
+                self.with_contract(contract_id, |contract| contract.state_records(&state), None)
+            })
+            .collect()
+    }
+
+    /// Converts the contract history into export records.
+    ///
+    /// See [`Contract::history_records`] for the details.
+    ///
+    /// # Panics
+    ///
+    /// If the contract id is not known.
+    pub fn contract_history_records(
+        &self,
+        contract_id: ContractId,
+    ) -> Vec<ExportRecord<<Sp::Pile as Pile>::Seal>> {
+        self.with_contract(contract_id, |contract| contract.history_records(), None)
+    }
+}
@@ -0,0 +1,221 @@
+// Standard Library for RGB smart contracts
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2019-2025 by Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+// Written in 2024-2025 by Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 LNP/BP Laboratories,
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2025 RGB Consortium, Switzerland.
+// Copyright (C) 2019-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+use alloc::string::{String, ToString};
+use core::fmt::Write;
+
+use hypersonic::{Articles, CellAddr, MethodName, Operation, Opid};
+
+use crate::WitnessStatus;
+
+/// Resolves the name of the method called by an operation using the default contract API.
+pub(crate) fn op_method(articles: &Articles, op: &Operation) -> Option<MethodName> {
+    articles
+        .default_api()
+        .verifiers
+        .iter()
+        .find(|(_, call_id)| **call_id == op.call_id)
+        .map(|(method, _)| method.clone())
+}
+
+/// Escapes the characters which have a special meaning in XML text and attribute values.
+fn xml_escape(text: impl ToString) -> String {
+    let mut escaped = String::new();
+    for c in text.to_string().chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Node of the contract operation graph.
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(rename_all = "camelCase"))]
+pub struct OpNode {
+    pub opid: Opid,
+    /// Name of the contract method which has created the operation, if known to the contract API.
+    pub method: Option<MethodName>,
+    /// The best status of the operation witnesses, if known.
+    pub status: Option<WitnessStatus>,
+    /// Whether the operation participates in the current contract state, if known.
+    pub valid: Option<bool>,
+}
+
+/// Kind of the relation between two operations in the contract operation graph.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display)]
+#[display(lowercase)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(rename_all = "camelCase"))]
+pub enum OpEdgeKind {
+    /// The operation spends the destructible state defined by the other operation.
+    Spent,
+    /// The operation reads the immutable (global) state defined by the other operation.
+    Read,
+}
+
+/// Edge of the contract operation graph, going from an operation defining a state cell to the
+/// operation which spends or reads it.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(rename_all = "camelCase"))]
+pub struct OpEdge {
+    pub cell: CellAddr,
+    pub to: Opid,
+    pub kind: OpEdgeKind,
+}
+
+impl OpEdge {
+    pub fn from(&self) -> Opid { self.cell.opid }
+}
+
+/// Directed acyclic graph of contract operations, which can be exported to Graphviz DOT or
+/// GraphML formats.
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(rename_all = "camelCase"))]
+pub struct OpGraph {
+    pub nodes: Vec<OpNode>,
+    pub edges: Vec<OpEdge>,
+}
+
+impl OpGraph {
+    /// Adds an operation to the graph, together with the edges to all the operations it spends
+    /// or reads the state from.
+    ///
+    /// The method name is resolved using the default API from the contract `articles`.
+    pub fn add_operation(
+        &mut self,
+        articles: &Articles,
+        opid: Opid,
+        op: &Operation,
+        status: Option<WitnessStatus>,
+        valid: Option<bool>,
+    ) {
+        let method = op_method(articles, op);
+        self.nodes.push(OpNode { opid, method, status, valid });
+        for input in &op.destructible_in {
+            self.edges
+                .push(OpEdge { cell: input.addr, to: opid, kind: OpEdgeKind::Spent });
+        }
+        for cell in &op.immutable_in {
+            self.edges
+                .push(OpEdge { cell: *cell, to: opid, kind: OpEdgeKind::Read });
+        }
+    }
+
+    /// Exports the graph in Graphviz DOT format.
+    ///
+    /// Invalid operations are drawn dashed; edges for the read state are dotted.
+    pub fn to_dot(&self) -> String {
+        let mut dot = s!("digraph operations {\n    node [shape=box];\n");
+        for node in &self.nodes {
+            let mut label = node.opid.to_string();
+            if let Some(method) = &node.method {
+                write!(label, "\\n{method}").ok();
+            }
+            if let Some(status) = node.status {
+                write!(label, "\\n{status}").ok();
+            }
+            let style = if node.valid == Some(false) { ", style=dashed" } else { "" };
+            writeln!(dot, "    \"{}\" [label=\"{label}\"{style}];", node.opid).ok();
+        }
+        for edge in &self.edges {
+            let style = match edge.kind {
+                OpEdgeKind::Spent => "",
+                OpEdgeKind::Read => ", style=dotted",
+            };
+            writeln!(
+                dot,
+                "    \"{}\" -> \"{}\" [label=\"{}\"{style}];",
+                edge.from(),
+                edge.to,
+                edge.cell.pos
+            )
+            .ok();
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Exports the graph in GraphML format.
+    ///
+    /// All the labels are XML-escaped.
+    pub fn to_graphml(&self) -> String {
+        let mut xml = s!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<graphml xmlns="http://graphml.graphdrawing.org/xmlns">
+  <key id="method" for="node" attr.name="method" attr.type="string"/>
+  <key id="status" for="node" attr.name="status" attr.type="string"/>
+  <key id="valid" for="node" attr.name="valid" attr.type="boolean"/>
+  <key id="kind" for="edge" attr.name="kind" attr.type="string"/>
+  <key id="cell" for="edge" attr.name="cell" attr.type="string"/>
+  <graph id="operations" edgedefault="directed">
+"#);
+        for node in &self.nodes {
+            writeln!(xml, "    <node id=\"{}\">", xml_escape(node.opid)).ok();
+            if let Some(method) = &node.method {
+                writeln!(xml, "      <data key=\"method\">{}</data>", xml_escape(method)).ok();
+            }
+            if let Some(status) = node.status {
+                writeln!(xml, "      <data key=\"status\">{}</data>", xml_escape(status)).ok();
+            }
+            if let Some(valid) = node.valid {
+                writeln!(xml, "      <data key=\"valid\">{valid}</data>").ok();
+            }
+            xml.push_str("    </node>\n");
+        }
+        for edge in &self.edges {
+            writeln!(
+                xml,
+                "    <edge source=\"{}\" target=\"{}\">\n      <data \
+                 key=\"kind\">{}</data>\n      <data key=\"cell\">{}</data>\n    </edge>",
+                xml_escape(edge.from()),
+                xml_escape(edge.to),
+                xml_escape(edge.kind),
+                xml_escape(edge.cell)
+            )
+            .ok();
+        }
+        xml.push_str("  </graph>\n</graphml>\n");
+        xml
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![cfg_attr(coverage_nightly, coverage(off))]
+
+    use super::*;
+
+    #[test]
+    fn xml_escaping() {
+        assert_eq!(xml_escape("transfer"), "transfer");
+        assert_eq!(
+            xml_escape(r#"<a href="x">'&'</a>"#),
+            "&lt;a href=&quot;x&quot;&gt;&apos;&amp;&apos;&lt;/a&gt;"
+        );
+    }
+}
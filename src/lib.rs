@@ -63,6 +63,14 @@ mod stockpile;
 mod contract;
 mod consignment;
 mod contracts;
+mod events;
+#[cfg(feature = "export")]
+mod export;
+mod graph;
+mod query;
+mod resolver;
+mod status;
+mod typed;
 pub mod popls;
 mod util;
 #[cfg(feature = "stl")]
@@ -72,16 +80,29 @@ pub mod stl;
 pub use bp::{Outpoint, Txid};
 pub use consignment::{parse_consignment, Consignment, MAX_CONSIGNMENT_OPS};
 pub use contract::{
-    Assignment, ConsumeError, Contract, ContractState, CreateParams, EitherSeal, ImmutableState,
-    OwnedState,
+    AggregatedChange, Assignment, ConsumeError, Contract, ContractState, CreateParams, EitherSeal,
+    GlobalStateEntry, ImmutableState, OwnedState, ProvenanceStep, StateDiff,
 };
 #[cfg(feature = "binfile")]
 pub use contracts::CONSIGN_MAGIC_NUMBER;
 pub use contracts::{
-    ContractStateName, Contracts, IssuerError, SyncError, WalletState, CONSIGN_VERSION,
+    ContractStateName, Contracts, IssuerError, PromoteError, SyncError, WalletState,
+    CONSIGN_VERSION,
 };
+pub use events::ContractEvent;
+#[cfg(feature = "export")]
+pub use export::{write_csv, write_jsonl, ExportError, ExportRecord, ExportedState};
+pub use graph::{OpEdge, OpEdgeKind, OpGraph, OpNode};
 pub use hypersonic::*;
-pub use pile::{OpRels, Pile, Witness, WitnessStatus};
+pub use pile::{
+    BlockInfo, Confirmation, ConfirmationDepth, ExpiryPolicy, OpRels, Pile, SealIndex, Witness,
+    WitnessDirection, WitnessInfo, WitnessSeen, WitnessStatus,
+};
+#[cfg(feature = "bitcoin")]
+pub use query::SealOutpoint;
+pub use query::{OpCursor, OpPage, OpQuery, OwnedStateQuery, OwnedStateRef};
+pub use resolver::WitnessResolver;
 pub use rgb::*;
-pub use stockpile::Stockpile;
+pub use stockpile::{Stockpile, SyncCheckpoint};
+pub use typed::TypedStateError;
 pub use util::{ContractRef, InvalidContractRef};
@@ -25,11 +25,15 @@
 use alloc::collections::BTreeSet;
 use core::error::Error as StdError;
 use core::fmt::Debug;
+use core::hash::Hash;
+use core::iter;
 use core::marker::PhantomData;
 use core::num::NonZeroU64;
 use std::collections::HashSet;
 
 use amplify::confinement::SmallOrdMap;
+use amplify::Bytes32;
+use chrono::{DateTime, Utc};
 use hypersonic::Opid;
 use rgb::RgbSeal;
 
@@ -114,6 +118,132 @@ impl WitnessStatus {
             other
         }
     }
+
+    /// Projects the status onto the state of the blockchain at a given `height`.
+    ///
+    /// Witnesses mined above the height, as well as tentative and offchain witnesses, were not
+    /// known to the blockchain at that height and thus are reported as [`Self::Archived`].
+    pub fn at_height(self, height: u64) -> Self {
+        match self {
+            Self::Genesis => Self::Genesis,
+            Self::Mined(h) if h.get() <= height => self,
+            _ => Self::Archived,
+        }
+    }
+}
+
+/// Classification of a state by the confirmation depth of the witnesses in its history (see
+/// [`ConfirmationDepth::classify`]).
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Display)]
+#[display(lowercase)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(rename_all = "camelCase"))]
+pub enum Confirmation {
+    /// Genesis state, or the state mined at least as deep as the required number of
+    /// confirmations.
+    Final,
+
+    /// Mined state which doesn't have the required number of confirmations yet.
+    Confirming,
+
+    /// State which history includes tentative witnesses, not yet mined.
+    Unconfirmed,
+
+    /// State which history includes offchain witnesses.
+    Offchain,
+
+    /// State which history includes archived witnesses.
+    Archived,
+}
+
+impl Confirmation {
+    pub fn is_final(self) -> bool { self == Self::Final }
+}
+
+/// Current blockchain tip and the minimal number of confirmations required for the state to be
+/// considered final.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(rename_all = "camelCase"))]
+pub struct ConfirmationDepth {
+    pub tip_height: u64,
+    pub min_confirmations: u32,
+}
+
+impl ConfirmationDepth {
+    pub fn new(tip_height: u64, min_confirmations: u32) -> Self {
+        Self { tip_height, min_confirmations }
+    }
+
+    /// Number of confirmations for a witness mined at a given `height`, including the block
+    /// mining the witness itself.
+    pub fn confirmations(self, height: NonZeroU64) -> u64 {
+        self.tip_height
+            .saturating_sub(height.get())
+            .saturating_add(1)
+    }
+
+    pub fn classify(self, status: WitnessStatus) -> Confirmation {
+        match status {
+            WitnessStatus::Genesis => Confirmation::Final,
+            WitnessStatus::Mined(height)
+                if self.confirmations(height) >= self.min_confirmations as u64 =>
+            {
+                Confirmation::Final
+            }
+            WitnessStatus::Mined(_) => Confirmation::Confirming,
+            WitnessStatus::Offchain => Confirmation::Offchain,
+            WitnessStatus::Tentative => Confirmation::Unconfirmed,
+            WitnessStatus::Archived => Confirmation::Archived,
+        }
+    }
+}
+
+/// Policy for archiving stale tentative witnesses, which are not seen in the mempool or in the
+/// blockchain for a while; the operations depending on them get rolled back.
+///
+/// The default policy never expires witnesses.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(rename_all = "camelCase"))]
+pub struct ExpiryPolicy {
+    /// Number of blocks after which an unseen tentative witness expires.
+    pub blocks: Option<u64>,
+    /// Number of seconds after which an unseen tentative witness expires.
+    pub seconds: Option<u64>,
+    /// Direction of the witnesses the policy applies to; `None` applies it to all witnesses.
+    pub direction: Option<WitnessDirection>,
+}
+
+impl ExpiryPolicy {
+    pub fn after_blocks(blocks: u64) -> Self { Self { blocks: Some(blocks), ..default!() } }
+
+    pub fn after_seconds(seconds: u64) -> Self { Self { seconds: Some(seconds), ..default!() } }
+
+    /// Restricts the policy to the witnesses of the given direction.
+    pub fn only(mut self, direction: WitnessDirection) -> Self {
+        self.direction = Some(direction);
+        self
+    }
+
+    /// Checks whether the policy never expires witnesses, which is the case for the default policy.
+    pub fn never_expires(&self) -> bool { self.blocks.is_none() && self.seconds.is_none() }
+
+    /// Checks whether a tentative witness has expired at the given blockchain `height` and
+    /// `timestamp`.
+    pub fn is_expired(&self, seen: WitnessSeen, height: u64, timestamp: i64) -> bool {
+        if self
+            .direction
+            .is_some_and(|direction| direction != seen.direction)
+        {
+            return false;
+        }
+        let Some((seen_height, seen_timestamp)) = seen.last_seen else {
+            return false;
+        };
+        self.blocks
+            .is_some_and(|blocks| height.saturating_sub(seen_height) >= blocks)
+            || self.seconds.is_some_and(|seconds| {
+                timestamp.saturating_sub(seen_timestamp) >= seconds.min(i64::MAX as u64) as i64
+            })
+    }
 }
 
 impl From<[u8; 8]> for WitnessStatus {
@@ -138,6 +268,133 @@ impl From<WitnessStatus> for [u8; 8] {
     }
 }
 
+/// Details of the block mining a witness.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(rename_all = "camelCase"))]
+pub struct BlockInfo {
+    /// Block hash, used to detect re-orgs replacing the block at the same height.
+    pub hash: Bytes32,
+    /// Block timestamp, as the number of seconds since the Unix epoch.
+    pub timestamp: i64,
+    /// Position of the witness transaction in the block, if known.
+    pub tx_pos: Option<u32>,
+}
+
+impl BlockInfo {
+    pub fn new(hash: Bytes32, timestamp: i64) -> Self { Self { hash, timestamp, tx_pos: None } }
+
+    pub fn with_tx_pos(mut self, tx_pos: u32) -> Self {
+        self.tx_pos = Some(tx_pos);
+        self
+    }
+
+    /// Block timestamp as a UTC date and time; `None` if the timestamp is out of the supported
+    /// range.
+    pub fn time(&self) -> Option<DateTime<Utc>> { DateTime::from_timestamp(self.timestamp, 0) }
+}
+
+impl From<[u8; 44]> for BlockInfo {
+    fn from(value: [u8; 44]) -> Self {
+        let hash = Bytes32::from_slice_checked(&value[..32]);
+        let timestamp = i64::from_be_bytes(value[32..40].try_into().expect("fixed length"));
+        let tx_pos = u32::from_be_bytes(value[40..].try_into().expect("fixed length"));
+        Self {
+            hash,
+            timestamp,
+            tx_pos: (tx_pos != u32::MAX).then_some(tx_pos),
+        }
+    }
+}
+
+impl From<BlockInfo> for [u8; 44] {
+    fn from(value: BlockInfo) -> Self {
+        let mut bytes = [0u8; 44];
+        bytes[..32].copy_from_slice(value.hash.as_slice());
+        bytes[32..40].copy_from_slice(&value.timestamp.to_be_bytes());
+        bytes[40..].copy_from_slice(&value.tx_pos.unwrap_or(u32::MAX).to_be_bytes());
+        bytes
+    }
+}
+
+/// Witness status reported by a resolver, together with the details of the block mining the
+/// witness, if known.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(rename_all = "camelCase"))]
+pub struct WitnessInfo {
+    pub status: WitnessStatus,
+    /// Details of the block mining the witness; ignored unless the status is
+    /// [`WitnessStatus::Mined`].
+    pub block: Option<BlockInfo>,
+}
+
+impl From<WitnessStatus> for WitnessInfo {
+    fn from(status: WitnessStatus) -> Self { Self { status, block: None } }
+}
+
+impl WitnessInfo {
+    /// Constructs information for a witness mined at the `height` in a block with the given
+    /// details.
+    pub fn mined(height: NonZeroU64, block: BlockInfo) -> Self {
+        Self { status: WitnessStatus::Mined(height), block: Some(block) }
+    }
+}
+
+/// Direction of a witness from the point of view of the local wallet.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display)]
+#[display(lowercase)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(rename_all = "camelCase"))]
+pub enum WitnessDirection {
+    /// Witness included by the local wallet, like the one of a transfer made by it.
+    Outgoing,
+    /// Witness received with a consignment.
+    Incoming,
+}
+
+/// Tracking details of a witness, used to expire stale tentative witnesses (see
+/// [`ExpiryPolicy`]).
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(rename_all = "camelCase"))]
+pub struct WitnessSeen {
+    pub direction: WitnessDirection,
+    /// Blockchain height and time (as the number of seconds since the Unix epoch) of the last
+    /// sync at which the witness was seen in the mempool or in the blockchain, or at which its
+    /// tracking has started; `None` if the witness was not synced yet.
+    pub last_seen: Option<(u64, i64)>,
+}
+
+impl WitnessSeen {
+    pub fn new(direction: WitnessDirection) -> Self { Self { direction, last_seen: None } }
+}
+
+impl From<[u8; 17]> for WitnessSeen {
+    fn from(value: [u8; 17]) -> Self {
+        let direction = match value[0] {
+            0 => WitnessDirection::Outgoing,
+            _ => WitnessDirection::Incoming,
+        };
+        let height = u64::from_be_bytes(value[1..9].try_into().expect("fixed length"));
+        let timestamp = i64::from_be_bytes(value[9..].try_into().expect("fixed length"));
+        Self {
+            direction,
+            last_seen: (height != u64::MAX).then_some((height, timestamp)),
+        }
+    }
+}
+
+impl From<WitnessSeen> for [u8; 17] {
+    fn from(value: WitnessSeen) -> Self {
+        let mut bytes = [0u8; 17];
+        bytes[0] = match value.direction {
+            WitnessDirection::Outgoing => 0,
+            WitnessDirection::Incoming => 1,
+        };
+        let (height, timestamp) = value.last_seen.unwrap_or((u64::MAX, i64::MIN));
+        bytes[1..9].copy_from_slice(&height.to_be_bytes());
+        bytes[9..].copy_from_slice(&timestamp.to_be_bytes());
+        bytes
+    }
+}
+
 #[derive(Clone, Debug)]
 #[cfg_attr(feature = "serde", derive(Serialize))]
 pub struct Witness<Seal: RgbSeal> {
@@ -162,6 +419,33 @@ pub struct OpRels<Seal: RgbSeal> {
     pub _phantom: PhantomData<Seal>,
 }
 
+/// Single-use seals which can be looked up in a [`Pile`] by their primary component.
+///
+/// The primary component is the part of the seal which is closed by the witness; for bitcoin
+/// seals it is a transaction outpoint. Multiple seals may share the same primary component.
+pub trait SealIndex: RgbSeal {
+    /// Primary component of the seal.
+    type Primary: Copy + Eq + Hash + Debug;
+
+    /// Returns the primary component of the seal.
+    fn primary(&self) -> Self::Primary;
+
+    /// Returns the key under which the seals with the given primary component are indexed.
+    ///
+    /// Different primary components are allowed to share the same key, thus lookups by the key
+    /// must be filtered against the actual primary component.
+    fn index_key(primary: Self::Primary) -> [u8; 32];
+}
+
+#[cfg(feature = "bitcoin")]
+impl SealIndex for bp::seals::TxoSeal {
+    type Primary = bp::Outpoint;
+
+    fn primary(&self) -> Self::Primary { self.primary }
+
+    fn index_key(primary: Self::Primary) -> [u8; 32] { primary.txid.into() }
+}
+
 /// Persistent storage for contract witness and single-use seal definition data.
 pub trait Pile {
     /// Type of RGB seal used in the contract.
@@ -235,6 +519,24 @@ pub trait Pile {
 
     fn op_relations(&self, opid: Opid, up_to: u16) -> OpRels<Self::Seal>;
 
+    /// Returns addresses of all known cells which are assigned to seals with the given primary
+    /// component (for bitcoin, a transaction outpoint).
+    ///
+    /// Includes cells which are already spent, as well as cells defined by the operations which
+    /// witnesses are archived; it is up to the caller to filter them against the contract state.
+    ///
+    /// Returns `None` if the pile doesn't index the seals, which is the default; the callers then
+    /// have to scan the whole contract state.
+    fn cells_by_primary(
+        &self,
+        _primary: <Self::Seal as SealIndex>::Primary,
+    ) -> Option<impl Iterator<Item = CellAddr>>
+    where
+        Self::Seal: SealIndex,
+    {
+        None::<iter::Empty<CellAddr>>
+    }
+
     /// Adds operation id and witness components, registers witness as `Archived`.
     ///
     /// If the anchor (client-side witness) is already present, MUST update the anchor.
@@ -247,12 +549,31 @@ pub trait Pile {
         status: WitnessStatus,
     );
 
+    /// Adds seal definitions for the operation outputs.
+    ///
+    /// Seals which do not depend on the witness are added to the primary component index (see
+    /// [`Self::cells_by_primary`]) right away, as well as the rest of the seals resolved with the
+    /// witnesses already known for the operation (which is the case for the consignments, where
+    /// witnesses come first); the seals of the witnesses added later get indexed with
+    /// [`Self::index_witness_seals`].
     fn add_seals(
         &mut self,
         opid: Opid,
         seals: SmallOrdMap<u16, <Self::Seal as RgbSeal>::Definition>,
     );
 
+    /// Adds seals of the operation outputs (up to `up_to` output number), which are resolved with
+    /// the witness `wid`, to the primary component index (see [`Self::cells_by_primary`]).
+    ///
+    /// Does nothing by default, for the piles which don't index the seals.
+    fn index_witness_seals(
+        &mut self,
+        _opid: Opid,
+        _up_to: u16,
+        _wid: <Self::Seal as RgbSeal>::WitnessId,
+    ) {
+    }
+
     /// # Panics
     ///
     /// If the witness is not known
@@ -262,6 +583,48 @@ pub trait Pile {
         status: WitnessStatus,
     );
 
+    /// Returns the details of the block mining the witness, if the witness is mined and the
+    /// details were provided when its status was updated.
+    ///
+    /// Returns `None` by default, for the piles which don't store the block details; the re-orgs
+    /// of the deeply mined witnesses are not detected then.
+    fn witness_block(&self, _wid: <Self::Seal as RgbSeal>::WitnessId) -> Option<BlockInfo> { None }
+
+    /// Updates the details of the block mining the witness; `None` removes the details.
+    ///
+    /// Does nothing by default, for the piles which don't store the block details.
+    ///
+    /// # Panics
+    ///
+    /// If the witness is not known
+    fn update_witness_block(
+        &mut self,
+        _wid: <Self::Seal as RgbSeal>::WitnessId,
+        _block: Option<BlockInfo>,
+    ) {
+    }
+
+    /// Returns the tracking details of the witness; `None` for unknown witnesses and for the
+    /// witnesses added before the tracking was introduced.
+    ///
+    /// Returns `None` by default, for the piles which don't track the witnesses; the tentative
+    /// witnesses never expire then.
+    fn witness_seen(&self, _wid: <Self::Seal as RgbSeal>::WitnessId) -> Option<WitnessSeen> { None }
+
+    /// Updates the tracking details of the witness.
+    ///
+    /// Does nothing by default, for the piles which don't track the witnesses.
+    ///
+    /// # Panics
+    ///
+    /// If the witness is not known
+    fn update_witness_seen(
+        &mut self,
+        _wid: <Self::Seal as RgbSeal>::WitnessId,
+        _seen: WitnessSeen,
+    ) {
+    }
+
     /// Commits information about all updated witness statuses ("mine" structure) to the
     /// persistence as a new database transaction.
     ///
@@ -290,6 +653,51 @@ mod tests {
         ]);
     }
 
+    #[test]
+    fn block_info_bytes() {
+        let block = BlockInfo::new(Bytes32::from_byte_array([0xAB; 32]), 1_231_006_505);
+        assert_eq!(BlockInfo::from(<[u8; 44]>::from(block)), block);
+        let block = block.with_tx_pos(7);
+        assert_eq!(BlockInfo::from(<[u8; 44]>::from(block)), block);
+        assert_eq!(block.time().unwrap().to_rfc3339(), "2009-01-03T18:15:05+00:00");
+    }
+
+    #[test]
+    fn witness_seen_bytes() {
+        let seen = WitnessSeen::new(WitnessDirection::Incoming);
+        assert_eq!(WitnessSeen::from(<[u8; 17]>::from(seen)), seen);
+        let seen = WitnessSeen {
+            direction: WitnessDirection::Outgoing,
+            last_seen: Some((840_000, 1_713_571_767)),
+        };
+        assert_eq!(WitnessSeen::from(<[u8; 17]>::from(seen)), seen);
+    }
+
+    #[test]
+    fn expiry_policy() {
+        let seen = WitnessSeen {
+            direction: WitnessDirection::Outgoing,
+            last_seen: Some((100, 1_000_000)),
+        };
+        assert!(!ExpiryPolicy::default().is_expired(seen, u64::MAX, i64::MAX));
+        assert!(!ExpiryPolicy::after_blocks(6).is_expired(seen, 105, 1_000_000));
+        assert!(ExpiryPolicy::after_blocks(6).is_expired(seen, 106, 1_000_000));
+        assert!(!ExpiryPolicy::after_seconds(3600).is_expired(seen, 100, 1_003_599));
+        assert!(ExpiryPolicy::after_seconds(3600).is_expired(seen, 100, 1_003_600));
+        let policy = ExpiryPolicy { seconds: Some(3600), ..ExpiryPolicy::after_blocks(6) };
+        assert!(policy.is_expired(seen, 106, 1_000_000));
+        assert!(policy.is_expired(seen, 100, 1_003_600));
+        assert!(!ExpiryPolicy::after_blocks(6)
+            .only(WitnessDirection::Incoming)
+            .is_expired(seen, 106, 1_000_000));
+        assert!(ExpiryPolicy::after_blocks(6)
+            .only(WitnessDirection::Outgoing)
+            .is_expired(seen, 106, 1_000_000));
+        // Witnesses which were never synced do not expire
+        let seen = WitnessSeen::new(WitnessDirection::Outgoing);
+        assert!(!ExpiryPolicy::after_blocks(0).is_expired(seen, 106, 1_000_000));
+    }
+
     #[test]
     fn witness_status_ordering() {
         assert!(WitnessStatus::Genesis.is_better(WitnessStatus::Mined(NonZeroU64::new(1).unwrap())));
@@ -302,4 +710,29 @@ mod tests {
         assert!(WitnessStatus::Offchain.is_better(WitnessStatus::Archived));
         assert!(WitnessStatus::Archived.is_worse(WitnessStatus::Genesis));
     }
+
+    #[test]
+    fn witness_status_at_height() {
+        let mined = WitnessStatus::Mined(NonZeroU64::new(100).unwrap());
+        assert_eq!(WitnessStatus::Genesis.at_height(0), WitnessStatus::Genesis);
+        assert_eq!(mined.at_height(100), mined);
+        assert_eq!(mined.at_height(99), WitnessStatus::Archived);
+        assert_eq!(WitnessStatus::Tentative.at_height(u64::MAX), WitnessStatus::Archived);
+        assert_eq!(WitnessStatus::Offchain.at_height(u64::MAX), WitnessStatus::Archived);
+    }
+
+    #[test]
+    fn confirmation_depth() {
+        let depth = ConfirmationDepth::new(105, 6);
+        let mined = |height| WitnessStatus::Mined(NonZeroU64::new(height).unwrap());
+        assert_eq!(depth.classify(WitnessStatus::Genesis), Confirmation::Final);
+        assert_eq!(depth.classify(mined(100)), Confirmation::Final);
+        assert_eq!(depth.classify(mined(101)), Confirmation::Confirming);
+        // Witness from a block above the known tip
+        assert_eq!(depth.classify(mined(200)), Confirmation::Confirming);
+        assert_eq!(depth.classify(WitnessStatus::Tentative), Confirmation::Unconfirmed);
+        assert_eq!(depth.classify(WitnessStatus::Offchain), Confirmation::Offchain);
+        assert_eq!(depth.classify(WitnessStatus::Archived), Confirmation::Archived);
+        assert_eq!(ConfirmationDepth::new(0, 0).classify(mined(1)), Confirmation::Final);
+    }
 }
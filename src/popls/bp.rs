@@ -28,7 +28,9 @@
 use alloc::collections::btree_map::Entry;
 use alloc::collections::{btree_set, BTreeMap, BTreeSet};
 use alloc::vec;
-use core::mem;
+use core::cmp::Ordering;
+use core::num::NonZeroU64;
+use core::{iter, mem};
 use std::collections::HashMap;
 
 use amplify::confinement::{
@@ -43,9 +45,10 @@ use commit_verify::mpc::ProtocolId;
 use commit_verify::{mpc, Digest, DigestExt, Sha256, StrictHash};
 use hypersonic::{
     AcceptError, AuthToken, CallParams, CellAddr, ContractId, CoreParams, DataCell, MethodName,
-    NamedState, Operation, Satisfaction, StateAtom, StateCalc, StateCalcError, StateName,
+    NamedState, Operation, Opid, Satisfaction, StateAtom, StateCalc, StateCalcError, StateName,
     StateUnknown, Stock,
 };
+use indexmap::IndexMap;
 use invoice::bp::{Address, WitnessOut};
 use invoice::{RgbBeneficiary, RgbInvoice};
 use rgb::RgbSealDef;
@@ -55,9 +58,9 @@ use strict_types::StrictVal;
 
 use crate::contracts::SyncError;
 use crate::{
-    Assignment, CodexId, Consensus, ConsumeError, Contract, ContractState, Contracts, CreateParams,
-    EitherSeal, Identity, Issuer, IssuerError, OwnedState, Pile, SigBlob, Stockpile, WalletState,
-    WitnessStatus,
+    Assignment, CodexId, ConfirmationDepth, Consensus, ConsumeError, Contract, ContractState,
+    Contracts, CreateParams, EitherSeal, Identity, Issuer, IssuerError, OwnedState, Pile, SigBlob,
+    Stockpile, WalletState, WitnessResolver, WitnessStatus,
 };
 
 /// Trait abstracting a specific implementation of a bitcoin wallet.
@@ -83,13 +86,45 @@ pub trait WalletProvider {
     fn next_nonce(&mut self) -> u64;
 
     #[cfg(not(feature = "async"))]
-    /// Returns a closure which can retrieve a witness status of an arbitrary transaction id
-    /// (including the ones that are not related to the wallet).
-    fn txid_resolver(&self) -> impl Fn(Txid) -> Result<WitnessStatus, Self::Error>;
+    /// Returns a resolver which can retrieve witness statuses of arbitrary transaction ids
+    /// (including the ones that are not related to the wallet), together with the details of the
+    /// blocks mining the transactions, if known.
+    fn txid_resolver(&self) -> impl WitnessResolver<Txid, Error = Self::Error>;
     #[cfg(feature = "async")]
-    /// Returns a closure which can retrieve a witness status of an arbitrary transaction id
-    /// (including the ones that are not related to the wallet).
-    fn txid_resolver_async(&self) -> impl AsyncFn(Txid) -> Result<WitnessStatus, Self::Error>;
+    /// Returns a resolver which can retrieve witness statuses of arbitrary transaction ids
+    /// (including the ones that are not related to the wallet), together with the details of the
+    /// blocks mining the transactions, if known.
+    fn txid_resolver_async(&self) -> impl WitnessResolver<Txid, Error = Self::Error>;
+
+    #[cfg(not(feature = "async"))]
+    /// Returns a closure which can retrieve the hash of the block at a given height in the best
+    /// chain; `None` if the height is above the chain tip.
+    fn block_resolver(&self) -> impl Fn(u64) -> Result<Option<Bytes32>, Self::Error>;
+    #[cfg(feature = "async")]
+    /// Returns a closure which can retrieve the hash of the block at a given height in the best
+    /// chain; `None` if the height is above the chain tip.
+    fn block_resolver_async(&self) -> impl AsyncFn(u64) -> Result<Option<Bytes32>, Self::Error>;
+
+    #[cfg(not(feature = "async"))]
+    /// Returns a closure which can retrieve the id of the transaction spending a given output,
+    /// if the output is spent by a mined or a mempool transaction.
+    ///
+    /// Used to detect the witnesses conflicting with other transactions; the default
+    /// implementation doesn't detect any conflicts.
+    fn spender_resolver(&self) -> impl Fn(Outpoint) -> Result<Option<Txid>, Self::Error> {
+        |_| Ok(None)
+    }
+    #[cfg(feature = "async")]
+    /// Returns a closure which can retrieve the id of the transaction spending a given output,
+    /// if the output is spent by a mined or a mempool transaction.
+    ///
+    /// Used to detect the witnesses conflicting with other transactions; the default
+    /// implementation doesn't detect any conflicts.
+    fn spender_resolver_async(
+        &self,
+    ) -> impl AsyncFn(Outpoint) -> Result<Option<Txid>, Self::Error> {
+        async |_| Ok(None)
+    }
 
     #[cfg(not(feature = "async"))]
     /// Returns the height of the last known block.
@@ -406,6 +441,108 @@ impl PrefabBundle {
     }
 }
 
+/// Direction of a transfer from the wallet point of view.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Display)]
+#[display(lowercase)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(rename_all = "camelCase"))]
+pub enum TransferDirection {
+    /// Contract genesis assigning state to the wallet.
+    Issue,
+
+    /// State received by the wallet from other parties.
+    Incoming,
+
+    /// State sent by the wallet to other parties.
+    Outgoing,
+
+    /// State re-assigned between the wallet's own outputs, like in blank operations.
+    Internal,
+}
+
+/// Entry in the wallet transfer history, describing how a single operation has changed the state
+/// of a specific kind owned by the wallet.
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(rename_all = "camelCase"))]
+pub struct TransferEntry {
+    pub contract_id: ContractId,
+    pub opid: Opid,
+    pub method: Option<MethodName>,
+    pub state_name: StateName,
+    pub direction: TransferDirection,
+    /// The witness transaction with the best status; `None` for genesis.
+    pub witness_id: Option<Txid>,
+    pub status: WitnessStatus,
+    /// State received by the wallet; empty for outgoing and internal transfers.
+    pub received: Vec<StrictVal>,
+    /// State sent by the wallet, not including change; empty for incoming and internal transfers.
+    pub sent: Vec<StrictVal>,
+    /// Seals of the state sent to other parties: either an auth token from an invoice, or a
+    /// witness transaction output.
+    pub counterparties: Vec<EitherSeal<Outpoint>>,
+}
+
+impl TransferEntry {
+    /// Height of the block mining the witness transaction, if it is mined.
+    pub fn height(&self) -> Option<NonZeroU64> {
+        match self.status {
+            WitnessStatus::Mined(height) => Some(height),
+            _ => None,
+        }
+    }
+}
+
+/// Filters and pagination for the wallet transfer history (see [`RgbWallet::history`]).
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(rename_all = "camelCase"))]
+pub struct HistoryQuery {
+    pub contract_id: Option<ContractId>,
+    pub state_name: Option<StateName>,
+    pub direction: Option<TransferDirection>,
+    /// Whether to include operations which witnesses are all archived.
+    pub include_archived: bool,
+    /// Number of entries to skip.
+    pub offset: usize,
+    /// Maximum number of entries to return.
+    pub limit: Option<usize>,
+}
+
+/// Balance of a state owned by the wallet, broken down by the status of the witnesses in the
+/// state history (see [`RgbWallet::balance`]).
+///
+/// Each of the fields contains the state aggregated with the contract API state arithmetics; for
+/// fungible state this is either a single value or an empty vector for a zero balance.
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(rename_all = "camelCase"))]
+pub struct Balance {
+    /// State assigned in genesis and never spent since.
+    pub genesis: Vec<StrictVal>,
+    /// State which history is mined at least as deep as the required number of confirmations.
+    pub confirmed: Vec<StrictVal>,
+    /// State which history is mined, but not deep enough.
+    pub unconfirmed: Vec<StrictVal>,
+    /// State which history includes offchain witnesses.
+    pub offchain: Vec<StrictVal>,
+    /// State which history includes tentative (not yet mined) witnesses.
+    pub tentative: Vec<StrictVal>,
+    /// Total of all the above.
+    pub total: Vec<StrictVal>,
+}
+
+/// Conflict of a tentative witness transaction with another transaction spending the same output,
+/// like a double-spend or an RBF replacement.
+///
+/// Conflicting witnesses are archived by [`RgbWallet::update`], rolling back their operations.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(rename_all = "camelCase"))]
+pub struct WitnessConflict {
+    /// The archived witness transaction.
+    pub witness_id: Txid,
+    /// The output spent by both transactions.
+    pub prevout: Outpoint,
+    /// The transaction which has won the output.
+    pub spender: Txid,
+}
+
 /// RGB wallet contains a bunch of RGB contracts, which are held by a single owner (a wallet);
 /// such that when a new operation under any of the contracts happens, it may affect other contracts
 /// sharing the same UTXOs.
@@ -449,7 +586,18 @@ where
         ContractId,
         MultiError<IssuerError, <Sp::Stock as Stock>::Error, <Sp::Pile as Pile>::Error>,
     > {
-        self.contracts.issue(params.transform(self.noise_engine()))
+        let params = params.transform(self.noise_engine());
+        // Remember the genesis seals assigned to the wallet UTXOs, so the state stays recognized
+        // as ours once spent
+        for assignment in &params.owned {
+            if let EitherSeal::Alt(seal) = assignment.state.seal {
+                if matches!(seal.primary, WOutpoint::Extern(outpoint) if self.wallet.has_utxo(outpoint))
+                {
+                    self.wallet.register_seal(seal);
+                }
+            }
+        }
+        self.contracts.issue(params)
     }
 
     pub fn auth_token(&mut self, nonce: Option<u64>) -> Option<AuthToken> {
@@ -477,17 +625,8 @@ where
 
     pub fn wallet_contract_state(&self, contract_id: ContractId) -> ContractState<Outpoint> {
         self.contracts
-            .contract_state(contract_id)
-            .clone()
-            .filter_map(
-                |seal| {
-                    if self.wallet.has_utxo(seal.primary) {
-                        Some(seal.primary)
-                    } else {
-                        None
-                    }
-                },
-            )
+            .contract_state_by_primary(contract_id, self.wallet.utxos())
+            .map(|seal| seal.primary)
     }
 
     pub fn contract_state_full(
@@ -497,6 +636,149 @@ where
         self.contracts.contract_state(contract_id)
     }
 
+    /// Get the history of transfers affecting the state owned by the wallet.
+    ///
+    /// Each operation is classified from the wallet point of view, producing an entry for each
+    /// state name it affects. Entries are grouped by contract; within a contract they are ordered
+    /// by their witness status, from genesis to mined (in the order of block heights), offchain,
+    /// tentative and archived. The histories of the contracts are computed one by one, stopping
+    /// once the `limit` of the query is reached.
+    ///
+    /// # Nota bene
+    ///
+    /// The state is considered owned by the wallet if its seal was created or received by the
+    /// wallet (i.e. is known to [`WalletProvider::resolve_seals`]), or if it is assigned to a
+    /// wallet UTXO. Thus, the state received on a witness output of other party's transaction
+    /// is recognized as owned only while the output remains unspent.
+    pub fn history(&self, query: &HistoryQuery) -> Result<Vec<TransferEntry>, StateCalcError> {
+        let contract_ids = match query.contract_id {
+            Some(contract_id) => vec![contract_id],
+            None => self.contracts.contract_ids().collect(),
+        };
+        let is_owned = |auth: AuthToken, outpoint: Option<Outpoint>| {
+            self.wallet.resolve_seals(iter::once(auth)).next().is_some()
+                || outpoint.is_some_and(|outpoint| self.wallet.has_utxo(outpoint))
+        };
+        let limit = query.limit.unwrap_or(usize::MAX);
+        let mut skip = query.offset;
+        let mut entries = vec![];
+        for contract_id in contract_ids {
+            if entries.len() >= limit {
+                break;
+            }
+            let mut history = self.contracts.with_contract(
+                contract_id,
+                |contract| contract_history(contract, is_owned),
+                None,
+            )?;
+            history.retain(|entry| {
+                (query.include_archived || entry.status.is_valid())
+                    && query
+                        .state_name
+                        .as_ref()
+                        .is_none_or(|name| name == &entry.state_name)
+                    && query
+                        .direction
+                        .is_none_or(|direction| direction == entry.direction)
+            });
+            history.sort_by(|a, b| {
+                if a.status.is_better(b.status) {
+                    Ordering::Less
+                } else if b.status.is_better(a.status) {
+                    Ordering::Greater
+                } else {
+                    Ordering::Equal
+                }
+            });
+            let skipped = skip.min(history.len());
+            skip -= skipped;
+            let take = limit - entries.len();
+            entries.extend(history.into_iter().skip(skipped).take(take));
+        }
+        Ok(entries)
+    }
+
+    #[cfg(not(feature = "async"))]
+    /// Get the balance of the state owned by the wallet under a given contract, broken down by
+    /// the witness status.
+    ///
+    /// Mined state is considered confirmed if the witness of the least deep operation in its
+    /// history has at least `min_confirmations` confirmations.
+    pub fn balance(
+        &self,
+        contract_id: ContractId,
+        state_name: impl Into<StateName>,
+        min_confirmations: u32,
+    ) -> Result<Balance, BalanceError<W::Error>> {
+        let last_height = self
+            .wallet
+            .last_block_height()
+            .map_err(BalanceError::Wallet)?;
+        self.compute_balance(contract_id, state_name.into(), last_height, min_confirmations)
+    }
+
+    #[cfg(feature = "async")]
+    /// Get the balance of the state owned by the wallet under a given contract, broken down by
+    /// the witness status.
+    ///
+    /// Mined state is considered confirmed if the witness of the least deep operation in its
+    /// history has at least `min_confirmations` confirmations.
+    pub async fn balance_async(
+        &self,
+        contract_id: ContractId,
+        state_name: impl Into<StateName>,
+        min_confirmations: u32,
+    ) -> Result<Balance, BalanceError<W::Error>> {
+        let last_height = self
+            .wallet
+            .last_block_height_async()
+            .await
+            .map_err(BalanceError::Wallet)?;
+        self.compute_balance(contract_id, state_name.into(), last_height, min_confirmations)
+    }
+
+    fn compute_balance(
+        &self,
+        contract_id: ContractId,
+        state_name: StateName,
+        last_height: u64,
+        min_confirmations: u32,
+    ) -> Result<Balance, BalanceError<W::Error>> {
+        let calc = self
+            .contracts
+            .contract_articles(contract_id)
+            .default_api()
+            .calculate(state_name.clone())?;
+        let [mut genesis, mut confirmed, mut unconfirmed, mut offchain, mut tentative, mut total] =
+            [(); 6].map(|_| calc.clone());
+        let depth = ConfirmationDepth::new(last_height, min_confirmations);
+        let state = self.wallet_contract_state(contract_id);
+        for owned in state.owned.get(&state_name).into_iter().flatten() {
+            let calc = match owned.status {
+                WitnessStatus::Genesis => &mut genesis,
+                WitnessStatus::Mined(height)
+                    if depth.confirmations(height) >= depth.min_confirmations as u64 =>
+                {
+                    &mut confirmed
+                }
+                WitnessStatus::Mined(_) => &mut unconfirmed,
+                WitnessStatus::Offchain => &mut offchain,
+                WitnessStatus::Tentative => &mut tentative,
+                WitnessStatus::Archived => continue,
+            };
+            calc.accumulate(&owned.assignment.data)?;
+            total.accumulate(&owned.assignment.data)?;
+        }
+        Ok(Balance {
+            genesis: genesis.diff()?,
+            confirmed: confirmed.diff()?,
+            unconfirmed: unconfirmed.diff()?,
+            offchain: offchain.diff()?,
+            tentative: tentative.diff()?,
+            total: total.diff()?,
+        })
+    }
+
     fn noise_engine(&self) -> Sha256 {
         let noise_seed = self.wallet.noise_seed();
         let mut noise_engine = Sha256::new();
@@ -505,10 +787,32 @@ where
     }
 
     pub fn fulfill(
+        &mut self,
+        invoice: &RgbInvoice<ContractId>,
+        coinselect: impl Coinselect,
+        giveaway: Option<Sats>,
+    ) -> Result<OpRequest<Option<WoutAssignment>>, FulfillError> {
+        self.fulfill_internal(invoice, coinselect, giveaway, None)
+    }
+
+    /// Fulfills the invoice using only the state which is final at a given confirmation `depth`
+    /// (see [`ConfirmationDepth::classify`]).
+    pub fn fulfill_confirmed(
+        &mut self,
+        invoice: &RgbInvoice<ContractId>,
+        coinselect: impl Coinselect,
+        giveaway: Option<Sats>,
+        depth: ConfirmationDepth,
+    ) -> Result<OpRequest<Option<WoutAssignment>>, FulfillError> {
+        self.fulfill_internal(invoice, coinselect, giveaway, Some(depth))
+    }
+
+    fn fulfill_internal(
         &mut self,
         invoice: &RgbInvoice<ContractId>,
         mut coinselect: impl Coinselect,
         giveaway: Option<Sats>,
+        depth: Option<ConfirmationDepth>,
     ) -> Result<OpRequest<Option<WoutAssignment>>, FulfillError> {
         let contract_id = invoice.scope;
 
@@ -527,7 +831,10 @@ where
         let value = invoice.data.as_ref().ok_or(FulfillError::ValueMissed)?;
 
         // Do coinselection
-        let state = self.wallet_contract_state(contract_id);
+        let mut state = self.wallet_contract_state(contract_id);
+        if let Some(depth) = depth {
+            state = state.finalized(depth);
+        }
         let state = state
             .owned
             .get(&state_name)
@@ -723,32 +1030,34 @@ where
             prefabs.insert(prefab);
         }
 
+        // Collecting the state of other contracts assigned to the closed outpoints
+        let mut affected = BTreeMap::<ContractId, Vec<(StateName, OwnedState<TxoSeal>)>>::new();
+        for outpoint in &outpoints {
+            for (contract_id, owned) in self.contracts.owned_by_primary(*outpoint) {
+                if contracts.contains(&contract_id) {
+                    continue;
+                }
+                affected.entry(contract_id).or_default().extend(
+                    owned.into_iter().flat_map(|(name, state)| {
+                        state.into_iter().map(move |s| (name.clone(), s))
+                    }),
+                );
+            }
+        }
+
         // Constructing blank operation requests
         let mut blank_requests = Vec::new();
         let root_noise_engine = self.noise_engine();
-        for contract_id in self.contracts.contract_ids() {
-            if contracts.contains(&contract_id) {
-                continue;
-            }
-            // We need to clone here not to conflict with mutable calls below
-            let owned = self.contracts.contract_state(contract_id).owned.clone();
+        for (contract_id, owned) in affected {
             let (using, prev): (Vec<_>, Vec<_>) = owned
-                .iter()
-                .flat_map(|(name, map)| map.iter().map(move |owned| (name, owned)))
-                .filter_map(|(name, owned)| {
+                .into_iter()
+                .map(|(name, owned)| {
                     let outpoint = owned.assignment.seal.primary;
-                    if !outpoints.contains(&outpoint) {
-                        return None;
-                    }
                     let prevout = UsedState { addr: owned.addr, outpoint, satisfaction: None };
-                    Some((prevout, (name.clone(), owned)))
+                    (prevout, (name, owned))
                 })
                 .unzip();
 
-            if using.is_empty() {
-                continue;
-            };
-
             let articles = self.contracts.contract_articles(contract_id);
             let api = articles.default_api();
             let mut calcs = BTreeMap::<StateName, StateCalc>::new();
@@ -803,6 +1112,28 @@ where
             prefabs.push(prefab);
         }
 
+        // Remember the seals assigned to the change, so the state stays recognized as ours once
+        // spent
+        if let Some(change) = change {
+            for prefab in &prefabs {
+                let operation = &prefab.operation;
+                let seals = self.contracts.with_contract(
+                    operation.contract_id,
+                    |contract| {
+                        contract
+                            .op_seals(operation.opid(), operation.destructible_out.len_u16())
+                            .defines
+                    },
+                    None,
+                );
+                for seal in seals.into_values() {
+                    if seal.primary == WOutpoint::Wout(change) {
+                        self.wallet.register_seal(seal);
+                    }
+                }
+            }
+        }
+
         Ok(PrefabBundle(
             SmallOrdSet::try_from(prefabs)
                 .map_err(|_| MultiError::A(BundleError::TooManyBlanks))?,
@@ -817,7 +1148,46 @@ where
         mpc: mpc::MerkleBlock,
         dbc: Option<TapretProof>,
         prevouts: &[Outpoint],
-    ) -> Result<(), IncludeError> {
+    ) -> Result<(), MultiError<IncludeError, <Sp::Pile as Pile>::Error>> {
+        let anchors = Self::anchors(bundle, mpc, dbc, prevouts).map_err(MultiError::A)?;
+        for (contract_id, opid, anchor) in anchors {
+            self.contracts
+                .include(contract_id, opid, witness, anchor)
+                .map_err(MultiError::B)?;
+        }
+        Ok(())
+    }
+
+    /// Include a prefab bundle with an offchain witness, like a commitment transaction of a
+    /// payment channel, creating the necessary anchors on the fly.
+    ///
+    /// Once the witness gets published, it has to be promoted with
+    /// [`Contracts::promote_witnesses`], unless the wallet update detects it in the mempool or in
+    /// the blockchain first.
+    pub fn include_offchain(
+        &mut self,
+        bundle: &PrefabBundle,
+        witness: &Tx,
+        mpc: mpc::MerkleBlock,
+        dbc: Option<TapretProof>,
+        prevouts: &[Outpoint],
+    ) -> Result<(), MultiError<IncludeError, <Sp::Pile as Pile>::Error>> {
+        let anchors = Self::anchors(bundle, mpc, dbc, prevouts).map_err(MultiError::A)?;
+        for (contract_id, opid, anchor) in anchors {
+            self.contracts
+                .include_offchain(contract_id, opid, witness, anchor)
+                .map_err(MultiError::B)?;
+        }
+        Ok(())
+    }
+
+    fn anchors(
+        bundle: &PrefabBundle,
+        mpc: mpc::MerkleBlock,
+        dbc: Option<TapretProof>,
+        prevouts: &[Outpoint],
+    ) -> Result<Vec<(ContractId, Opid, Anchor)>, IncludeError> {
+        let mut anchors = Vec::with_capacity(bundle.len());
         for prefab in bundle {
             let protocol_id = ProtocolId::from(prefab.operation.contract_id.to_byte_array());
             let opid = prefab.operation.opid();
@@ -836,10 +1206,77 @@ where
                 dbc_proof: dbc.clone(),
                 fallback_proof: default!(),
             };
+            anchors.push((prefab.operation.contract_id, opid, anchor));
+        }
+        Ok(anchors)
+    }
+
+    /// Include a fee-bumping replacement (RBF) of a tentative witness of a prefab bundle.
+    ///
+    /// The replacement transaction is constructed from the `replaced` witness by deducting the
+    /// additional `fee` from its `change` output. Since the inputs and the commitment are kept
+    /// intact, the anchors of the replaced witness are re-used for the replacement. Both witnesses
+    /// are kept for the bundle operations; once one of them gets mined, [`Self::update`] archives
+    /// the other one as conflicting (see [`WitnessConflict`]).
+    ///
+    /// Returns the replacement transaction with the input signatures removed, which inputs must be
+    /// signed once again before it gets broadcasted. Since the witness is identified by its txid,
+    /// the signing must not change it, i.e. all the transaction inputs must be segwit ones; signed
+    /// non-segwit inputs of the replaced witness result in [`RbfError::NonSegwitInput`].
+    pub fn bump_fee(
+        &mut self,
+        bundle: &PrefabBundle,
+        replaced: Txid,
+        change: Vout,
+        fee: Sats,
+    ) -> Result<Tx, MultiError<RbfError, <Sp::Pile as Pile>::Error>> {
+        let mut anchors = Vec::with_capacity(bundle.len());
+        let mut published = None;
+        for prefab in bundle {
+            let contract_id = prefab.operation.contract_id;
+            let opid = prefab.operation.opid();
+            let witness = self
+                .contracts
+                .with_contract(contract_id, |contract| contract.witness(replaced), Some(None))
+                .filter(|witness| witness.opids.contains(&opid))
+                .ok_or(MultiError::A(RbfError::UnknownWitness(replaced)))?;
+            if witness.status != WitnessStatus::Tentative {
+                return Err(MultiError::A(RbfError::NotTentative(replaced)));
+            }
+            published.get_or_insert(witness.published);
+            anchors.push((contract_id, opid, witness.client));
+        }
+        let mut tx = published.ok_or(MultiError::A(RbfError::UnknownWitness(replaced)))?;
+
+        // BIP-125 signalling
+        if tx
+            .inputs()
+            .all(|input| input.sequence.to_consensus_u32() >= 0xFFFF_FFFE)
+        {
+            return Err(MultiError::A(RbfError::NotReplaceable(replaced)));
+        }
+        let output = tx
+            .outputs
+            .get_mut(change.to_usize())
+            .ok_or(MultiError::A(RbfError::NoOutput(change)))?;
+        output.value = output
+            .value
+            .checked_sub(fee)
+            .ok_or(MultiError::A(RbfError::InsufficientValue(change, fee)))?;
+        for input in tx.inputs.iter_mut() {
+            if input.witness.is_empty() && !input.sig_script.is_empty() {
+                return Err(MultiError::A(RbfError::NonSegwitInput(input.prev_output)));
+            }
+            input.sig_script = none!();
+            input.witness = none!();
+        }
+
+        for (contract_id, opid, anchor) in anchors {
             self.contracts
-                .include(prefab.operation.contract_id, opid, witness, anchor);
+                .include(contract_id, opid, &tx, anchor)
+                .map_err(MultiError::B)?;
         }
-        Ok(())
+        Ok(tx)
     }
 
     /// Consume consignment.
@@ -895,7 +1332,10 @@ where
     pub fn update(
         &mut self,
         min_conformations: u32,
-    ) -> Result<(), MultiError<SyncError<W::Error>, <Sp::Stock as Stock>::Error>> {
+    ) -> Result<
+        Vec<WitnessConflict>,
+        MultiError<SyncError<W::Error>, <Sp::Stock as Stock>::Error, <Sp::Pile as Pile>::Error>,
+    > {
         self.wallet
             .update_utxos()
             .map_err(SyncError::Wallet)
@@ -905,9 +1345,34 @@ where
             .last_block_height()
             .map_err(SyncError::Wallet)
             .map_err(MultiError::from_a)?;
+        let tentative = self.tentative_witnesses();
         self.contracts
-            .update_witnesses(self.wallet.txid_resolver(), last_height, min_conformations)
+            .update_witnesses_with_blocks(
+                self.wallet.txid_resolver(),
+                self.wallet.block_resolver(),
+                last_height,
+                min_conformations,
+            )
             .map_err(MultiError::from_other_a)
+            .map_err(MultiError::with_third)?;
+
+        let spender_resolver = self.wallet.spender_resolver();
+        let mut conflicts = vec![];
+        for (witness_id, tx, contract_id) in self.unmined(tentative) {
+            for prevout in tx.inputs().map(|input| input.prev_output) {
+                let spender = spender_resolver(prevout)
+                    .map_err(SyncError::Status)
+                    .map_err(MultiError::from_a)?;
+                if let Some(spender) = spender.filter(|spender| *spender != witness_id) {
+                    if !self.is_pending_replacement(contract_id, witness_id, spender) {
+                        conflicts.push(WitnessConflict { witness_id, prevout, spender });
+                    }
+                    break;
+                }
+            }
+        }
+        drop(spender_resolver);
+        self.archive_conflicts(conflicts)
     }
 
     #[cfg(feature = "async")]
@@ -919,7 +1384,10 @@ where
     pub async fn update_async(
         &mut self,
         min_conformations: u32,
-    ) -> Result<(), MultiError<SyncError<W::Error>, <Sp::Stock as Stock>::Error>>
+    ) -> Result<
+        Vec<WitnessConflict>,
+        MultiError<SyncError<W::Error>, <Sp::Stock as Stock>::Error, <Sp::Pile as Pile>::Error>,
+    >
     where
         Sp::Stock: 'static,
         Sp::Pile: 'static,
@@ -935,17 +1403,236 @@ where
             .await
             .map_err(SyncError::Wallet)
             .map_err(MultiError::from_a)?;
+        let tentative = self.tentative_witnesses();
         self.contracts
-            .update_witnesses_async(
+            .update_witnesses_with_blocks_async(
                 self.wallet.txid_resolver_async(),
+                self.wallet.block_resolver_async(),
                 last_height,
                 min_conformations,
             )
             .await
             .map_err(MultiError::from_other_a)
+            .map_err(MultiError::with_third)?;
+
+        let spender_resolver = self.wallet.spender_resolver_async();
+        let mut conflicts = vec![];
+        for (witness_id, tx, contract_id) in self.unmined(tentative) {
+            for prevout in tx.inputs().map(|input| input.prev_output) {
+                let spender = spender_resolver(prevout)
+                    .await
+                    .map_err(SyncError::Status)
+                    .map_err(MultiError::from_a)?;
+                if let Some(spender) = spender.filter(|spender| *spender != witness_id) {
+                    if !self.is_pending_replacement(contract_id, witness_id, spender) {
+                        conflicts.push(WitnessConflict { witness_id, prevout, spender });
+                    }
+                    break;
+                }
+            }
+        }
+        drop(spender_resolver);
+        self.archive_conflicts(conflicts)
+    }
+
+    /// Collects the witnesses which are tentative in any of the contracts, together with the
+    /// contract used to check their status later.
+    fn tentative_witnesses(&self) -> IndexMap<Txid, (Tx, ContractId)> {
+        let mut tentative = IndexMap::new();
+        for contract_id in self.contracts.contract_ids().collect::<Vec<_>>() {
+            self.contracts.with_contract(
+                contract_id,
+                |contract| {
+                    for witness in contract.witnesses() {
+                        if witness.status == WitnessStatus::Tentative {
+                            tentative
+                                .entry(witness.id)
+                                .or_insert((witness.published, contract_id));
+                        }
+                    }
+                },
+                None,
+            );
+        }
+        tentative
+    }
+
+    /// Filters out the witnesses which got mined, leaving the ones which may be conflicted.
+    fn unmined(&self, witnesses: IndexMap<Txid, (Tx, ContractId)>) -> Vec<(Txid, Tx, ContractId)> {
+        witnesses
+            .into_iter()
+            .filter(|(wid, (_, contract_id))| {
+                self.contracts.with_contract(
+                    *contract_id,
+                    |contract| !contract.witness_status(*wid).is_mined(),
+                    Some(false),
+                )
+            })
+            .map(|(wid, (tx, contract_id))| (wid, tx, contract_id))
+            .collect()
+    }
+
+    /// Checks whether the `spender` of the witness prevouts is another tentative witness of the
+    /// same operations, i.e. its fee-bumping replacement or the witness replaced by it (see
+    /// [`Self::bump_fee`]).
+    ///
+    /// A replacement may be not broadcasted yet, so such witnesses don't conflict until one of
+    /// them gets mined.
+    fn is_pending_replacement(
+        &self,
+        contract_id: ContractId,
+        witness_id: Txid,
+        spender: Txid,
+    ) -> bool {
+        self.contracts.with_contract(
+            contract_id,
+            |contract| {
+                let Some(other) = contract.witness(spender) else {
+                    return false;
+                };
+                other.status == WitnessStatus::Tentative
+                    && contract.witness(witness_id).is_some_and(|witness| {
+                        witness.opids.iter().any(|opid| other.opids.contains(opid))
+                    })
+            },
+            Some(false),
+        )
+    }
+
+    fn archive_conflicts(
+        &mut self,
+        conflicts: Vec<WitnessConflict>,
+    ) -> Result<
+        Vec<WitnessConflict>,
+        MultiError<SyncError<W::Error>, <Sp::Stock as Stock>::Error, <Sp::Pile as Pile>::Error>,
+    > {
+        let wids = conflicts
+            .iter()
+            .map(|conflict| conflict.witness_id)
+            .collect::<Vec<_>>();
+        self.contracts
+            .archive_witnesses(&wids)
+            .map_err(MultiError::from_other_a)?;
+        Ok(conflicts)
     }
 }
 
+/// Classifies contract operations from the point of view of a wallet, which owns the cells for
+/// which `is_owned` returns `true` given their auth token and the outpoint of their seal (if the
+/// seal is known).
+fn contract_history<S: Stock, P: Pile<Seal = TxoSeal>>(
+    contract: &Contract<S, P>,
+    is_owned: impl Fn(AuthToken, Option<Outpoint>) -> bool,
+) -> Result<Vec<TransferEntry>, StateCalcError> {
+    #[derive(Default)]
+    struct Flow {
+        spends: bool,
+        ours: Vec<StrictVal>,
+        theirs: Vec<StrictVal>,
+        counterparties: Vec<EitherSeal<Outpoint>>,
+    }
+
+    let contract_id = contract.contract_id();
+    let articles = contract.articles();
+    let api = articles.default_api();
+    let sum = |name: &StateName, values: Vec<StrictVal>| -> Result<_, StateCalcError> {
+        let mut calc = api
+            .calculate(name.clone())
+            .expect("state name is taken from the contract API");
+        for value in &values {
+            calc.accumulate(value)?;
+        }
+        calc.diff()
+    };
+
+    let genesis_opid = articles.genesis_opid();
+    let genesis = articles.genesis().to_operation(contract_id);
+    let genesis_rels = contract.op_seals(genesis_opid, genesis.destructible_out.len_u16());
+    let ops = iter::once((genesis_opid, genesis, genesis_rels)).chain(contract.operations());
+
+    // State name and ownership flag for each of the known cells
+    let mut cells = HashMap::<CellAddr, (StateName, bool)>::new();
+    let mut entries = vec![];
+    for (opid, op, rels) in ops {
+        let witness = rels
+            .witness_ids
+            .iter()
+            .map(|wid| (*wid, contract.witness_status(*wid)))
+            .reduce(|best, other| if best.1.is_better(other.1) { best } else { other });
+        let witness_id = witness.map(|(wid, _)| wid);
+        let status = witness.map_or(WitnessStatus::Genesis, |(_, status)| status);
+
+        let mut flows = BTreeMap::<StateName, Flow>::new();
+        for input in &op.destructible_in {
+            if let Some((name, true)) = cells.get(&input.addr) {
+                flows.entry(name.clone()).or_default().spends = true;
+            }
+        }
+        for (no, cell) in op.destructible_out.iter().enumerate() {
+            let addr = CellAddr::new(opid, no as u16);
+            let Ok(Some((name, value))) = api.convert_owned(cell.data, articles.types()) else {
+                continue;
+            };
+            let outpoint = rels
+                .defines
+                .get(&(no as u16))
+                .and_then(|seal| {
+                    seal.to_src()
+                        .or_else(|| witness_id.map(|wid| seal.resolve(wid)))
+                })
+                .map(|seal| seal.primary);
+            let owned = is_owned(cell.auth, outpoint);
+            let flow = flows.entry(name.clone()).or_default();
+            if owned {
+                flow.ours.push(value);
+            } else {
+                flow.theirs.push(value);
+                flow.counterparties
+                    .push(outpoint.map_or(EitherSeal::Token(cell.auth), EitherSeal::Alt));
+            }
+            cells.insert(addr, (name, owned));
+        }
+
+        let method = contract.op_method(&op);
+        for (state_name, flow) in flows {
+            if !flow.spends && flow.ours.is_empty() {
+                continue;
+            }
+            let direction = if opid == genesis_opid {
+                TransferDirection::Issue
+            } else if !flow.spends {
+                TransferDirection::Incoming
+            } else if flow.theirs.is_empty() {
+                TransferDirection::Internal
+            } else {
+                TransferDirection::Outgoing
+            };
+            let (received, sent, counterparties) = match direction {
+                TransferDirection::Issue | TransferDirection::Incoming => {
+                    (sum(&state_name, flow.ours)?, vec![], vec![])
+                }
+                TransferDirection::Outgoing => {
+                    (vec![], sum(&state_name, flow.theirs)?, flow.counterparties)
+                }
+                TransferDirection::Internal => (vec![], vec![], vec![]),
+            };
+            entries.push(TransferEntry {
+                contract_id,
+                opid,
+                method: method.clone(),
+                state_name,
+                direction,
+                witness_id,
+                status,
+                received,
+                sent,
+                counterparties,
+            });
+        }
+    }
+    Ok(entries)
+}
+
 impl CreateParams<Outpoint> {
     pub fn new_bitcoin_testnet(codex_id: CodexId, name: impl Into<TypeName>) -> Self {
         Self::new_testnet(codex_id, Consensus::Bitcoin, name)
@@ -997,6 +1684,21 @@ pub enum BundleError {
     TooManyBlanks,
 }
 
+#[derive(Debug, Display, Error, From)]
+#[display(doc_comments)]
+pub enum BalanceError<E: core::error::Error> {
+    /// unable to retrieve the last block height from the wallet. Details: {0}
+    Wallet(E),
+
+    /// contract API doesn't contain information about the state name.
+    #[from(StateUnknown)]
+    StateNameUnknown,
+
+    #[from]
+    #[display(inner)]
+    StateCalc(StateCalcError),
+}
+
 #[derive(Copy, Clone, Eq, PartialEq, Debug, Display, Error, From)]
 #[display(doc_comments)]
 pub enum FulfillError {
@@ -1037,6 +1739,30 @@ pub enum IncludeError {
     Mpc(mpc::LeafNotKnown),
 }
 
+#[derive(Clone, Eq, PartialEq, Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum RbfError {
+    /// witness {0} is not known for the operations of the prefab bundle.
+    UnknownWitness(Txid),
+
+    /// witness {0} is not tentative and can't be replaced.
+    NotTentative(Txid),
+
+    /// witness {0} doesn't signal replaceability.
+    NotReplaceable(Txid),
+
+    /// the replaced witness has no output #{0} to pay the additional fee from.
+    NoOutput(Vout),
+
+    /// output #{0} of the replaced witness has insufficient value to pay the additional fee of
+    /// {1} sats.
+    InsufficientValue(Vout, Sats),
+
+    /// input spending {0} of the replaced witness is not a segwit one, so signing the replacement
+    /// would change its id.
+    NonSegwitInput(Outpoint),
+}
+
 #[cfg(feature = "binfile")]
 mod _fs {
     use std::io;
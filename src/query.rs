@@ -0,0 +1,405 @@
+// Standard Library for RGB smart contracts
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2019-2025 by Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+// Written in 2024-2025 by Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 LNP/BP Laboratories,
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2025 RGB Consortium, Switzerland.
+// Copyright (C) 2019-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+use alloc::boxed::Box;
+use core::cmp::Ordering;
+use core::ops::{Bound, RangeBounds};
+
+use hypersonic::{ContractId, MethodName, Operation, Opid, StateName, Stock};
+use rgb::RgbSeal;
+use strict_types::value::StrictNum;
+use strict_types::StrictVal;
+
+use crate::{Contract, ContractState, OpRels, OwnedState, Pile, WalletState, WitnessStatus};
+
+/// Borrowed view on an element of the owned state returned by [`OwnedStateQuery`].
+#[derive(Debug)]
+pub struct OwnedStateRef<'s, Seal> {
+    /// Contract the state belongs to; `None` when the query is run over a single contract state.
+    pub contract_id: Option<ContractId>,
+    pub state_name: &'s StateName,
+    pub state: &'s OwnedState<Seal>,
+}
+
+impl<Seal> Clone for OwnedStateRef<'_, Seal> {
+    fn clone(&self) -> Self { *self }
+}
+impl<Seal> Copy for OwnedStateRef<'_, Seal> {}
+
+type Filter<'a, Seal> = Box<dyn Fn(&OwnedState<Seal>) -> bool + 'a>;
+type Sorter<'a, Seal> = Box<dyn Fn(&OwnedState<Seal>, &OwnedState<Seal>) -> Ordering + 'a>;
+
+/// Composable query over the owned state of a [`ContractState`] or [`WalletState`].
+///
+/// # Example
+///
+/// ```ignore
+/// let cells = OwnedStateQuery::new()
+///     .state_name("amount")
+///     .status(|status| status.is_mined())
+///     .min_amount(1000)
+///     .limit(10);
+/// let found = wallet_state.query(&cells);
+/// ```
+pub struct OwnedStateQuery<'a, Seal> {
+    contract_id: Option<ContractId>,
+    state_name: Option<StateName>,
+    filters: Vec<Filter<'a, Seal>>,
+    sorter: Option<Sorter<'a, Seal>>,
+    limit: Option<usize>,
+}
+
+impl<Seal> Default for OwnedStateQuery<'_, Seal> {
+    fn default() -> Self {
+        Self {
+            contract_id: None,
+            state_name: None,
+            filters: vec![],
+            sorter: None,
+            limit: None,
+        }
+    }
+}
+
+impl<'a, Seal> OwnedStateQuery<'a, Seal> {
+    pub fn new() -> Self { Self::default() }
+
+    /// Leaves only the state of a specific contract; ignored for a single contract state.
+    pub fn contract(mut self, contract_id: ContractId) -> Self {
+        self.contract_id = Some(contract_id);
+        self
+    }
+
+    pub fn state_name(mut self, state_name: impl Into<StateName>) -> Self {
+        self.state_name = Some(state_name.into());
+        self
+    }
+
+    pub fn filter(mut self, f: impl Fn(&OwnedState<Seal>) -> bool + 'a) -> Self {
+        self.filters.push(Box::new(f));
+        self
+    }
+
+    pub fn status(self, f: impl Fn(WitnessStatus) -> bool + 'a) -> Self {
+        self.filter(move |state| f(state.status))
+    }
+
+    pub fn seal(self, f: impl Fn(&Seal) -> bool + 'a) -> Self {
+        self.filter(move |state| f(&state.assignment.seal))
+    }
+
+    pub fn value(self, f: impl Fn(&StrictVal) -> bool + 'a) -> Self {
+        self.filter(move |state| f(&state.assignment.data))
+    }
+
+    /// Leaves only the fungible state with the value not less than `min`.
+    ///
+    /// Non-fungible state is filtered out.
+    pub fn min_amount(self, min: u64) -> Self {
+        self.value(move |val| matches!(val, StrictVal::Number(StrictNum::Uint(val)) if *val >= min))
+    }
+
+    pub fn sort_by(
+        mut self,
+        f: impl Fn(&OwnedState<Seal>, &OwnedState<Seal>) -> Ordering + 'a,
+    ) -> Self {
+        self.sorter = Some(Box::new(f));
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    fn matches(&self, state: &OwnedState<Seal>) -> bool { self.filters.iter().all(|f| f(state)) }
+
+    fn finalize<'s>(
+        &self,
+        mut found: Vec<OwnedStateRef<'s, Seal>>,
+    ) -> Vec<OwnedStateRef<'s, Seal>> {
+        if let Some(sorter) = &self.sorter {
+            found.sort_by(|a, b| sorter(a.state, b.state));
+        }
+        if let Some(limit) = self.limit {
+            found.truncate(limit);
+        }
+        found
+    }
+}
+
+#[cfg(feature = "bitcoin")]
+mod _bitcoin {
+    use bp::seals::TxoSeal;
+    use bp::{Outpoint, Txid, Vout};
+
+    use super::*;
+
+    /// Seals which are defined by a bitcoin transaction output.
+    pub trait SealOutpoint {
+        fn outpoint(&self) -> Outpoint;
+    }
+
+    impl SealOutpoint for Outpoint {
+        fn outpoint(&self) -> Outpoint { *self }
+    }
+
+    impl SealOutpoint for TxoSeal {
+        fn outpoint(&self) -> Outpoint { self.primary }
+    }
+
+    impl<'a, Seal: SealOutpoint> OwnedStateQuery<'a, Seal> {
+        pub fn outpoints(self, outpoints: impl IntoIterator<Item = Outpoint>) -> Self {
+            let outpoints = outpoints
+                .into_iter()
+                .collect::<alloc::collections::BTreeSet<_>>();
+            self.seal(move |seal| outpoints.contains(&seal.outpoint()))
+        }
+
+        pub fn txid(self, txid: Txid) -> Self {
+            self.seal(move |seal| seal.outpoint().txid == txid)
+        }
+
+        pub fn vout(self, vout: Vout) -> Self {
+            self.seal(move |seal| seal.outpoint().vout == vout)
+        }
+    }
+}
+#[cfg(feature = "bitcoin")]
+pub use _bitcoin::SealOutpoint;
+
+impl<Seal> ContractState<Seal> {
+    /// Runs the query over the owned state, returning borrowed state elements.
+    pub fn query<'s>(&'s self, query: &OwnedStateQuery<Seal>) -> Vec<OwnedStateRef<'s, Seal>> {
+        let found = self
+            .owned
+            .iter()
+            .filter(|(name, _)| query.state_name.as_ref().is_none_or(|n| n == *name))
+            .flat_map(|(state_name, states)| {
+                states
+                    .iter()
+                    .filter(|state| query.matches(state))
+                    .map(move |state| OwnedStateRef { contract_id: None, state_name, state })
+            })
+            .collect();
+        query.finalize(found)
+    }
+}
+
+impl<Seal> WalletState<Seal> {
+    /// Runs the query over the owned state, returning borrowed state elements.
+    pub fn query<'s>(&'s self, query: &OwnedStateQuery<Seal>) -> Vec<OwnedStateRef<'s, Seal>> {
+        let found = self
+            .owned
+            .iter()
+            .filter(|(name, _)| {
+                query.contract_id.is_none_or(|id| id == name.contract_id)
+                    && query
+                        .state_name
+                        .as_ref()
+                        .is_none_or(|n| *n == name.state_name)
+            })
+            .flat_map(|(name, states)| {
+                states
+                    .iter()
+                    .filter(|state| query.matches(state))
+                    .map(move |state| OwnedStateRef {
+                        contract_id: Some(name.contract_id),
+                        state_name: &name.state_name,
+                        state,
+                    })
+            })
+            .collect();
+        query.finalize(found)
+    }
+}
+
+/// Position in the ledger order of operations to continue the [`OpQuery`] from.
+///
+/// Cursors remain valid when new operations are added to the contract, since they are appended to
+/// the end of the ledger.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default, Display, From)]
+#[display(inner)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(transparent))]
+pub struct OpCursor(u64);
+
+impl OpCursor {
+    /// Cursor pointing to the first operation in the ledger.
+    pub const START: Self = Self(0);
+
+    pub fn position(self) -> u64 { self.0 }
+}
+
+/// A page of operations returned by [`Contract::query_operations`].
+#[derive(Clone, Debug)]
+pub struct OpPage<Seal: RgbSeal> {
+    pub operations: Vec<(Opid, Operation, OpRels<Seal>)>,
+    /// Cursor to request the next page with; `None` if all operations were scanned.
+    pub next: Option<OpCursor>,
+}
+
+/// Filtered query over the operations of a contract, returning results page by page.
+///
+/// The operations are scanned in the ledger order, same as returned by [`Contract::operations`];
+/// genesis is not included.
+///
+/// # Example
+///
+/// ```ignore
+/// let query = OpQuery::new()
+///     .method("transfer")
+///     .status(|status| status.is_mined())
+///     .page_size(100);
+/// let mut cursor = OpCursor::START;
+/// loop {
+///     let page = contract.query_operations(&query, cursor);
+///     // process page.operations
+///     let Some(next) = page.next else { break };
+///     cursor = next;
+/// }
+/// ```
+pub struct OpQuery<'a> {
+    method: Option<MethodName>,
+    status: Option<Box<dyn Fn(WitnessStatus) -> bool + 'a>>,
+    opids: (Bound<Opid>, Bound<Opid>),
+    state_name: Option<StateName>,
+    page_size: Option<usize>,
+}
+
+impl Default for OpQuery<'_> {
+    fn default() -> Self {
+        Self {
+            method: None,
+            status: None,
+            opids: (Bound::Unbounded, Bound::Unbounded),
+            state_name: None,
+            page_size: None,
+        }
+    }
+}
+
+impl<'a> OpQuery<'a> {
+    pub fn new() -> Self { Self::default() }
+
+    /// Leaves only the operations called by the API method `method`.
+    ///
+    /// See [`Contract::op_method`] for how the method name is resolved.
+    pub fn method(mut self, method: impl Into<MethodName>) -> Self {
+        self.method = Some(method.into());
+        self
+    }
+
+    /// Leaves only the operations with the status of their best witness matching `f`.
+    pub fn status(mut self, f: impl Fn(WitnessStatus) -> bool + 'a) -> Self {
+        self.status = Some(Box::new(f));
+        self
+    }
+
+    /// Leaves only the operations which ids are within the range.
+    ///
+    /// The operation ids are compared as byte strings, not by the ledger order; this allows
+    /// splitting the contract operations into disjoint shards.
+    pub fn opids(mut self, range: impl RangeBounds<Opid>) -> Self {
+        self.opids = (range.start_bound().cloned(), range.end_bound().cloned());
+        self
+    }
+
+    /// Leaves only the operations which define or spend the state with the given name.
+    pub fn state_name(mut self, state_name: impl Into<StateName>) -> Self {
+        self.state_name = Some(state_name.into());
+        self
+    }
+
+    /// Limits the number of operations returned at once; by default all matching operations are
+    /// returned in a single page.
+    pub fn page_size(mut self, page_size: usize) -> Self {
+        self.page_size = Some(page_size);
+        self
+    }
+}
+
+impl<S: Stock, P: Pile> Contract<S, P> {
+    /// Checks whether the operation defines or spends the state with the given name.
+    ///
+    /// The spent state is taken from the execution trace, so the operations it was defined by are
+    /// not read from the stash.
+    fn op_touches(&self, opid: Opid, op: &Operation, state_name: &StateName) -> bool {
+        let articles = self.articles();
+        let api = articles.default_api();
+        let types = articles.types();
+        let is_owned = |data| matches!(api.convert_owned(data, types), Ok(Some((name, _))) if name == *state_name);
+        op.destructible_out.iter().any(|cell| is_owned(cell.data))
+            || op.immutable_out.iter().any(|data| {
+                matches!(api.convert_global(data, types), Ok(Some((name, _))) if name == *state_name)
+            })
+            || (!op.destructible_in.is_empty()
+                && self
+                    .ledger
+                    .stock()
+                    .transition(opid)
+                    .destroyed
+                    .values()
+                    .any(|cell| is_owned(cell.data)))
+    }
+
+    /// Get a page of the contract operations matching the query, starting from the `cursor`.
+    ///
+    /// The cursor is resolved with the in-memory index of the contract operations, so the
+    /// operations preceding it are not read at all; the operations are read one by one and the
+    /// scan stops once the page is full, returning the cursor to continue from.
+    pub fn query_operations(&self, query: &OpQuery, cursor: OpCursor) -> OpPage<P::Seal> {
+        let mut operations = vec![];
+        let page_size = query.page_size.unwrap_or(usize::MAX);
+        if page_size == 0 {
+            return OpPage { operations, next: Some(cursor) };
+        }
+        for (pos, opid) in (cursor.0..).zip(self.op_ids_from(cursor.0).iter().copied()) {
+            if !query.opids.contains(&opid) {
+                continue;
+            }
+            let op = self.ledger.operation(opid);
+            if query
+                .method
+                .as_ref()
+                .is_some_and(|method| self.op_method(&op).as_ref() != Some(method))
+                || query
+                    .status
+                    .as_ref()
+                    .is_some_and(|f| !f(self.best_op_status(opid)))
+                || query
+                    .state_name
+                    .as_ref()
+                    .is_some_and(|name| !self.op_touches(opid, &op, name))
+            {
+                continue;
+            }
+            let rels = self.op_seals(opid, op.destructible_out.len_u16());
+            operations.push((opid, op, rels));
+            if operations.len() == page_size {
+                return OpPage { operations, next: Some(OpCursor(pos + 1)) };
+            }
+        }
+        OpPage { operations, next: None }
+    }
+}
@@ -0,0 +1,88 @@
+// Standard Library for RGB smart contracts
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2019-2025 by Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+// Written in 2024-2025 by Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 LNP/BP Laboratories,
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2025 RGB Consortium, Switzerland.
+// Copyright (C) 2019-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+use core::hash::Hash;
+use std::collections::HashMap;
+
+use crate::WitnessInfo;
+
+/// Resolver of the witness statuses, which resolves multiple witnesses at once.
+///
+/// Any function resolving a single witness id into its status is a resolver too, resolving the
+/// witnesses one by one.
+pub trait WitnessResolver<Id> {
+    type Error: core::error::Error;
+
+    #[cfg(not(feature = "async"))]
+    /// Resolves the statuses of the witnesses with the given ids.
+    ///
+    /// Witnesses which are missing from the returned map are considered unresolved and keep their
+    /// current status.
+    fn resolve_witnesses(&self, wids: &[Id]) -> Result<HashMap<Id, WitnessInfo>, Self::Error>;
+    #[cfg(feature = "async")]
+    /// Resolves the statuses of the witnesses with the given ids.
+    ///
+    /// Witnesses which are missing from the returned map are considered unresolved and keep their
+    /// current status.
+    async fn resolve_witnesses_async(
+        &self,
+        wids: &[Id],
+    ) -> Result<HashMap<Id, WitnessInfo>, Self::Error>;
+}
+
+#[cfg(not(feature = "async"))]
+impl<Id, I, E, F> WitnessResolver<Id> for F
+where
+    Id: Copy + Eq + Hash,
+    I: Into<WitnessInfo>,
+    E: core::error::Error,
+    F: Fn(Id) -> Result<I, E>,
+{
+    type Error = E;
+
+    fn resolve_witnesses(&self, wids: &[Id]) -> Result<HashMap<Id, WitnessInfo>, E> {
+        wids.iter()
+            .map(|wid| self(*wid).map(|info| (*wid, info.into())))
+            .collect()
+    }
+}
+
+#[cfg(feature = "async")]
+impl<Id, I, E, F> WitnessResolver<Id> for F
+where
+    Id: Copy + Eq + Hash,
+    I: Into<WitnessInfo>,
+    E: core::error::Error,
+    F: AsyncFn(Id) -> Result<I, E>,
+{
+    type Error = E;
+
+    async fn resolve_witnesses_async(&self, wids: &[Id]) -> Result<HashMap<Id, WitnessInfo>, E> {
+        let mut resolved = HashMap::with_capacity(wids.len());
+        for wid in wids {
+            resolved.insert(*wid, self(*wid).await?.into());
+        }
+        Ok(resolved)
+    }
+}
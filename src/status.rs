@@ -0,0 +1,186 @@
+// Standard Library for RGB smart contracts
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2019-2025 by Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+// Written in 2024-2025 by Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 LNP/BP Laboratories,
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2025 RGB Consortium, Switzerland.
+// Copyright (C) 2019-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use hypersonic::Opid;
+
+use crate::WitnessStatus;
+
+/// In-memory index of the effective operation statuses, which is updated incrementally.
+///
+/// The effective status of an operation is the worst status over the operation itself and all its
+/// ancestors; the status of the operation itself is the best status of its witnesses.
+///
+/// The index also keeps the order in which the operations were registered, allowing to seek the
+/// operations by their position without reading the preceding ones.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct OpStatuses {
+    order: Vec<Opid>,
+    own: HashMap<Opid, WitnessStatus>,
+    effective: HashMap<Opid, WitnessStatus>,
+    parents: HashMap<Opid, HashSet<Opid>>,
+    children: HashMap<Opid, HashSet<Opid>>,
+}
+
+impl OpStatuses {
+    /// Effective status of the operation; [`WitnessStatus::Archived`] for unknown operations.
+    pub fn effective(&self, opid: Opid) -> WitnessStatus {
+        self.effective.get(&opid).copied().unwrap_or_default()
+    }
+
+    /// Operations in the order they were registered, starting from the `pos`-th one.
+    pub fn ops_from(&self, pos: usize) -> &[Opid] { self.order.get(pos..).unwrap_or_default() }
+
+    /// Registers an operation and its parents, which are the operations it spends or reads the
+    /// state from.
+    ///
+    /// The effective status is not computed until [`Self::update`] is called for the operation.
+    pub fn add(&mut self, opid: Opid, parents: impl IntoIterator<Item = Opid>) {
+        let parents = parents.into_iter().collect::<HashSet<_>>();
+        for parent in &parents {
+            self.children.entry(*parent).or_default().insert(opid);
+        }
+        if self.parents.insert(opid, parents).is_none() {
+            self.order.push(opid);
+        }
+    }
+
+    /// Updates the status of the operations themselves, and propagates the changes to the
+    /// effective status of the operations and all of their descendants.
+    pub fn update(&mut self, changes: impl IntoIterator<Item = (Opid, WitnessStatus)>) {
+        let mut queue = VecDeque::new();
+        for (opid, status) in changes {
+            self.own.insert(opid, status);
+            queue.push_back(opid);
+        }
+        let mut queued = queue.iter().copied().collect::<HashSet<_>>();
+        while let Some(opid) = queue.pop_front() {
+            queued.remove(&opid);
+            let own = self.own.get(&opid).copied().unwrap_or_default();
+            let status = self
+                .parents
+                .get(&opid)
+                .into_iter()
+                .flatten()
+                .filter_map(|parent| self.effective.get(parent))
+                .fold(own, |worst, other| worst.worst(*other));
+            if self.effective.insert(opid, status) == Some(status) {
+                continue;
+            }
+            for child in self.children.get(&opid).into_iter().flatten() {
+                if queued.insert(*child) {
+                    queue.push_back(*child);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![cfg_attr(coverage_nightly, coverage(off))]
+
+    use core::num::NonZeroU64;
+
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+
+    fn random_status(rng: &mut StdRng) -> WitnessStatus {
+        match rng.random_range(0..5) {
+            0 => WitnessStatus::Archived,
+            1 => WitnessStatus::Tentative,
+            2 => WitnessStatus::Offchain,
+            _ => WitnessStatus::Mined(NonZeroU64::new(rng.random_range(1..100)).unwrap()),
+        }
+    }
+
+    /// Worst status over the operation and all its ancestors, computed by walking the whole graph.
+    fn brute_force(opid: usize, parents: &[Vec<usize>], own: &[WitnessStatus]) -> WitnessStatus {
+        let mut ancestors = HashSet::from([opid]);
+        let mut stack = vec![opid];
+        while let Some(no) = stack.pop() {
+            for parent in &parents[no] {
+                if ancestors.insert(*parent) {
+                    stack.push(*parent);
+                }
+            }
+        }
+        ancestors
+            .into_iter()
+            .map(|no| own[no])
+            .fold(WitnessStatus::Genesis, |worst, other| worst.worst(other))
+    }
+
+    #[test]
+    fn random_dags() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..20 {
+            let count = rng.random_range(1..60);
+            let opids = (0..count)
+                .map(|_| Opid::from(rng.random::<[u8; 32]>()))
+                .collect::<Vec<_>>();
+            let mut own = vec![WitnessStatus::Genesis];
+            let mut parents = vec![vec![]];
+            for no in 1..count {
+                own.push(random_status(&mut rng));
+                parents.push(
+                    (0..rng.random_range(1..=3))
+                        .map(|_| rng.random_range(0..no))
+                        .collect(),
+                );
+            }
+
+            let mut statuses = OpStatuses::default();
+            for (opid, parents) in opids.iter().zip(&parents) {
+                statuses.add(*opid, parents.iter().map(|parent| opids[*parent]));
+            }
+            statuses.update(opids.iter().copied().zip(own.iter().copied()));
+            // Repeated registration doesn't change the order
+            statuses.add(opids[count - 1], parents[count - 1].iter().map(|parent| opids[*parent]));
+            assert_eq!(statuses.ops_from(0), opids.as_slice());
+            assert_eq!(statuses.ops_from(count / 2), &opids[count / 2..]);
+            assert!(statuses.ops_from(count + 1).is_empty());
+            for (no, opid) in opids.iter().enumerate() {
+                assert_eq!(statuses.effective(*opid), brute_force(no, &parents, &own));
+            }
+
+            // Random status changes, as during sync
+            for _ in 0..10 {
+                let changes = (0..rng.random_range(1..5))
+                    .map(|_| (rng.random_range(0..count), random_status(&mut rng)))
+                    .collect::<Vec<_>>();
+                for (no, status) in &changes {
+                    own[*no] = *status;
+                }
+                statuses.update(changes.into_iter().map(|(no, status)| (opids[no], status)));
+                for (no, opid) in opids.iter().enumerate() {
+                    assert_eq!(statuses.effective(*opid), brute_force(no, &parents, &own));
+                }
+            }
+        }
+    }
+}
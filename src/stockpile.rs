@@ -22,9 +22,10 @@
 // or implied. See the License for the specific language governing permissions and limitations under
 // the License.
 
+use alloc::collections::BTreeSet;
 use core::error::Error as StdError;
 
-use amplify::MultiError;
+use amplify::{Bytes32, MultiError};
 use hypersonic::{CodexId, ContractId, Stock};
 use rgb::RgbSeal;
 use strict_encoding::StrictDecode;
@@ -34,6 +35,25 @@ use crate::{
     Pile,
 };
 
+/// Checkpoint of the witness synchronization of a contract, allowing the next call to
+/// [`crate::Contracts::update_witnesses_with_blocks`] to resolve only the pending witnesses of the
+/// contract.
+///
+/// The checkpoint is valid for as long as its tip block stays in the best chain and the same
+/// number of confirmations is required.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct SyncCheckpoint<WitnessId: Ord> {
+    /// Height of the blockchain tip at the synchronization.
+    pub tip_height: u64,
+    /// Hash of the blockchain tip block at the synchronization.
+    pub tip_hash: Bytes32,
+    /// Number of confirmations after which the witnesses were not considered pending anymore.
+    pub min_confirmations: u32,
+    /// Witnesses which were not mined deeper than `min_confirmations`, including tentative,
+    /// offchain and archived ones.
+    pub pending: BTreeSet<WitnessId>,
+}
+
 /// Stockpile provides a specific persistence implementation for the use in [`crate::Contracts`].
 /// It allows for it to abstract from a specific storage media, whether it is a file system,
 /// database, or a network service. Its main task is to load already known contract issuers and
@@ -101,4 +121,30 @@ pub trait Stockpile {
     >;
 
     fn purge(&mut self, contract_id: ContractId) -> Result<(), Self::Error>;
+
+    /// Returns the checkpoint of the last witness synchronization of the contract, if any.
+    ///
+    /// The default implementation doesn't keep the checkpoints, so each synchronization resolves
+    /// all the contract witnesses.
+    #[allow(clippy::type_complexity)]
+    fn sync_checkpoint(
+        &self,
+        _contract_id: ContractId,
+    ) -> Option<SyncCheckpoint<<<Self::Pile as Pile>::Seal as RgbSeal>::WitnessId>> {
+        None
+    }
+
+    /// Saves the checkpoint of the witness synchronization of the contract; `None` removes the
+    /// existing checkpoint, if any.
+    ///
+    /// The checkpoint is a part of the contract data, so the failures are reported as the errors
+    /// of the contract [`Pile`]. The default implementation discards the checkpoint.
+    #[allow(clippy::type_complexity)]
+    fn set_sync_checkpoint(
+        &mut self,
+        _contract_id: ContractId,
+        _checkpoint: Option<&SyncCheckpoint<<<Self::Pile as Pile>::Seal as RgbSeal>::WitnessId>>,
+    ) -> Result<(), <Self::Pile as Pile>::Error> {
+        Ok(())
+    }
 }
@@ -0,0 +1,437 @@
+// Standard Library for RGB smart contracts
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+// Designed in 2019-2025 by Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+// Written in 2024-2025 by Dr Maxim Orlovsky <orlovsky@lnp-bp.org>
+//
+// Copyright (C) 2019-2024 LNP/BP Standards Association, Switzerland.
+// Copyright (C) 2024-2025 LNP/BP Laboratories,
+//                         Institute for Distributed and Cognitive Systems (InDCS), Switzerland.
+// Copyright (C) 2025 RGB Consortium, Switzerland.
+// Copyright (C) 2019-2025 Dr Maxim Orlovsky.
+// All rights under the above copyrights are reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use this file except
+// in compliance with the License. You may obtain a copy of the License at
+//
+//        http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed under the License
+// is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express
+// or implied. See the License for the specific language governing permissions and limitations under
+// the License.
+
+//! Typed access to the global and aggregated contract state.
+//!
+//! The state values are converted into Rust types by typifying them against the types declared
+//! in the contract API, serializing with the contract type system, and strict-decoding the
+//! result. The requested Rust type must have the same semantic type id as the declared one.
+
+use alloc::boxed::Box;
+use core::any::type_name;
+use std::collections::{BTreeSet, VecDeque};
+use std::io::{self, BufRead};
+use std::panic;
+
+use amplify::confinement::U24;
+use hypersonic::{Aggregator, Api, CellAddr, Opid, StateName, Stock, SubAggregator};
+use strict_encoding::{
+    DeserializeError, SerializeError, StrictDecode, StrictDumb, StrictEncode, StrictProduct,
+    StrictReader, StrictTuple, StrictType, TypeName, TypedWrite,
+};
+use strict_types::stl::std_stl;
+use strict_types::typelib::{LibBuilder, LibRef};
+use strict_types::typify::Error as TypifyError;
+use strict_types::{SemId, StrictVal, Ty, TypeSystem};
+
+use crate::{Contract, Pile};
+
+/// Errors happening during typed access to the contract state.
+#[derive(Debug, Display, Error)]
+#[display(doc_comments)]
+pub enum TypedStateError {
+    /// contract API doesn't declare global state `{0}`.
+    UnknownGlobal(StateName),
+
+    /// contract API doesn't declare aggregated state `{0}`.
+    UnknownAggregated(StateName),
+
+    /// global state `{0}` has no values defined.
+    NoGlobal(StateName),
+
+    /// aggregated state `{0}` is not computed for the current contract state.
+    NoAggregated(StateName),
+
+    /// type of aggregated state `{0}` can't be derived from its aggregator; only aggregators
+    /// taking a constant or a global state value have a known type.
+    UntypedAggregated(StateName),
+
+    /// type `{ty}` requested for state `{name}` can't be compiled into a strict type library;
+    /// only types from libraries depending on the standard library alone are supported.
+    UnknownType { name: StateName, ty: &'static str },
+
+    /// state `{name}` has declared type {sem_id}, while the requested type `{ty}` has semantic id
+    /// {found}.
+    TypeMismatch {
+        name: StateName,
+        sem_id: SemId,
+        ty: &'static str,
+        found: SemId,
+    },
+
+    /// value of state `{name}` doesn't match its declared type {sem_id}: {error}
+    Typify {
+        name: StateName,
+        sem_id: SemId,
+        error: Box<TypifyError>,
+    },
+
+    /// value of state `{name}` can't be serialized with the contract type system: {error}
+    Serialize {
+        name: StateName,
+        error: SerializeError,
+    },
+
+    /// value of state `{name}` with the declared type {sem_id} can't be decoded as `{ty}`:
+    /// {error}
+    Decode {
+        name: StateName,
+        sem_id: SemId,
+        ty: &'static str,
+        error: Box<DeserializeError>,
+    },
+}
+
+/// Returns semantic type id of the global state with the given name.
+fn global_sem_id(api: &Api, name: &StateName) -> Result<SemId, TypedStateError> {
+    api.global()
+        .get(name)
+        .map(|api| api.sem_id)
+        .ok_or_else(|| TypedStateError::UnknownGlobal(name.clone()))
+}
+
+/// Returns semantic type id of the aggregated state with the given name, if it can be derived from
+/// its aggregator.
+fn aggregated_sem_id(api: &Api, name: &StateName) -> Option<SemId> {
+    let sub_sem_id = |sub: &SubAggregator| match sub {
+        SubAggregator::Const(sem_id, _) => Some(*sem_id),
+        SubAggregator::TheOnly(global)
+        | SubAggregator::First(global)
+        | SubAggregator::Nth(global, _)
+        | SubAggregator::Last(global)
+        | SubAggregator::NthBack(global, _) => api.global().get(global).map(|api| api.sem_id),
+        SubAggregator::Copy(other) if other != name => aggregated_sem_id(api, other),
+        _ => None,
+    };
+    match api.aggregators().get(name)? {
+        Aggregator::Take(sub) => sub_sem_id(sub),
+        Aggregator::Or(first, second) => {
+            let sem_id = sub_sem_id(first)?;
+            (sub_sem_id(second)? == sem_id).then_some(sem_id)
+        }
+        _ => None,
+    }
+}
+
+/// Library name used to compile types which are not a part of any library.
+const LIB_NAME_PROBE: &str = "RGBTypeProbe";
+
+/// Wrapper giving a name to the type `T` inside the library of `T`, such that the semantic id of
+/// `T` can be taken from the compiled library for both named and unnamed types.
+struct TypeProbe<T>(T);
+
+impl<T: StrictType> StrictType for TypeProbe<T> {
+    const STRICT_LIB_NAME: &'static str = match T::STRICT_LIB_NAME.as_bytes() {
+        b"_" => LIB_NAME_PROBE,
+        _ => T::STRICT_LIB_NAME,
+    };
+    fn strict_name() -> Option<TypeName> { Some(tn!("RGBTypeProbe")) }
+}
+impl<T: StrictDumb + StrictType> StrictProduct for TypeProbe<T> {}
+impl<T: StrictDumb + StrictType> StrictTuple for TypeProbe<T> {
+    const FIELD_COUNT: u8 = 1;
+}
+impl<T: StrictDumb + StrictType> StrictDumb for TypeProbe<T> {
+    fn strict_dumb() -> Self { Self(T::strict_dumb()) }
+}
+impl<T: StrictDumb + StrictEncode> StrictEncode for TypeProbe<T> {
+    fn strict_encode<W: TypedWrite>(&self, writer: W) -> io::Result<W> {
+        writer.write_newtype::<Self>(&self.0)
+    }
+}
+
+/// Returns semantic type id of the Rust type `T`.
+///
+/// The id is computed by compiling `T` into a type library which may depend only on the standard
+/// strict types library; `None` is returned for types which can't be compiled this way.
+fn type_sem_id<T: StrictEncode + StrictDumb>() -> Option<SemId> {
+    let compile = || {
+        LibBuilder::with(libname!(TypeProbe::<T>::STRICT_LIB_NAME), [
+            std_stl().to_dependency_types()
+        ])
+        .transpile::<TypeProbe<T>>()
+        .compile()
+        .ok()
+    };
+    // The library builder panics on the use of types from libraries which are not its
+    // dependencies
+    let lib = panic::catch_unwind(compile).ok().flatten()?;
+    let Ty::Tuple(fields) = lib.types.get(&tn!("RGBTypeProbe"))? else {
+        return None;
+    };
+    fields.iter().next().map(|field| match field {
+        LibRef::Inline(ty) => ty.sem_id_unnamed(),
+        LibRef::Named(sem_id) => *sem_id,
+        LibRef::Extern(ext) => ext.sem_id,
+    })
+}
+
+/// Checks that the Rust type `T` has the declared semantic type id of the state `name`.
+fn check_type<T: StrictEncode + StrictDumb>(
+    name: &StateName,
+    sem_id: SemId,
+) -> Result<(), TypedStateError> {
+    let found = type_sem_id::<T>()
+        .ok_or_else(|| TypedStateError::UnknownType { name: name.clone(), ty: type_name::<T>() })?;
+    if found != sem_id {
+        return Err(TypedStateError::TypeMismatch {
+            name: name.clone(),
+            sem_id,
+            ty: type_name::<T>(),
+            found,
+        });
+    }
+    Ok(())
+}
+
+/// Converts a state value into a Rust type using the declared semantic type id.
+fn decode<T: StrictDecode>(
+    types: &TypeSystem,
+    name: &StateName,
+    sem_id: SemId,
+    val: StrictVal,
+) -> Result<T, TypedStateError> {
+    let typed = types
+        .typify(val, sem_id)
+        .map_err(|error| TypedStateError::Typify {
+            name: name.clone(),
+            sem_id,
+            error: Box::new(error),
+        })?;
+    let data = types
+        .strict_serialize_value::<U24>(&typed)
+        .map_err(|error| TypedStateError::Serialize { name: name.clone(), error })?;
+    let decode_exact = || -> Result<T, DeserializeError> {
+        let mut reader = StrictReader::in_memory::<U24>(data);
+        let val = T::strict_decode(&mut reader)?;
+        if !reader.into_cursor().fill_buf()?.is_empty() {
+            return Err(DeserializeError::DataNotEntirelyConsumed);
+        }
+        Ok(val)
+    };
+    decode_exact().map_err(|error| TypedStateError::Decode {
+        name: name.clone(),
+        sem_id,
+        ty: type_name::<T>(),
+        error: Box::new(error),
+    })
+}
+
+impl<S: Stock, P: Pile> Contract<S, P> {
+    /// Finds the operation among `opids` which is not an ancestor of any other of them.
+    ///
+    /// The ancestors are walked only until a single candidate is left; if several independent
+    /// operations remain, the one which comes last in the contract history is returned.
+    fn latest_operation(&self, opids: BTreeSet<Opid>) -> Opid {
+        let genesis_opid = self.articles().genesis_opid();
+        let mut candidates = opids.clone();
+        let mut visited = opids;
+        let mut queue = visited.iter().copied().collect::<VecDeque<_>>();
+        while candidates.len() > 1 {
+            let Some(opid) = queue.pop_front() else {
+                break;
+            };
+            if opid == genesis_opid {
+                continue;
+            }
+            let op = self.ledger.operation(opid);
+            let parents = op
+                .destructible_in
+                .iter()
+                .map(|input| input.addr.opid)
+                .chain(op.immutable_in.iter().map(|addr| addr.opid));
+            for parent in parents {
+                candidates.remove(&parent);
+                if visited.insert(parent) {
+                    queue.push_back(parent);
+                }
+            }
+        }
+        if candidates.len() == 1 {
+            return candidates.pop_first().expect("single candidate");
+        }
+        // Genesis is not a part of the operations and always comes first
+        self.ledger
+            .operations()
+            .map(|(opid, _)| opid)
+            .filter(|opid| candidates.contains(opid))
+            .last()
+            .unwrap_or(genesis_opid)
+    }
+
+    /// Get all values of the global state `name`, decoded into type `T`.
+    ///
+    /// The values are ordered by the cell address which defines them. Only the verifiable part of
+    /// the state is decoded.
+    pub fn global<T: StrictDecode + StrictEncode + StrictDumb>(
+        &self,
+        name: impl Into<StateName>,
+    ) -> Result<Vec<T>, TypedStateError> {
+        let name = name.into();
+        let articles = self.articles();
+        let sem_id = global_sem_id(articles.default_api(), &name)?;
+        check_type::<T>(&name, sem_id)?;
+        self.full_state()
+            .main
+            .global
+            .get(&name)
+            .into_iter()
+            .flat_map(|map| map.values())
+            .map(|atom| decode(articles.types(), &name, sem_id, atom.verified.clone()))
+            .collect()
+    }
+
+    /// Get the latest value of the global state `name`, decoded into type `T`.
+    ///
+    /// The latest value is the one defined by the operation which is not an ancestor of any other
+    /// operation defining the state. If there are several of them, which do not depend on each
+    /// other, the one coming last in the contract history is taken; the history is ordered
+    /// topologically, so in this case the choice is not guaranteed.
+    pub fn global_latest<T: StrictDecode + StrictEncode + StrictDumb>(
+        &self,
+        name: impl Into<StateName>,
+    ) -> Result<T, TypedStateError> {
+        let name = name.into();
+        let articles = self.articles();
+        let sem_id = global_sem_id(articles.default_api(), &name)?;
+        check_type::<T>(&name, sem_id)?;
+        let state = self
+            .full_state()
+            .main
+            .global
+            .get(&name)
+            .filter(|map| !map.is_empty())
+            .ok_or_else(|| TypedStateError::NoGlobal(name.clone()))?;
+        let latest = self.latest_operation(state.keys().map(|addr| addr.opid).collect());
+        let (_, atom) = state
+            .range(CellAddr::new(latest, 0)..=CellAddr::new(latest, u16::MAX))
+            .next_back()
+            .expect("non-empty state");
+        decode(articles.types(), &name, sem_id, atom.verified.clone())
+    }
+
+    /// Get the aggregated state `name`, decoded into type `T`.
+    ///
+    /// The type of the aggregated state is derived from its aggregator; see
+    /// [`TypedStateError::UntypedAggregated`] for the cases when this is not possible.
+    pub fn aggregated<T: StrictDecode + StrictEncode + StrictDumb>(
+        &self,
+        name: impl Into<StateName>,
+    ) -> Result<T, TypedStateError> {
+        let name = name.into();
+        let articles = self.articles();
+        let api = articles.default_api();
+        if !api.aggregators().contains_key(&name) {
+            return Err(TypedStateError::UnknownAggregated(name));
+        }
+        let sem_id = aggregated_sem_id(api, &name)
+            .ok_or_else(|| TypedStateError::UntypedAggregated(name.clone()))?;
+        check_type::<T>(&name, sem_id)?;
+        let val = self
+            .full_state()
+            .main
+            .aggregated
+            .get(&name)
+            .ok_or_else(|| TypedStateError::NoAggregated(name.clone()))?;
+        decode(articles.types(), &name, sem_id, val.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![cfg_attr(coverage_nightly, coverage(off))]
+
+    use strict_encoding::Bool;
+    use strict_types::stl::std_stl;
+    use strict_types::SystemBuilder;
+
+    use super::*;
+
+    #[test]
+    fn decode_declared_type() {
+        let sys = SystemBuilder::new()
+            .import(std_stl())
+            .unwrap()
+            .finalize()
+            .unwrap();
+        let sem_id = *sys.resolve("Std.Bool").unwrap();
+        let types = sys.as_types();
+        let name = vname!("flag");
+
+        let val = decode::<Bool>(types, &name, sem_id, StrictVal::bool(true)).unwrap();
+        assert_eq!(val, Bool::True);
+
+        let err = decode::<Bool>(types, &name, sem_id, StrictVal::num(2u8)).unwrap_err();
+        assert!(matches!(err, TypedStateError::Typify { .. }));
+
+        let err = decode::<u16>(types, &name, sem_id, StrictVal::bool(false)).unwrap_err();
+        assert!(matches!(err, TypedStateError::Decode { ty: "u16", .. }));
+    }
+
+    #[test]
+    fn check_declared_type() {
+        let sys = SystemBuilder::new()
+            .import(std_stl())
+            .unwrap()
+            .finalize()
+            .unwrap();
+        let sem_id = *sys.resolve("Std.Bool").unwrap();
+        let name = vname!("flag");
+
+        check_type::<Bool>(&name, sem_id).unwrap();
+        let err = check_type::<u8>(&name, sem_id).unwrap_err();
+        assert!(matches!(err, TypedStateError::TypeMismatch { ty: "u8", .. }));
+        let err = check_type::<Bool>(&name, Ty::<SemId>::U8.sem_id_unnamed()).unwrap_err();
+        assert!(matches!(err, TypedStateError::TypeMismatch { .. }));
+    }
+
+    #[test]
+    fn rust_type_sem_id() {
+        let sys = SystemBuilder::new()
+            .import(std_stl())
+            .unwrap()
+            .finalize()
+            .unwrap();
+        assert_eq!(type_sem_id::<Bool>(), Some(*sys.resolve("Std.Bool").unwrap()));
+        assert_eq!(type_sem_id::<u64>(), Some(Ty::<SemId>::U64.sem_id_unnamed()));
+        assert_ne!(type_sem_id::<u32>(), type_sem_id::<u64>());
+    }
+
+    #[test]
+    fn unknown_library() {
+        #[derive(Clone, Copy, Debug, Default)]
+        #[derive(StrictType, StrictEncode, StrictDecode)]
+        #[strict_type(lib = "TestInner")]
+        struct Inner(u8);
+
+        #[derive(Clone, Copy, Debug, Default)]
+        #[derive(StrictType, StrictEncode, StrictDecode)]
+        #[strict_type(lib = "TestOuter")]
+        struct Outer(Inner);
+
+        assert_eq!(type_sem_id::<Outer>(), None);
+        let err = check_type::<Outer>(&vname!("outer"), SemId::unit()).unwrap_err();
+        assert!(matches!(err, TypedStateError::UnknownType { .. }));
+    }
+}
@@ -12,8 +12,16 @@ use std::convert::Infallible;
 use std::fs;
 use std::path::PathBuf;
 
-use bp::seals::TxoSeal;
-use rgb::{Consensus, Contracts, Operation};
+use amplify::confinement::{Confined, SmallOrdMap};
+use amplify::ByteArray;
+use bp::seals::{mmb, mpc, Anchor, TxoSeal, WTxoSeal};
+use bp::{LockTime, Outpoint, Sats, ScriptPubkey, SeqNo, Tx, TxIn, TxOut, Txid, Vout};
+use commit_verify::{CommitId, Digest, DigestExt, Sha256};
+use hypersonic::CallParams;
+use rgb::{
+    Assignment, CellAddr, Consensus, Contracts, CoreParams, CreateParams, Issuer, NamedState,
+    Operation, RgbSealDef,
+};
 use rgb_persist_fs::StockpileDir;
 
 use crate::utils::setup;
@@ -56,3 +64,116 @@ fn export_import_contract() {
         })
         .unwrap();
 }
+
+fn stockpile(name: &str) -> StockpileDir<TxoSeal> {
+    let dir = PathBuf::from(format!("tests/data/{name}"));
+    fs::remove_dir_all(&dir).ok();
+    fs::create_dir_all(&dir).ok();
+    StockpileDir::<TxoSeal>::load(dir, Consensus::Bitcoin, true).unwrap()
+}
+
+#[test]
+fn consume_valid_witness() {
+    let mut noise_engine = Sha256::new();
+    noise_engine.input_raw(b"test");
+
+    let mut contracts =
+        Contracts::<_, HashMap<_, _>, HashMap<_, _>>::load(stockpile("storage-sender"));
+    let issuer = Issuer::load("tests/data/Test.issuer", |_, _, _| -> Result<_, Infallible> {
+        unreachable!()
+    })
+    .unwrap();
+    let codex_id = contracts.import_issuer(issuer).unwrap();
+
+    // Issue
+    let funding = Outpoint::new(Txid::from_byte_array([0xFA; 32]), 0);
+    let mut params = CreateParams::new_bitcoin_testnet(codex_id, "Test");
+    params.push_owned_unlocked("amount", Assignment::new_internal(funding, 100u64));
+    let contract_id = contracts
+        .issue(params.transform(noise_engine.clone()))
+        .unwrap();
+    let genesis_addr = CellAddr::new(contracts.contract_articles(contract_id).genesis_opid(), 0);
+
+    // Transfer to the outputs of a witness transaction
+    let seals = small_bmap![
+        0 => WTxoSeal::vout_no_fallback(Vout::from_u32(0), noise_engine.clone(), 0),
+        1 => WTxoSeal::vout_no_fallback(Vout::from_u32(1), noise_engine.clone(), 1)
+    ];
+    let mut params = CallParams {
+        core: CoreParams { method: vname!("transfer"), global: none!(), owned: none!() },
+        using: none!(),
+        reading: none!(),
+    };
+    params.using.insert(genesis_addr, None);
+    for (pos, amount) in [(0u16, 30u64), (1, 70)] {
+        params.core.owned.push(NamedState::new_unlocked(
+            "amount",
+            seals[&pos].auth_token(),
+            amount,
+        ));
+    }
+    let op = contracts.contract_call(contract_id, params, seals).unwrap();
+    let opid = op.opid();
+
+    // Commit to the operation with an opret witness transaction
+    let protocol_id = mpc::ProtocolId::from(contract_id.to_byte_array());
+    let msg = mmb::Message::from_byte_array(opid.to_byte_array());
+    let mmb_proof = mmb::BundleProof { map: SmallOrdMap::from_checked(bmap! { 0 => msg }) };
+    let mut messages = mpc::MessageMap::default();
+    messages
+        .insert(protocol_id, mpc::MessageSource::Mmb(mmb_proof.clone()))
+        .unwrap();
+    let source = mpc::Source { min_depth: mpc::MPC_MINIMAL_DEPTH, entropy: 0, messages };
+    let tree = source.into_merkle_tree().unwrap();
+    let commitment = tree.commit_id();
+    let tx = Tx {
+        version: default!(),
+        inputs: Confined::from_checked(vec![TxIn {
+            prev_output: funding,
+            sig_script: none!(),
+            sequence: SeqNo::from_consensus_u32(0xFFFF_FFFF),
+            witness: none!(),
+        }]),
+        outputs: Confined::from_checked(vec![
+            TxOut::new(ScriptPubkey::default(), Sats::from(1000u64)),
+            TxOut::new(ScriptPubkey::default(), Sats::from(1000u64)),
+            TxOut::new(ScriptPubkey::op_return(commitment.as_slice()), Sats::ZERO),
+        ]),
+        lock_time: LockTime::ZERO,
+    };
+    let anchor = Anchor {
+        mmb_proof,
+        mpc_protocol: protocol_id,
+        mpc_proof: mpc::MerkleBlock::from(tree)
+            .to_merkle_proof(protocol_id)
+            .unwrap(),
+        dbc_proof: None,
+        fallback_proof: default!(),
+    };
+    contracts.include(contract_id, opid, &tx, anchor).unwrap();
+
+    let filename = "tests/data/valid.rgb";
+    fs::remove_file(filename).ok();
+    let terminals = op.destructible_out.iter().map(|cell| cell.auth);
+    contracts
+        .consign_to_file(filename, contract_id, terminals)
+        .unwrap();
+
+    // The witness is applied before its operation, which must not break the consumption
+    let mut receiver =
+        Contracts::<_, HashMap<_, _>, HashMap<_, _>>::load(stockpile("storage-receiver"));
+    let resolver = |_: &Operation| -> BTreeMap<_, _> { bmap![] };
+    receiver
+        .consume_from_file(true, filename, resolver, |_, _, _| -> Result<_, Infallible> {
+            unreachable!()
+        })
+        .unwrap();
+    assert!(receiver.has_contract(contract_id));
+    assert_eq!(
+        receiver.contract_state(contract_id).owned["amount"][0].addr,
+        CellAddr::new(opid, 0)
+    );
+    // The witness output seals are indexed once the operation gets applied
+    let owned = receiver.owned_by_primary(Outpoint::new(tx.txid(), 1));
+    assert_eq!(owned[&contract_id]["amount"][0].addr, CellAddr::new(opid, 1));
+}
@@ -0,0 +1,690 @@
+#![cfg(not(target_arch = "wasm32"))]
+
+#[macro_use]
+extern crate amplify;
+#[macro_use]
+extern crate strict_types;
+
+mod utils;
+
+use std::collections::{BTreeSet, HashMap};
+use std::convert::Infallible;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Seek, SeekFrom, Write};
+use std::num::NonZeroU64;
+use std::path::PathBuf;
+
+use amplify::confinement::SmallOrdMap;
+use amplify::num::u256;
+use amplify::Bytes32;
+use bp::seals::{Anchor, TxoSeal, WTxoSeal};
+use bp::{Tx, Txid, Vout};
+use commit_verify::{Digest, DigestExt, Sha256};
+use hypersonic::{
+    Aggregator, CallParams, GlobalApi, RawBuilder, RawConvertor, StateAtom, StateBuilder,
+    StateConvertor, SubAggregator,
+};
+use rgb::{
+    Assignment, BlockInfo, CellAddr, Contract, CoreParams, CreateParams, Issuer, NamedState,
+    OpCursor, OpQuery, OpRels, Opid, Outpoint, OwnedStateQuery, Pile, TypedStateError, Witness,
+    WitnessInfo, WitnessStatus,
+};
+use rgb_persist_fs::{PileFs, StockFs};
+use rgbcore::RgbSealDef;
+use strict_encoding::{StrictDecode, StrictDumb, StrictEncode, StrictType};
+use strict_types::SemId;
+
+use crate::utils::setup;
+
+/// Fungible amount type used by the test codex.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
+#[derive(StrictType, StrictDumb, StrictEncode, StrictDecode)]
+#[strict_type(lib = "Fungible")]
+struct Amount(u64);
+
+#[test]
+fn state_at_height() {
+    let mut contract = setup("StateAtHeight");
+    let genesis_state = contract.state_at(0);
+    let genesis_owned = genesis_state.owned.get("amount").unwrap();
+    assert_eq!(genesis_owned.len(), 20);
+    assert!(genesis_owned
+        .iter()
+        .all(|owned| owned.status == WitnessStatus::Genesis));
+
+    // Nothing is mined yet: all the operations are tentative
+    assert_eq!(contract.state_at(u64::MAX), genesis_state);
+
+    let mined = WitnessStatus::Mined(NonZeroU64::new(100).unwrap());
+    let changed = contract
+        .witness_ids()
+        .map(|wid| (wid, mined))
+        .collect::<Vec<_>>();
+    contract.sync(changed).unwrap();
+
+    assert_eq!(contract.state_at(99), genesis_state);
+    assert_eq!(contract.state_at(100), contract.state());
+    assert_eq!(contract.state_at(u64::MAX), contract.state());
+}
+
+#[test]
+fn provenance() {
+    let contract = setup("Provenance");
+    let genesis_opid = contract.articles().genesis_opid();
+    let state = contract.state();
+    let owned = state.owned.get("amount").unwrap();
+    let last = owned
+        .iter()
+        .find(|owned| owned.addr.opid != genesis_opid)
+        .unwrap();
+
+    let steps = contract.provenance(last.addr).unwrap();
+    assert_eq!(steps[0].opid, last.addr.opid);
+    assert!(steps[0].seals.contains_key(&last.addr.pos));
+    let genesis = steps.last().unwrap();
+    assert_eq!(genesis.opid, genesis_opid);
+    assert!(genesis.witnesses.is_empty());
+    assert!(genesis.consumed.is_empty());
+    for step in &steps[..steps.len() - 1] {
+        assert!(!step.consumed.is_empty());
+        assert!(step
+            .witnesses
+            .iter()
+            .all(|(_, status)| *status == WitnessStatus::Tentative));
+    }
+
+    let genesis_addr = CellAddr::new(genesis_opid, 0);
+    assert_eq!(contract.provenance(genesis_addr).unwrap().len(), 1);
+    assert!(contract
+        .provenance(CellAddr::new(genesis_opid, u16::MAX))
+        .is_none());
+}
+
+#[test]
+fn owned_state_query() {
+    let contract = setup("OwnedStateQuery");
+    let state = contract.state();
+    let total = state.owned.get("amount").unwrap().len();
+
+    let query = OwnedStateQuery::new().state_name("amount");
+    assert_eq!(state.query(&query).len(), total);
+    assert!(state
+        .query(&OwnedStateQuery::new().state_name("unknown"))
+        .is_empty());
+
+    let outpoint = state.owned.get("amount").unwrap()[0]
+        .assignment
+        .seal
+        .primary;
+    let query = OwnedStateQuery::<TxoSeal>::new()
+        .status(|status| status.is_valid())
+        .outpoints([outpoint])
+        .vout(outpoint.vout)
+        .min_amount(1);
+    let found = state.query(&query);
+    assert!(!found.is_empty());
+    assert!(found.iter().all(
+        |owned| owned.state.assignment.seal.primary == outpoint && owned.contract_id.is_none()
+    ));
+    assert!(state
+        .query(&OwnedStateQuery::new().min_amount(u64::MAX))
+        .is_empty());
+
+    let query = OwnedStateQuery::new()
+        .sort_by(|a, b| b.addr.pos.cmp(&a.addr.pos))
+        .limit(3);
+    let found = state.query(&query);
+    assert_eq!(found.len(), 3);
+    assert!(found[0].state.addr.pos >= found[1].state.addr.pos);
+    assert!(found[1].state.addr.pos >= found[2].state.addr.pos);
+}
+
+#[test]
+fn owned_by_primary() {
+    let contract = setup("OwnedByPrimary");
+    let state = contract.state();
+    let owned = state.owned.get("amount").unwrap();
+
+    let outpoints = owned
+        .iter()
+        .map(|owned| owned.assignment.seal.primary)
+        .collect::<BTreeSet<_>>();
+    let mut count = 0;
+    for outpoint in &outpoints {
+        let found = contract.owned_by_primary(*outpoint);
+        let found = found.get("amount").unwrap();
+        for state in found {
+            assert_eq!(state.assignment.seal.primary, *outpoint);
+            assert!(owned.contains(state));
+        }
+        count += found.len();
+    }
+    assert_eq!(count, owned.len());
+
+    // All genesis state is already spent
+    assert!(contract
+        .owned_by_primary(Outpoint::strict_dumb())
+        .is_empty());
+
+    let some = outpoints
+        .iter()
+        .step_by(2)
+        .copied()
+        .collect::<BTreeSet<_>>();
+    let filtered = state
+        .clone()
+        .filter_map(|seal| some.contains(&seal.primary).then_some(seal));
+    assert_eq!(contract.state_by_primary(some.iter().copied()), filtered);
+    assert_eq!(contract.state_by_primary([]), state.clone().filter_map(|_| None));
+
+    let path = PathBuf::from("tests/data/OwnedByPrimary.contract");
+    let reloaded = Contract::<StockFs, PileFs<TxoSeal>>::load(path.clone(), path.clone()).unwrap();
+    for outpoint in &outpoints {
+        assert_eq!(reloaded.owned_by_primary(*outpoint), contract.owned_by_primary(*outpoint));
+    }
+    drop(reloaded);
+
+    // An interrupted write leaves an incomplete record, and the index is rebuilt on load
+    let mut log = OpenOptions::new()
+        .append(true)
+        .open(path.join("seals.log"))
+        .unwrap();
+    log.write_all(&[0xFF; 40]).unwrap();
+    drop(log);
+    let recovered = Contract::<StockFs, PileFs<TxoSeal>>::load(path.clone(), path.clone()).unwrap();
+    for outpoint in &outpoints {
+        assert_eq!(recovered.owned_by_primary(*outpoint), contract.owned_by_primary(*outpoint));
+    }
+    drop(recovered);
+
+    // Piles created before the index, the block details and the witness tracking were introduced
+    // get the index rebuilt on load, without modifying the pile
+    for name in ["seals.log", "blocks.log", "seen.log"] {
+        fs::remove_file(path.join(name)).unwrap();
+    }
+    let files = fs::read_dir(&path).unwrap().count();
+    let upgraded = Contract::<StockFs, PileFs<TxoSeal>>::load(path.clone(), path.clone()).unwrap();
+    for outpoint in outpoints {
+        assert_eq!(upgraded.owned_by_primary(outpoint), contract.owned_by_primary(outpoint));
+    }
+    drop(upgraded);
+    assert_eq!(fs::read_dir(&path).unwrap().count(), files);
+    assert!(!path.join("seals.log").exists());
+}
+
+#[test]
+fn state_diff() {
+    let mut contract = setup("StateDiff");
+    let state = contract.state();
+    assert_eq!(contract.state_without([]), state);
+    assert!(state.diff(&state).is_empty());
+
+    let opid = state.owned.get("amount").unwrap()[0].addr.opid;
+    let diff = contract.state_diff_ops([opid]);
+    let created = diff.created.get("amount").unwrap();
+    assert_eq!(created.len(), 2);
+    assert!(created.iter().all(|owned| owned.addr.opid == opid));
+    let destroyed = diff.destroyed.get("amount").unwrap();
+    assert_eq!(destroyed.len(), 2);
+    assert!(destroyed
+        .iter()
+        .all(|owned| owned.assignment.data == svnum!(92u64)));
+    assert!(diff.global_added.is_empty() && diff.global_removed.is_empty());
+
+    let mined = WitnessStatus::Mined(NonZeroU64::new(100).unwrap());
+    let changed = contract
+        .witness_ids()
+        .map(|wid| (wid, mined))
+        .collect::<Vec<_>>();
+    contract.sync(changed).unwrap();
+
+    let diff = contract.state_diff_at(99, 100);
+    let genesis_opid = contract.articles().genesis_opid();
+    assert_eq!(diff.destroyed.get("amount").unwrap().len(), 20);
+    assert!(diff
+        .destroyed
+        .values()
+        .flatten()
+        .all(|owned| owned.addr.opid == genesis_opid));
+    assert_eq!(diff.created, contract.state().owned);
+    assert!(contract.state_diff_at(100, u64::MAX).is_empty());
+}
+
+#[test]
+fn typed_state_errors() {
+    let contract = setup("TypedState");
+    // The test codex doesn't declare any global or aggregated state
+    assert!(matches!(
+        contract.global::<u64>("ticker"),
+        Err(TypedStateError::UnknownGlobal(name)) if name.as_str() == "ticker"
+    ));
+    assert!(matches!(
+        contract.global_latest::<u64>("ticker"),
+        Err(TypedStateError::UnknownGlobal(_))
+    ));
+    assert!(matches!(
+        contract.aggregated::<u64>("totalSupply"),
+        Err(TypedStateError::UnknownAggregated(_))
+    ));
+}
+
+/// Issues a contract under the test codex with an API extended with the global state `_counter`,
+/// which is then updated by a chain of transfers, and the aggregated state `limit`.
+fn counter_contract(name: &str, updates: u64) -> Contract<StockFs, PileFs<TxoSeal>> {
+    let mut noise_engine = Sha256::new();
+    noise_engine.input_raw(b"test");
+
+    let issuer = Issuer::load("tests/data/Test.issuer", |_, _, _| -> Result<_, Infallible> {
+        unreachable!()
+    })
+    .unwrap();
+    let (codex, mut semantics) = issuer.dismember();
+    let api = &mut semantics.default;
+    let sem_id = api.owned.get("amount").unwrap().sem_id;
+    let global = |ty: u8| GlobalApi {
+        sem_id,
+        published: true,
+        convertor: StateConvertor::TypedEncoder(u256::from(ty)),
+        builder: StateBuilder::TypedEncoder(u256::from(ty)),
+        raw_convertor: RawConvertor::StrictDecode(SemId::unit()),
+        raw_builder: RawBuilder::StrictEncode(SemId::unit()),
+    };
+    api.global.insert(vname!("_counter"), global(0x10)).unwrap();
+    api.global.insert(vname!("_limit"), global(0x11)).unwrap();
+    api.aggregators
+        .insert(vname!("limit"), Aggregator::Take(SubAggregator::TheOnly(vname!("_limit"))))
+        .unwrap();
+    let issuer = Issuer::new(codex, semantics).unwrap();
+
+    let mut params = CreateParams::new_bitcoin_testnet(issuer.codex_id(), "Test");
+    params.global.push(NamedState {
+        name: vname!("_counter"),
+        state: StateAtom::new_verified(svnum!(0u64)),
+    });
+    params.global.push(NamedState {
+        name: vname!("_limit"),
+        state: StateAtom::new_verified(svnum!(1000u64)),
+    });
+    params.push_owned_unlocked("amount", Assignment::new_internal(Outpoint::strict_dumb(), 100u64));
+
+    let contract_path = PathBuf::from(format!("tests/data/{name}.contract"));
+    fs::remove_dir_all(&contract_path).ok();
+    fs::create_dir_all(&contract_path).unwrap();
+    let mut contract = Contract::<StockFs, PileFs<TxoSeal>>::issue(
+        issuer,
+        params.transform(noise_engine.clone()),
+        |_| Ok(contract_path.clone()),
+    )
+    .unwrap();
+
+    let mut prev = CellAddr::new(contract.articles().genesis_opid(), 0);
+    for counter in 1..=updates {
+        let seal = WTxoSeal::vout_no_fallback(Vout::from_u32(0), noise_engine.clone(), counter);
+        let mut params = CallParams {
+            core: CoreParams { method: vname!("transfer"), global: none!(), owned: none!() },
+            using: none!(),
+            reading: none!(),
+        };
+        params.using.insert(prev, None);
+        params.core.global.push(NamedState {
+            name: vname!("_counter"),
+            state: StateAtom::new_verified(svnum!(counter)),
+        });
+        params
+            .core
+            .owned
+            .push(NamedState::new_unlocked("amount", seal.auth_token(), 100u64));
+        let op = contract.call(params, small_bmap![0 => seal]).unwrap();
+        prev = CellAddr::new(op.opid(), 0);
+    }
+    // The aggregated state is computed once the contract is loaded
+    drop(contract);
+    Contract::load(contract_path.clone(), contract_path).unwrap()
+}
+
+#[test]
+fn typed_global_state() {
+    let contract = counter_contract("TypedGlobalGenesis", 0);
+    assert_eq!(contract.global::<Amount>("_counter").unwrap(), vec![Amount(0)]);
+    assert_eq!(contract.global_latest::<Amount>("_counter").unwrap(), Amount(0));
+    assert_eq!(contract.aggregated::<Amount>("limit").unwrap(), Amount(1000));
+
+    let contract = counter_contract("TypedGlobalState", 5);
+    let mut values = contract.global::<Amount>("_counter").unwrap();
+    values.sort();
+    assert_eq!(values, (0..=5).map(Amount).collect::<Vec<_>>());
+    assert_eq!(contract.global_latest::<Amount>("_counter").unwrap(), Amount(5));
+    assert_eq!(contract.global::<Amount>("_limit").unwrap(), vec![Amount(1000)]);
+    assert_eq!(contract.global_latest::<Amount>("_limit").unwrap(), Amount(1000));
+    assert_eq!(contract.aggregated::<Amount>("limit").unwrap(), Amount(1000));
+}
+
+#[test]
+fn typed_global_mismatch() {
+    let contract = counter_contract("TypedGlobalMismatch", 1);
+
+    // Values of a different type are not decoded
+    assert!(matches!(
+        contract.global_latest::<u8>("_counter"),
+        Err(TypedStateError::TypeMismatch { ty: "u8", .. })
+    ));
+    // The declared type is a named wrapper, so even a type with the same encoding is rejected
+    assert!(matches!(
+        contract.global::<u64>("_counter"),
+        Err(TypedStateError::TypeMismatch { ty: "u64", .. })
+    ));
+    assert!(matches!(
+        contract.aggregated::<u64>("limit"),
+        Err(TypedStateError::TypeMismatch { ty: "u64", .. })
+    ));
+}
+
+#[test]
+#[cfg(feature = "export")]
+fn export_records() {
+    let contract = setup("ExportRecords");
+    let genesis_opid = contract.articles().genesis_opid();
+
+    let state = contract.state();
+    let records = contract.state_records(&state);
+    assert_eq!(records.len(), state.owned.get("amount").unwrap().len());
+    assert!(records.iter().all(|record| {
+        let rgb::ExportedState::Owned(owned) = &record.state else {
+            return false;
+        };
+        owned.status == WitnessStatus::Tentative
+            && record.height().is_none()
+            && record.witness_id.is_some()
+            && owned.assignment.seal.is_some()
+            && owned.assignment.data.to_string() == "91"
+    }));
+
+    let history = contract.history_records();
+    let ops_out = contract
+        .operations()
+        .map(|(_, op, _)| op.destructible_out.len())
+        .sum::<usize>();
+    assert_eq!(history.len(), 20 + ops_out);
+    let genesis = history
+        .iter()
+        .filter(|record| record.state.addr().opid == genesis_opid)
+        .collect::<Vec<_>>();
+    assert_eq!(genesis.len(), 20);
+    assert!(genesis.iter().all(|record| {
+        let rgb::ExportedState::Owned(owned) = &record.state else {
+            return false;
+        };
+        owned.status == WitnessStatus::Genesis
+            && record.witness_id.is_none()
+            && record.timestamp.is_some()
+            && owned.assignment.data.to_string() == "100"
+    }));
+
+    let mut csv = vec![];
+    rgb::write_csv(&records, &mut csv).unwrap();
+    let csv = String::from_utf8(csv).unwrap();
+    let mut lines = csv.lines();
+    assert_eq!(
+        lines.next().unwrap(),
+        "contractId,contractName,stateName,kind,addr,outpoint,value,status,height,witnessId,\
+         timestamp"
+    );
+    assert_eq!(lines.count(), records.len());
+
+    let mut jsonl = vec![];
+    rgb::write_jsonl(&history, &mut jsonl).unwrap();
+    let jsonl = String::from_utf8(jsonl).unwrap();
+    assert_eq!(jsonl.lines().count(), history.len());
+    assert!(jsonl
+        .lines()
+        .next()
+        .unwrap()
+        .contains(r#""contractName":"Test","stateName":"amount","kind":"owned""#));
+}
+
+#[test]
+fn query_operations() {
+    let contract = setup("QueryOperations");
+    let all = contract
+        .operations()
+        .map(|(opid, _, _)| opid)
+        .collect::<Vec<_>>();
+    assert!(!all.is_empty());
+
+    let query = OpQuery::new().page_size(3);
+    let mut cursor = OpCursor::START;
+    let mut paged = vec![];
+    loop {
+        let page = contract.query_operations(&query, cursor);
+        assert!(page.operations.len() <= 3);
+        paged.extend(page.operations.into_iter().map(|(opid, _, _)| opid));
+        let Some(next) = page.next else { break };
+        cursor = next;
+    }
+    assert_eq!(paged, all);
+
+    let select = |query: OpQuery| {
+        contract
+            .query_operations(&query, OpCursor::START)
+            .operations
+            .into_iter()
+            .map(|(opid, _, _)| opid)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(select(OpQuery::new().method("transfer")), all);
+    assert!(select(OpQuery::new().method("issue")).is_empty());
+    assert_eq!(select(OpQuery::new().status(|status| status == WitnessStatus::Tentative)), all);
+    assert!(select(OpQuery::new().status(|status| status.is_mined())).is_empty());
+    assert_eq!(select(OpQuery::new().state_name("amount")), all);
+    assert!(select(OpQuery::new().state_name("unknown")).is_empty());
+
+    let pivot = all[all.len() / 2];
+    let below = select(OpQuery::new().opids(..pivot));
+    let above = select(OpQuery::new().opids(pivot..));
+    assert!(below.iter().all(|opid| *opid < pivot));
+    assert!(above.iter().all(|opid| *opid >= pivot));
+    assert_eq!(below.len() + above.len(), all.len());
+
+    // Only the genesis defines the limit, which is not a part of the operations
+    let contract = counter_contract("QueryGlobalState", 3);
+    let select = |query: OpQuery| {
+        contract
+            .query_operations(&query, OpCursor::START)
+            .operations
+    };
+    assert_eq!(select(OpQuery::new().state_name("_counter")).len(), 3);
+    assert_eq!(select(OpQuery::new().state_name("amount")).len(), 3);
+    assert!(select(OpQuery::new().state_name("_limit")).is_empty());
+}
+
+#[test]
+fn query_operations_seek() {
+    let contract = setup("QueryOperationsSeek");
+    let all = contract
+        .operations()
+        .map(|(opid, _, _)| opid)
+        .collect::<Vec<_>>();
+    let half = all.len() / 2;
+    let query = OpQuery::new().page_size(half);
+    let page = contract.query_operations(&query, OpCursor::START);
+    assert_eq!(page.operations.len(), half);
+    let cursor = page.next.unwrap();
+    assert_eq!(cursor.position(), half as u64);
+
+    // The operations of the first page can't be read from the stash anymore, so the next page
+    // must be found without scanning them
+    let dir = PathBuf::from("tests/data/QueryOperationsSeek.contract");
+    let index = fs::read(dir.join("stash.idx")).unwrap();
+    let positions = index[10..]
+        .chunks_exact(40)
+        .map(|entry| {
+            let opid = Opid::from(<[u8; 32]>::try_from(&entry[..32]).unwrap());
+            (opid, u64::from_le_bytes(entry[32..].try_into().unwrap()))
+        })
+        .collect::<HashMap<_, _>>();
+    let mut log = OpenOptions::new()
+        .write(true)
+        .open(dir.join("stash.log"))
+        .unwrap();
+    for opid in &all[..half] {
+        log.seek(SeekFrom::Start(positions[opid])).unwrap();
+        log.write_all(&[0xFF; 32]).unwrap();
+    }
+    drop(log);
+
+    let page = contract.query_operations(&OpQuery::new(), cursor);
+    assert_eq!(page.next, None);
+    assert_eq!(
+        page.operations
+            .into_iter()
+            .map(|(opid, _, _)| opid)
+            .collect::<Vec<_>>(),
+        all[half..]
+    );
+}
+
+#[test]
+fn witness_block_info() {
+    let mut contract = setup("WitnessBlockInfo");
+    let wids = contract.witness_ids().collect::<Vec<_>>();
+    let height = NonZeroU64::new(100).unwrap();
+    let block = BlockInfo::new(Bytes32::from_byte_array([0xAA; 32]), 1_700_000_000);
+    assert!(wids
+        .iter()
+        .all(|wid| contract.witness_block(*wid).is_none()));
+
+    contract
+        .sync(
+            wids.iter()
+                .map(|wid| (*wid, WitnessInfo::mined(height, block))),
+        )
+        .unwrap();
+    assert!(wids
+        .iter()
+        .all(|wid| contract.witness_block(*wid) == Some(block)));
+
+    // Resolvers which do not provide block details keep the known ones
+    contract
+        .sync(wids.iter().map(|wid| (*wid, WitnessStatus::Mined(height))))
+        .unwrap();
+    assert!(wids
+        .iter()
+        .all(|wid| contract.witness_block(*wid) == Some(block)));
+
+    let path = PathBuf::from("tests/data/WitnessBlockInfo.contract");
+    let reloaded = Contract::<StockFs, PileFs<TxoSeal>>::load(path.clone(), path).unwrap();
+    assert!(wids
+        .iter()
+        .all(|wid| reloaded.witness_block(*wid) == Some(block)));
+    drop(reloaded);
+
+    contract
+        .sync(wids.iter().map(|wid| (*wid, WitnessStatus::Tentative)))
+        .unwrap();
+    assert!(wids
+        .iter()
+        .all(|wid| contract.witness_block(*wid).is_none()));
+
+    assert!(contract.global_history("unknown").is_empty());
+}
+
+/// Pile which doesn't index the seals by their primary component, doesn't store the block details
+/// and doesn't track the witnesses, as a third-party implementation using the default methods of
+/// the [`Pile`] trait.
+struct ScanPile(PileFs<TxoSeal>);
+
+impl Pile for ScanPile {
+    type Seal = TxoSeal;
+    type Conf = PathBuf;
+    type Error = io::Error;
+
+    fn new(conf: PathBuf) -> Result<Self, io::Error> { PileFs::new(conf).map(Self) }
+
+    fn load(conf: PathBuf) -> Result<Self, io::Error> { PileFs::load(conf).map(Self) }
+
+    fn pub_witness(&self, wid: Txid) -> Tx { self.0.pub_witness(wid) }
+
+    fn has_witness(&self, wid: Txid) -> bool { self.0.has_witness(wid) }
+
+    fn cli_witness(&self, wid: Txid) -> Anchor { self.0.cli_witness(wid) }
+
+    fn witness_status(&self, wid: Txid) -> WitnessStatus { self.0.witness_status(wid) }
+
+    fn witness_ids(&self) -> impl Iterator<Item = Txid> { self.0.witness_ids() }
+
+    fn witnesses(&self) -> impl Iterator<Item = Witness<TxoSeal>> { self.0.witnesses() }
+
+    fn op_witness_ids(&self, opid: Opid) -> impl ExactSizeIterator<Item = Txid> {
+        self.0.op_witness_ids(opid)
+    }
+
+    fn ops_by_witness_id(&self, wid: Txid) -> impl ExactSizeIterator<Item = Opid> {
+        self.0.ops_by_witness_id(wid)
+    }
+
+    fn seal(&self, addr: CellAddr) -> Option<WTxoSeal> { self.0.seal(addr) }
+
+    fn seals(&self, opid: Opid, up_to: u16) -> SmallOrdMap<u16, WTxoSeal> {
+        self.0.seals(opid, up_to)
+    }
+
+    fn op_relations(&self, opid: Opid, up_to: u16) -> OpRels<TxoSeal> {
+        self.0.op_relations(opid, up_to)
+    }
+
+    fn add_witness(
+        &mut self,
+        opid: Opid,
+        wid: Txid,
+        published: &Tx,
+        anchor: &Anchor,
+        status: WitnessStatus,
+    ) {
+        self.0.add_witness(opid, wid, published, anchor, status)
+    }
+
+    fn add_seals(&mut self, opid: Opid, seals: SmallOrdMap<u16, WTxoSeal>) {
+        self.0.add_seals(opid, seals)
+    }
+
+    fn update_witness_status(&mut self, wid: Txid, status: WitnessStatus) {
+        self.0.update_witness_status(wid, status)
+    }
+
+    fn commit_transaction(&mut self) { self.0.commit_transaction() }
+}
+
+#[test]
+fn owned_by_primary_scan() {
+    let contract = setup("OwnedByPrimaryScan");
+    let outpoints = contract
+        .state()
+        .owned
+        .get("amount")
+        .unwrap()
+        .iter()
+        .map(|owned| owned.assignment.seal.primary)
+        .collect::<BTreeSet<_>>();
+    drop(contract);
+
+    let path = PathBuf::from("tests/data/OwnedByPrimaryScan.contract");
+    let indexed = Contract::<StockFs, PileFs<TxoSeal>>::load(path.clone(), path.clone()).unwrap();
+    let scanned = Contract::<StockFs, ScanPile>::load(path.clone(), path).unwrap();
+    for outpoint in &outpoints {
+        assert!(!scanned.owned_by_primary(*outpoint).is_empty());
+        assert_eq!(scanned.owned_by_primary(*outpoint), indexed.owned_by_primary(*outpoint));
+    }
+    assert_eq!(scanned.state_by_primary(outpoints.iter().copied()), indexed.state());
+    drop(indexed);
+
+    // Block details are ignored
+    let mut scanned = scanned;
+    let wid = scanned.witness_ids().next().unwrap();
+    let height = NonZeroU64::new(100).unwrap();
+    let block = BlockInfo::new(Bytes32::from_byte_array([1; 32]), 1_700_000_000);
+    scanned
+        .sync([(wid, WitnessInfo::mined(height, block))])
+        .unwrap();
+    assert_eq!(scanned.witness(wid).unwrap().status, WitnessStatus::Mined(height));
+    assert_eq!(scanned.witness_block(wid), None);
+}
@@ -7,10 +7,16 @@ extern crate strict_types;
 
 mod utils;
 
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::num::NonZeroU64;
+use std::path::PathBuf;
 
-use bp::Tx;
-use rgb::WitnessStatus;
+use bp::seals::TxoSeal;
+use bp::{LockTime, Tx};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rgb::{CellAddr, Contract, ContractEvent, ContractState, Opid, Pile, Stock, WitnessStatus};
+use rgb_persist_fs::{PileFs, StockFs};
 use rgbcore::ContractApi;
 use single_use_seals::SealWitness;
 use strict_encoding::StrictDumb;
@@ -51,6 +57,60 @@ fn rollback_forward() {
         .unwrap();
 }
 
+#[test]
+fn rollback_forward_events() {
+    let mut contract = setup("RollbackForwardEvents");
+    let events = contract.subscribe();
+    let wid = contract.witness_ids().nth(50).unwrap();
+    let opid = contract.ops_by_witness_id(wid).next().unwrap();
+
+    contract.sync([(wid, WitnessStatus::Archived)]).unwrap();
+    let received = events.try_iter().collect::<Vec<_>>();
+    assert!(matches!(received[0], ContractEvent::WitnessStatusChanged {
+        witness_id,
+        old: WitnessStatus::Tentative,
+        new: WitnessStatus::Archived,
+        ..
+    } if witness_id == wid));
+    assert!(received.iter().any(
+        |event| matches!(event, ContractEvent::OperationRolledBack { opid: id, .. } if *id == opid)
+    ));
+
+    contract.sync([(wid, WitnessStatus::Offchain)]).unwrap();
+    let received = events.try_iter().collect::<Vec<_>>();
+    assert!(received.iter().any(
+        |event| matches!(event, ContractEvent::OperationForwarded { opid: id, .. } if *id == opid)
+    ));
+
+    // No events on idempotent sync
+    contract.sync([(wid, WitnessStatus::Offchain)]).unwrap();
+    assert_eq!(events.try_iter().count(), 0);
+}
+
+#[test]
+fn rollback_graph() {
+    let mut contract = setup("RollbackGraph");
+    let wid = contract.witness_ids().nth(50).unwrap();
+    let opid = contract.ops_by_witness_id(wid).next().unwrap();
+    contract.sync([(wid, WitnessStatus::Archived)]).unwrap();
+
+    let graph = contract.graph();
+    assert_eq!(graph.nodes.len(), contract.operations().count() + 1);
+    let node = graph.nodes.iter().find(|node| node.opid == opid).unwrap();
+    assert_eq!(node.status, Some(WitnessStatus::Archived));
+    assert_eq!(node.valid, Some(false));
+    assert!(graph.edges.iter().any(|edge| edge.to == opid));
+
+    let dot = graph.to_dot();
+    assert!(dot.starts_with("digraph"));
+    assert!(
+        dot.contains(&format!("\"{opid}\" [label=\"{opid}\\ntransfer\\narchived\", style=dashed]"))
+    );
+    let graphml = graph.to_graphml();
+    assert_eq!(graphml.matches("<node ").count(), graph.nodes.len());
+    assert_eq!(graphml.matches("<edge ").count(), graph.edges.len());
+}
+
 #[test]
 fn rbf() {
     let mut contract = setup("Rbf");
@@ -69,3 +129,289 @@ fn rbf() {
         ])
         .unwrap();
 }
+
+#[test]
+fn incremental_status() {
+    let mut contract = setup("IncrementalStatus");
+    let path = PathBuf::from("tests/data/IncrementalStatus.contract");
+    let wids = contract.witness_ids().collect::<Vec<_>>();
+    // Computes the operation statuses, which are then updated incrementally
+    let _ = contract.state();
+
+    let mut rng = StdRng::seed_from_u64(0);
+    for _ in 0..5 {
+        let changed = (0..10)
+            .map(|_| {
+                let wid = wids[rng.random_range(0..wids.len())];
+                let status = match rng.random_range(0..4) {
+                    0 => WitnessStatus::Archived,
+                    1 => WitnessStatus::Tentative,
+                    2 => WitnessStatus::Offchain,
+                    _ => WitnessStatus::Mined(NonZeroU64::new(rng.random_range(1..100)).unwrap()),
+                };
+                (wid, status)
+            })
+            .collect::<HashMap<_, _>>();
+        contract.sync(changed).unwrap();
+
+        let reloaded =
+            Contract::<StockFs, PileFs<TxoSeal>>::load(path.clone(), path.clone()).unwrap();
+        assert_eq!(contract.state(), reloaded.state());
+    }
+}
+
+/// Status of the state elements computed as before the statuses were cached: by walking all the
+/// ancestors of the operation defining the state and taking the worst status of their witnesses.
+fn ancestor_walk_statuses(
+    contract: &Contract<StockFs, PileFs<TxoSeal>>,
+) -> HashMap<CellAddr, HashMap<WitnessStatus, usize>> {
+    let mut parents = HashMap::<Opid, Vec<Opid>>::new();
+    for (opid, op, _) in contract.operations() {
+        let inputs = op.destructible_in.iter().map(|input| input.addr.opid);
+        parents.insert(
+            opid,
+            op.immutable_in
+                .iter()
+                .map(|addr| addr.opid)
+                .chain(inputs)
+                .collect(),
+        );
+    }
+    let mut op_witnesses = HashMap::<Opid, Vec<WitnessStatus>>::new();
+    for witness in contract.witnesses() {
+        for opid in witness.opids {
+            op_witnesses.entry(opid).or_default().push(witness.status);
+        }
+    }
+    let op_status = |opid: Opid| {
+        op_witnesses
+            .get(&opid)
+            .into_iter()
+            .flatten()
+            .copied()
+            .reduce(|best, other| best.best(other))
+            .unwrap_or(WitnessStatus::Genesis)
+    };
+    let ancestors_status = |opid: Opid| {
+        let mut ancestors = HashSet::from([opid]);
+        let mut stack = vec![opid];
+        while let Some(opid) = stack.pop() {
+            for parent in parents.get(&opid).into_iter().flatten() {
+                if ancestors.insert(*parent) {
+                    stack.push(*parent);
+                }
+            }
+        }
+        ancestors
+            .into_iter()
+            .map(op_status)
+            .fold(WitnessStatus::Genesis, |worst, other| worst.worst(other))
+    };
+
+    let mut expected = HashMap::<CellAddr, HashMap<WitnessStatus, usize>>::new();
+    for (addr, _) in contract.full_state().main.owned.values().flatten() {
+        let status = ancestors_status(addr.opid);
+        let statuses = expected.entry(*addr).or_default();
+        match op_witnesses.get(&addr.opid) {
+            None => *statuses.entry(status).or_default() += 1,
+            Some(witnesses) => {
+                for witness in witnesses {
+                    *statuses.entry(status.worst(*witness)).or_default() += 1;
+                }
+            }
+        }
+    }
+    expected
+}
+
+#[test]
+fn state_statuses() {
+    let mut contract = setup("StateStatuses");
+    let mut rng = StdRng::seed_from_u64(1);
+
+    // Some operations get multiple witnesses, as with RBF
+    let opids = contract
+        .operations()
+        .map(|(opid, ..)| opid)
+        .collect::<Vec<_>>();
+    for no in 0..10 {
+        let opid = opids[rng.random_range(0..opids.len())];
+        let mut tx = Tx::strict_dumb();
+        tx.lock_time = LockTime::from_consensus_u32(1000 + no);
+        contract.apply_witness(opid, SealWitness::new(tx, strict_dumb!()));
+    }
+    let wids = contract.witness_ids().collect::<Vec<_>>();
+
+    for _ in 0..10 {
+        let changed = (0..rng.random_range(1..20))
+            .map(|_| {
+                let wid = wids[rng.random_range(0..wids.len())];
+                let status = match rng.random_range(0..4) {
+                    0 => WitnessStatus::Archived,
+                    1 => WitnessStatus::Tentative,
+                    2 => WitnessStatus::Offchain,
+                    _ => WitnessStatus::Mined(NonZeroU64::new(rng.random_range(1..100)).unwrap()),
+                };
+                (wid, status)
+            })
+            .collect::<BTreeMap<_, _>>();
+        contract.sync(changed).unwrap();
+
+        let mut statuses = HashMap::<CellAddr, HashMap<WitnessStatus, usize>>::new();
+        for owned in contract.state().owned.values().flatten() {
+            *statuses
+                .entry(owned.addr)
+                .or_default()
+                .entry(owned.status)
+                .or_default() += 1;
+        }
+        assert_eq!(statuses, ancestor_walk_statuses(&contract));
+    }
+}
+
+#[test]
+#[cfg(not(feature = "async"))]
+fn block_reorg() {
+    use std::cell::Cell;
+    use std::convert::Infallible;
+    use std::fs;
+
+    use amplify::Bytes32;
+    use rgb::{BlockInfo, Consensus, Contracts, WitnessInfo};
+    use rgb_persist_fs::StockpileDir;
+
+    let contract = setup("BlockReorg");
+    let contract_id = contract.contract_id();
+    let wids = contract.witness_ids().collect::<Vec<_>>();
+    drop(contract);
+
+    let dir = PathBuf::from("tests/data/BlockReorg.stockpile");
+    fs::remove_dir_all(&dir).ok();
+    fs::create_dir_all(&dir).unwrap();
+    fs::rename(
+        "tests/data/BlockReorg.contract",
+        dir.join(format!("Test.{contract_id:-}.contract")),
+    )
+    .unwrap();
+    let stockpile = StockpileDir::<TxoSeal>::load(dir, Consensus::Bitcoin, true).unwrap();
+    let mut contracts = Contracts::<_, HashMap<_, _>, HashMap<_, _>>::load(stockpile);
+
+    let height = NonZeroU64::new(100).unwrap();
+    let block_a = BlockInfo::new(Bytes32::from_byte_array([0xAA; 32]), 1_700_000_000);
+    let block_b = BlockInfo::new(Bytes32::from_byte_array([0xBB; 32]), 1_700_000_600);
+    let resolved = Cell::new(0usize);
+    let resolver = |block: BlockInfo| {
+        let resolved = &resolved;
+        move |_| -> Result<_, Infallible> {
+            resolved.set(resolved.get() + 1);
+            Ok(WitnessInfo::mined(height, block))
+        }
+    };
+    let best_chain = |block: BlockInfo| move |_| -> Result<_, Infallible> { Ok(Some(block.hash)) };
+
+    contracts
+        .update_witnesses_with_blocks(resolver(block_a), best_chain(block_a), 100, 6)
+        .unwrap();
+    assert_eq!(resolved.get(), wids.len());
+
+    // Deeply mined witnesses in a block which is still in the best chain are not re-resolved
+    resolved.set(0);
+    contracts
+        .update_witnesses_with_blocks(resolver(block_a), best_chain(block_a), 200, 6)
+        .unwrap();
+    assert_eq!(resolved.get(), 0);
+
+    // The block at the same height was replaced
+    contracts
+        .update_witnesses_with_blocks(resolver(block_b), best_chain(block_b), 200, 6)
+        .unwrap();
+    assert_eq!(resolved.get(), wids.len());
+
+    resolved.set(0);
+    contracts
+        .update_witnesses_with_blocks(resolver(block_b), best_chain(block_b), 200, 6)
+        .unwrap();
+    assert_eq!(resolved.get(), 0);
+
+    let state = contracts.contract_state(contract_id);
+    assert!(state
+        .owned
+        .values()
+        .flatten()
+        .all(|owned| owned.status == WitnessStatus::Mined(height)));
+}
+
+#[test]
+#[cfg(not(feature = "async"))]
+fn batched_resolver() {
+    use std::cell::RefCell;
+    use std::convert::Infallible;
+    use std::fs;
+
+    use bp::Txid;
+    use rgb::{Consensus, Contracts, WitnessInfo, WitnessResolver};
+    use rgb_persist_fs::StockpileDir;
+
+    struct BatchResolver<'a>(&'a RefCell<Vec<Vec<Txid>>>);
+
+    impl WitnessResolver<Txid> for BatchResolver<'_> {
+        type Error = Infallible;
+
+        fn resolve_witnesses(
+            &self,
+            wids: &[Txid],
+        ) -> Result<HashMap<Txid, WitnessInfo>, Self::Error> {
+            self.0.borrow_mut().push(wids.to_vec());
+            let height = NonZeroU64::new(100).unwrap();
+            Ok(wids
+                .iter()
+                .map(|wid| (*wid, WitnessStatus::Mined(height).into()))
+                .collect())
+        }
+    }
+
+    let contract = setup("BatchResolver");
+    let contract_id = contract.contract_id();
+    let mut wids = contract.witness_ids().collect::<Vec<_>>();
+    drop(contract);
+
+    let dir = PathBuf::from("tests/data/BatchResolver.stockpile");
+    fs::remove_dir_all(&dir).ok();
+    fs::create_dir_all(&dir).unwrap();
+    fs::rename(
+        "tests/data/BatchResolver.contract",
+        dir.join(format!("Test.{contract_id:-}.contract")),
+    )
+    .unwrap();
+    let stockpile = StockpileDir::<TxoSeal>::load(dir, Consensus::Bitcoin, true).unwrap();
+    let mut contracts = Contracts::<_, HashMap<_, _>, HashMap<_, _>>::load(stockpile);
+
+    let calls = RefCell::new(vec![]);
+    contracts
+        .update_witnesses(BatchResolver(&calls), 100, 6)
+        .unwrap();
+
+    let mut calls = calls.into_inner();
+    assert_eq!(calls.len(), 1);
+    wids.sort();
+    calls[0].sort();
+    assert_eq!(calls[0], wids);
+
+    let state = contracts.contract_state(contract_id);
+    assert!(state
+        .owned
+        .values()
+        .flatten()
+        .all(|owned| owned.status.is_mined()));
+}
+
+#[test]
+fn contract_is_sync() {
+    fn assert_sync<T: Sync>() {}
+    // The contract can be shared between threads whenever its persistence can
+    #[allow(dead_code)]
+    fn contract<S: Stock + Sync, P: Pile<Seal = TxoSeal> + Sync>() {
+        assert_sync::<Contract<S, P>>();
+    }
+    assert_sync::<ContractState<TxoSeal>>();
+}
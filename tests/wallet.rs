@@ -0,0 +1,331 @@
+#![cfg(all(not(target_arch = "wasm32"), not(feature = "async")))]
+
+#[macro_use]
+extern crate amplify;
+#[macro_use]
+extern crate strict_types;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::convert::Infallible;
+use std::fs;
+use std::num::NonZeroU64;
+use std::path::PathBuf;
+
+use amplify::confinement::Confined;
+use amplify::Bytes32;
+use bp::seals::{Anchor, TxoSeal, WTxoSeal};
+use bp::{LockTime, Outpoint, Tx, Txid, Vout, WPubkeyHash};
+use commit_verify::{Digest, Sha256};
+use hypersonic::CallParams;
+use rgb::invoice::bp::{Address, AddressNetwork, AddressPayload};
+use rgb::popls::bp::{
+    Balance, HistoryQuery, RgbWallet, TransferDirection, TransferEntry, WalletProvider,
+};
+use rgb::{
+    Assignment, AuthToken, CellAddr, Consensus, ContractId, Contracts, CoreParams, CreateParams,
+    EitherSeal, Issuer, NamedState, Opid, WitnessResolver, WitnessStatus,
+};
+use rgb_persist_fs::StockpileDir;
+use rgbcore::RgbSealDef;
+use strict_encoding::StrictDumb;
+
+/// Wallet which owns a fixed set of UTXOs and resolves the witness statuses from a map, without
+/// any blockchain behind.
+#[derive(Default)]
+struct TestWallet {
+    utxos: BTreeSet<Outpoint>,
+    seals: BTreeMap<AuthToken, WTxoSeal>,
+    statuses: HashMap<Txid, WitnessStatus>,
+    height: u64,
+    nonce: u64,
+}
+
+impl WalletProvider for TestWallet {
+    type Error = Infallible;
+
+    fn has_utxo(&self, outpoint: Outpoint) -> bool { self.utxos.contains(&outpoint) }
+
+    fn utxos(&self) -> impl Iterator<Item = Outpoint> { self.utxos.iter().copied() }
+
+    fn update_utxos(&mut self) -> Result<(), Self::Error> { Ok(()) }
+
+    fn register_seal(&mut self, seal: WTxoSeal) { self.seals.insert(seal.auth_token(), seal); }
+
+    fn resolve_seals(
+        &self,
+        seals: impl Iterator<Item = AuthToken>,
+    ) -> impl Iterator<Item = WTxoSeal> {
+        seals.filter_map(|auth| self.seals.get(&auth).copied())
+    }
+
+    fn noise_seed(&self) -> Bytes32 { Bytes32::from([0xAB; 32]) }
+
+    fn next_address(&mut self) -> Address {
+        let payload = AddressPayload::Wpkh(WPubkeyHash::from([self.next_nonce() as u8; 20]));
+        Address::new(payload, AddressNetwork::Regtest)
+    }
+
+    fn next_nonce(&mut self) -> u64 {
+        self.nonce += 1;
+        self.nonce
+    }
+
+    fn txid_resolver(&self) -> impl WitnessResolver<Txid, Error = Self::Error> {
+        let statuses = self.statuses.clone();
+        move |txid| -> Result<_, Infallible> {
+            Ok(statuses
+                .get(&txid)
+                .copied()
+                .unwrap_or(WitnessStatus::Archived))
+        }
+    }
+
+    fn block_resolver(&self) -> impl Fn(u64) -> Result<Option<Bytes32>, Self::Error> {
+        |_| Ok(None)
+    }
+
+    fn last_block_height(&self) -> Result<u64, Self::Error> { Ok(self.height) }
+
+    fn broadcast(&mut self, _: &Tx, _: Option<(Vout, u32, u32)>) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+type Wallet = RgbWallet<TestWallet, StockpileDir<TxoSeal>>;
+
+/// Outpoint of the wallet UTXO receiving the genesis state.
+fn funding() -> Outpoint { Outpoint::new(Txid::from([0x01; 32]), 0u32) }
+
+/// Outpoint of the other party receiving the genesis state.
+fn external() -> Outpoint { Outpoint::new(Txid::from([0x02; 32]), 0u32) }
+
+/// Creates a wallet owning the [`funding`] UTXO and issues a contract assigning 100 to this UTXO
+/// and 40 to the [`external`] one.
+fn setup(name: &str) -> (Wallet, ContractId) {
+    let dir = PathBuf::from(format!("tests/data/{name}.stockpile"));
+    fs::remove_dir_all(&dir).ok();
+    fs::create_dir_all(&dir).unwrap();
+    let stockpile = StockpileDir::load(dir, Consensus::Bitcoin, true).unwrap();
+    let mut contracts = Contracts::load(stockpile);
+    let issuer = Issuer::load("tests/data/Test.issuer", |_, _, _| -> Result<_, Infallible> {
+        unreachable!()
+    })
+    .unwrap();
+    let codex_id = contracts.import_issuer(issuer).unwrap();
+
+    let wallet = TestWallet { utxos: bset![funding()], ..default!() };
+    let mut rgb = RgbWallet::with_components(wallet, contracts);
+    let mut params = CreateParams::new_bitcoin_testnet(codex_id, "Test");
+    params.push_owned_unlocked("amount", Assignment::new_internal(funding(), 100u64));
+    params.push_owned_unlocked("amount", Assignment::new_internal(external(), 40u64));
+    let contract_id = rgb.issue(params).unwrap();
+    (rgb, contract_id)
+}
+
+/// Spends the `using` cells, assigning the `amounts` to the witness outputs with the same
+/// numbers, and includes the operation with a witness transaction, which is returned.
+///
+/// The witness outputs listed in `ours` are added to the wallet UTXOs.
+fn transfer(
+    rgb: &mut Wallet,
+    contract_id: ContractId,
+    using: impl IntoIterator<Item = CellAddr>,
+    amounts: &[u64],
+    ours: &[u32],
+    offchain: bool,
+) -> (Opid, Txid) {
+    let mut call = CallParams {
+        core: CoreParams { method: vname!("transfer"), global: none!(), owned: none!() },
+        using: none!(),
+        reading: none!(),
+    };
+    for addr in using {
+        call.using.insert(addr, None);
+    }
+    let mut seals = small_bmap![];
+    for (vout, amount) in amounts.iter().enumerate() {
+        let nonce = rgb.wallet.next_nonce();
+        let seal = WTxoSeal::vout_no_fallback(Vout::from_u32(vout as u32), Sha256::new(), nonce);
+        call.core
+            .owned
+            .push(NamedState::new_unlocked("amount", seal.auth_token(), *amount));
+        seals.insert(vout as u16, seal).unwrap();
+    }
+    let op = rgb
+        .contracts
+        .contract_call(contract_id, call, seals)
+        .unwrap();
+    let opid = op.opid();
+
+    let nonce = rgb.wallet.next_nonce();
+    let tx = Tx {
+        version: default!(),
+        inputs: Confined::from_checked(vec![]),
+        outputs: Confined::from_checked(vec![]),
+        lock_time: LockTime::from_consensus_u32(nonce as u32),
+    };
+    let txid = tx.txid();
+    if offchain {
+        rgb.contracts
+            .include_offchain(contract_id, opid, &tx, Anchor::strict_dumb())
+            .unwrap();
+    } else {
+        rgb.contracts
+            .include(contract_id, opid, &tx, Anchor::strict_dumb())
+            .unwrap();
+        rgb.wallet.statuses.insert(txid, WitnessStatus::Tentative);
+    }
+    for vout in ours {
+        rgb.wallet.utxos.insert(Outpoint::new(txid, *vout));
+    }
+    (opid, txid)
+}
+
+fn mine(rgb: &mut Wallet, txid: Txid, height: u64) {
+    let height = NonZeroU64::new(height).unwrap();
+    rgb.wallet
+        .statuses
+        .insert(txid, WitnessStatus::Mined(height));
+    rgb.wallet.height = rgb.wallet.height.max(height.get());
+}
+
+#[test]
+fn history() {
+    let (mut rgb, contract_id) = setup("WalletHistory");
+    let genesis_opid = rgb.contracts.contract_articles(contract_id).genesis_opid();
+
+    // Send 70 out of 100 to other party, keeping 30 as a change
+    let (_, tx1) =
+        transfer(&mut rgb, contract_id, [CellAddr::new(genesis_opid, 0)], &[30, 70], &[0], false);
+    rgb.wallet.utxos.remove(&funding());
+    // Receive 40 from the other party
+    let (_, tx2) =
+        transfer(&mut rgb, contract_id, [CellAddr::new(genesis_opid, 1)], &[40], &[0], false);
+    mine(&mut rgb, tx1, 10);
+    rgb.update(1).unwrap();
+
+    let summary = |entries: Vec<TransferEntry>| {
+        entries
+            .into_iter()
+            .map(|entry| {
+                (entry.direction, entry.witness_id, entry.status, entry.received, entry.sent)
+            })
+            .collect::<Vec<_>>()
+    };
+    let mined = WitnessStatus::Mined(NonZeroU64::new(10).unwrap());
+    let history = rgb.history(&HistoryQuery::default()).unwrap();
+    assert_eq!(summary(history.clone()), vec![
+        // Only the genesis state assigned to the wallet is received
+        (TransferDirection::Issue, None, WitnessStatus::Genesis, vec![svnum!(100u64)], vec![]),
+        (TransferDirection::Outgoing, Some(tx1), mined, vec![], vec![svnum!(70u64)]),
+        (
+            TransferDirection::Incoming,
+            Some(tx2),
+            WitnessStatus::Tentative,
+            vec![svnum!(40u64)],
+            vec![]
+        ),
+    ]);
+    assert!(history
+        .iter()
+        .all(|entry| entry.contract_id == contract_id && entry.state_name.as_str() == "amount"));
+    assert!(history[1..]
+        .iter()
+        .all(|entry| entry.method == Some(vname!("transfer"))));
+    assert_eq!(history[1].height(), Some(NonZeroU64::new(10).unwrap()));
+    assert_eq!(history[1].counterparties, vec![EitherSeal::Alt(Outpoint::new(tx1, 1u32))]);
+    assert!(history[2].counterparties.is_empty());
+
+    // Filtering
+    let query = HistoryQuery { direction: Some(TransferDirection::Outgoing), ..default!() };
+    assert_eq!(rgb.history(&query).unwrap(), vec![history[1].clone()]);
+    let query = HistoryQuery { contract_id: Some(contract_id), ..default!() };
+    assert_eq!(rgb.history(&query).unwrap(), history);
+    let query = HistoryQuery { state_name: Some(vname!("other")), ..default!() };
+    assert!(rgb.history(&query).unwrap().is_empty());
+
+    // Pagination
+    let query = HistoryQuery { offset: 1, limit: Some(1), ..default!() };
+    assert_eq!(rgb.history(&query).unwrap(), vec![history[1].clone()]);
+    let query = HistoryQuery { offset: 2, limit: Some(5), ..default!() };
+    assert_eq!(rgb.history(&query).unwrap(), vec![history[2].clone()]);
+    let query = HistoryQuery { offset: 3, ..default!() };
+    assert!(rgb.history(&query).unwrap().is_empty());
+
+    // Archived operations are listed only on request
+    rgb.wallet.statuses.insert(tx2, WitnessStatus::Archived);
+    rgb.update(1).unwrap();
+    assert_eq!(rgb.history(&HistoryQuery::default()).unwrap(), history[..2]);
+    let query = HistoryQuery { include_archived: true, ..default!() };
+    let archived = rgb.history(&query).unwrap();
+    assert_eq!(archived.len(), 3);
+    assert_eq!(archived[2].witness_id, Some(tx2));
+    assert_eq!(archived[2].status, WitnessStatus::Archived);
+}
+
+#[test]
+fn balance() {
+    let (mut rgb, contract_id) = setup("WalletBalance");
+    let genesis_opid = rgb.contracts.contract_articles(contract_id).genesis_opid();
+    let amounts = |values: &[u64]| {
+        values
+            .iter()
+            .map(|value| svnum!(*value))
+            .collect::<Vec<_>>()
+    };
+
+    // Only the state assigned to the wallet UTXOs is counted
+    let balance = rgb.balance(contract_id, "amount", 1).unwrap();
+    assert_eq!(balance, Balance {
+        genesis: amounts(&[100]),
+        total: amounts(&[100]),
+        ..default!()
+    });
+
+    // Send 70 out of 100 to other party, and receive 40 over an offchain witness
+    let (_, tx1) =
+        transfer(&mut rgb, contract_id, [CellAddr::new(genesis_opid, 0)], &[30, 70], &[0], false);
+    rgb.wallet.utxos.remove(&funding());
+    transfer(&mut rgb, contract_id, [CellAddr::new(genesis_opid, 1)], &[40], &[0], true);
+    let balance = rgb.balance(contract_id, "amount", 1).unwrap();
+    assert_eq!(balance, Balance {
+        tentative: amounts(&[30]),
+        offchain: amounts(&[40]),
+        total: amounts(&[70]),
+        ..default!()
+    });
+
+    // Mined state is confirmed once it gets the required number of confirmations
+    mine(&mut rgb, tx1, 10);
+    rgb.update(1).unwrap();
+    let balance = rgb.balance(contract_id, "amount", 1).unwrap();
+    assert_eq!(balance, Balance {
+        confirmed: amounts(&[30]),
+        offchain: amounts(&[40]),
+        total: amounts(&[70]),
+        ..default!()
+    });
+    let balance = rgb.balance(contract_id, "amount", 2).unwrap();
+    assert_eq!(balance, Balance {
+        unconfirmed: amounts(&[30]),
+        offchain: amounts(&[40]),
+        total: amounts(&[70]),
+        ..default!()
+    });
+    rgb.wallet.height = 11;
+    let balance = rgb.balance(contract_id, "amount", 2).unwrap();
+    assert_eq!(balance.confirmed, amounts(&[30]));
+
+    // The state assigned by an archived witness is not counted
+    rgb.wallet.statuses.insert(tx1, WitnessStatus::Archived);
+    rgb.update(1).unwrap();
+    let balance = rgb.balance(contract_id, "amount", 1).unwrap();
+    assert_eq!(balance, Balance {
+        offchain: amounts(&[40]),
+        total: amounts(&[40]),
+        ..default!()
+    });
+
+    // Unknown state names are reported as errors
+    assert!(rgb.balance(contract_id, "other", 1).is_err());
+}